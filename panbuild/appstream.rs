@@ -0,0 +1,379 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// The AppStream metadata shipped by the applications. The .appdata.xml
+// extension is the legacy name of the .metainfo.xml files.
+// See https://www.freedesktop.org/software/appstream/docs/
+const APPSTREAM_FILE_EXTENSIONS: &[&str] = &[".metainfo.xml", ".appdata.xml"];
+
+// Legacy AppStream ids were the name of the desktop file of the application.
+const LEGACY_ID_SUFFIX: &str = ".desktop";
+
+// The directories where the applications conventionally keep their AppStream
+// metadata, relative to the root of their repo.
+const APPSTREAM_DIRS: &[&str] = &["data", "share/metainfo", "share/appdata"];
+
+#[derive(Debug, Clone, Default)]
+pub struct AppStreamRelease {
+    pub version: String,
+    // The release date, in the YYYY-MM-DD format, if known.
+    pub date: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AppStreamComponent {
+    pub id: String,
+    // The type of the component, for example desktop-application or console-application.
+    pub component_type: String,
+    pub name: String,
+    pub summary: String,
+    // The description, converted from its markup to plain text.
+    pub description: String,
+    pub project_license: String,
+    // The urls of the component, with their type, for example homepage or bugtracker.
+    pub urls: Vec<(String, String)>,
+    pub releases: Vec<AppStreamRelease>,
+}
+impl AppStreamComponent {
+    pub fn parse(content: &str) -> Result<AppStreamComponent, String> {
+        let document = match roxmltree::Document::parse(content) {
+            Ok(d) => d,
+            Err(e) => return Err(format!("Could not parse AppStream metadata: {}.", e)),
+        };
+        let root = document.root_element();
+        // The application tag is used by the legacy appdata files.
+        if root.tag_name().name() != "component" && root.tag_name().name() != "application" {
+            return Err(format!("Invalid AppStream root element {}.", root.tag_name().name()));
+        }
+
+        let mut component = AppStreamComponent {
+            component_type: root.attribute("type").unwrap_or("").to_string(),
+            ..Default::default()
+        };
+        for child in root.children().filter(|n| n.is_element()) {
+            // Only keeping the untranslated values.
+            if is_translated(&child) {
+                continue;
+            }
+            match child.tag_name().name() {
+                "id" => component.id = get_text(&child),
+                "name" => component.name = get_text(&child),
+                "summary" => component.summary = get_text(&child),
+                "description" => component.description = get_description(&child),
+                "project_license" => component.project_license = get_text(&child),
+                "url" => {
+                    let url_type = child.attribute("type").unwrap_or("homepage").to_string();
+                    component.urls.push((url_type, get_text(&child)));
+                }
+                "releases" => {
+                    for release in child.children().filter(|n| n.has_tag_name("release")) {
+                        let version = match release.attribute("version") {
+                            Some(v) => v.to_string(),
+                            None => continue,
+                        };
+                        let date = match (release.attribute("date"), release.attribute("timestamp")) {
+                            (Some(d), _) => d.split('T').next().unwrap_or(d).to_string(),
+                            (None, Some(t)) => t.parse::<i64>().map(timestamp_to_date).unwrap_or_default(),
+                            (None, None) => "".to_string(),
+                        };
+                        component.releases.push(AppStreamRelease { version, date });
+                    }
+                }
+                _ => continue,
+            }
+        }
+        if component.id.is_empty() {
+            return Err("The AppStream component does not have an id.".to_string());
+        }
+        Ok(component)
+    }
+
+    pub fn load_from_file(path: &Path) -> Result<AppStreamComponent, String> {
+        let content = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) => return Err(format!("Could not read AppStream file {}: {}.", path.display(), e)),
+        };
+        AppStreamComponent::parse(&content)
+    }
+
+    // Gets the application id declared by the component, without the
+    // .desktop suffix of the legacy ids.
+    pub fn get_app_id(&self) -> &str {
+        self.id.strip_suffix(LEGACY_ID_SUFFIX).unwrap_or(&self.id)
+    }
+
+    pub fn get_release_date(&self, version: &str) -> Option<&str> {
+        let release = self.releases.iter().find(|r| r.version == version)?;
+        match release.date.is_empty() {
+            true => None,
+            false => Some(&release.date),
+        }
+    }
+
+    pub fn to_software_project(&self) -> crate::projects::SoftwareProject {
+        let mut project = crate::projects::SoftwareProject {
+            id: self.get_app_id().to_string(),
+            name: self.name.to_string(),
+            summary: self.summary.to_string(),
+            description: self.description.to_string(),
+            ..Default::default()
+        };
+        for (url_type, url) in &self.urls {
+            // The other urls, like the donation or translation urls, are not about the project itself.
+            let is_project_url = url_type == "homepage" || url_type == "bugtracker" || url_type == "vcs-browser";
+            if is_project_url && !url.is_empty() && !project.web_urls.contains(url) {
+                project.web_urls.push(url.to_string());
+            }
+        }
+        if !self.project_license.is_empty() {
+            project.license = Some(crate::manifests::manifest::License::from_spdx_id(&self.project_license));
+        }
+        for release in &self.releases {
+            if !project.versions.contains(&release.version) {
+                project.versions.push(release.version.to_string());
+            }
+        }
+        project
+    }
+}
+
+pub fn is_appstream_file(path: &Path) -> bool {
+    let file_name = match path.file_name().and_then(|n| n.to_str()) {
+        Some(n) => n,
+        None => return false,
+    };
+    APPSTREAM_FILE_EXTENSIONS.iter().any(|e| file_name.ends_with(e))
+}
+
+// Finds and parses all the AppStream files of a directory. The files that cannot be parsed are ignored.
+// The hidden directories are skipped, since they contain the git objects or the state of the
+// build tools, like the sources of the dependencies in .flatpak-builder. The symlinked
+// directories are also skipped, since they can create loops.
+pub fn find_components(dir_path: &Path) -> Vec<(PathBuf, AppStreamComponent)> {
+    let mut components: Vec<(PathBuf, AppStreamComponent)> = vec![];
+    let dir_entries = match fs::read_dir(dir_path) {
+        Ok(e) => e,
+        Err(_) => return components,
+    };
+    for entry in dir_entries.filter_map(|e| e.ok()) {
+        let entry_path = entry.path();
+        let file_type = match entry.file_type() {
+            Ok(t) => t,
+            Err(_) => continue,
+        };
+        if file_type.is_dir() {
+            let is_hidden = entry_path.file_name().and_then(|n| n.to_str()).map(|n| n.starts_with('.')).unwrap_or(false);
+            if !is_hidden {
+                components.extend(find_components(&entry_path));
+            }
+            continue;
+        }
+        if file_type.is_symlink() && entry_path.is_dir() {
+            continue;
+        }
+        if !is_appstream_file(&entry_path) {
+            continue;
+        }
+        match AppStreamComponent::load_from_file(&entry_path) {
+            Ok(c) => components.push((entry_path, c)),
+            Err(e) => log::warn!("{}", e),
+        };
+    }
+    components
+}
+
+// Finds the AppStream metadata of an application in its repo. The metadata of the vendored
+// dependencies, of the test fixtures or of the other applications of the repo is ignored,
+// so only the files named after the application or in the conventional directories are kept.
+pub fn find_app_components(repo_path: &Path, app_id: &str) -> Vec<(PathBuf, AppStreamComponent)> {
+    find_components(repo_path)
+        .into_iter()
+        .filter(|(file_path, component)| {
+            let relative_path = file_path.strip_prefix(repo_path).unwrap_or(file_path);
+            if APPSTREAM_DIRS.iter().any(|d| relative_path.starts_with(d)) {
+                return true;
+            }
+            let file_name = file_path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            let file_id = APPSTREAM_FILE_EXTENSIONS.iter().fold(file_name, |n, e| n.trim_end_matches(e));
+            is_same_app_name(file_id, app_id) || is_same_app_name(component.get_app_id(), app_id)
+        })
+        .collect()
+}
+
+// Checks if 2 application ids have the same name, which is the last part of the ids.
+// The case is ignored, since it was not always significant for legacy AppStream ids.
+fn is_same_app_name(id: &str, other_id: &str) -> bool {
+    let name = id.rsplit('.').next().unwrap_or(id);
+    let other_name = other_id.rsplit('.').next().unwrap_or(other_id);
+    !name.is_empty() && name.eq_ignore_ascii_case(other_name)
+}
+
+fn is_translated(node: &roxmltree::Node) -> bool {
+    node.attributes().iter().any(|a| a.name() == "lang")
+}
+
+fn get_text(node: &roxmltree::Node) -> String {
+    let text: String = node.descendants().filter(|n| n.is_text()).map(|n| n.text().unwrap_or("")).collect();
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+// The description is made of paragraphs and lists. The paragraphs are separated
+// by an empty line, and the list items are prefixed with a dash.
+fn get_description(node: &roxmltree::Node) -> String {
+    let mut blocks: Vec<String> = vec![];
+    for child in node.children().filter(|n| n.is_element() && !is_translated(n)) {
+        match child.tag_name().name() {
+            "p" => blocks.push(get_text(&child)),
+            "ul" | "ol" => {
+                let items: Vec<String> = child
+                    .children()
+                    .filter(|n| n.has_tag_name("li") && !is_translated(n))
+                    .map(|n| format!("- {}", get_text(&n)))
+                    .collect();
+                blocks.push(items.join("\n"));
+            }
+            _ => continue,
+        }
+    }
+    blocks.join("\n\n")
+}
+
+// Converts a unix timestamp to a YYYY-MM-DD date.
+// See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn timestamp_to_date(timestamp: i64) -> String {
+    let days = timestamp.div_euclid(86400) + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METAINFO: &str = r###"<?xml version="1.0" encoding="UTF-8"?>
+<component type="desktop-application">
+  <id>org.gnome.clocks</id>
+  <metadata_license>CC0-1.0</metadata_license>
+  <project_license>GPL-2.0-or-later</project_license>
+  <name>Clocks</name>
+  <summary>Keep track of time</summary>
+  <summary xml:lang="fr">Gardez un œil sur l’heure</summary>
+  <description>
+    <p>A simple and elegant clock application.</p>
+    <p xml:lang="fr">Une application d’horloge simple et élégante.</p>
+    <ul>
+      <li>World clocks</li>
+      <li>Alarms</li>
+    </ul>
+  </description>
+  <url type="homepage">https://wiki.gnome.org/Apps/Clocks</url>
+  <url type="bugtracker">https://gitlab.gnome.org/GNOME/gnome-clocks/issues</url>
+  <url type="donation">https://www.gnome.org/donate/</url>
+  <releases>
+    <release version="40.0" date="2021-03-20"/>
+    <release version="3.38.0" timestamp="1600387200"/>
+  </releases>
+</component>
+"###;
+
+    #[test]
+    pub fn test_parse() {
+        let component = AppStreamComponent::parse(METAINFO).unwrap();
+        assert_eq!(component.id, "org.gnome.clocks");
+        assert_eq!(component.component_type, "desktop-application");
+        assert_eq!(component.summary, "Keep track of time");
+        assert_eq!(component.description, "A simple and elegant clock application.\n\n- World clocks\n- Alarms");
+        assert_eq!(component.releases.len(), 2);
+        assert_eq!(component.get_release_date("40.0"), Some("2021-03-20"));
+        assert_eq!(component.get_release_date("3.38.0"), Some("2020-09-18"));
+        assert!(component.get_release_date("3.36.0").is_none());
+    }
+
+    #[test]
+    pub fn test_parse_invalid() {
+        assert!(AppStreamComponent::parse("<component><name>Clocks</name></component>").is_err());
+        assert!(AppStreamComponent::parse("<components/>").is_err());
+        assert!(AppStreamComponent::parse("not xml").is_err());
+    }
+
+    #[test]
+    pub fn test_get_app_id() {
+        let component = AppStreamComponent::parse("<application><id>gnome-clocks.desktop</id></application>").unwrap();
+        assert_eq!(component.get_app_id(), "gnome-clocks");
+    }
+
+    #[test]
+    pub fn test_to_software_project() {
+        let project = AppStreamComponent::parse(METAINFO).unwrap().to_software_project();
+        assert_eq!(project.name, "Clocks");
+        assert_eq!(
+            project.web_urls,
+            vec!["https://wiki.gnome.org/Apps/Clocks", "https://gitlab.gnome.org/GNOME/gnome-clocks/issues"]
+        );
+        assert_eq!(project.license, Some(crate::manifests::manifest::License::Gpl2));
+        assert_eq!(project.versions, vec!["40.0", "3.38.0"]);
+    }
+
+    #[test]
+    pub fn test_is_appstream_file() {
+        assert!(is_appstream_file(Path::new("data/org.gnome.clocks.metainfo.xml")));
+        assert!(is_appstream_file(Path::new("org.gnome.clocks.appdata.xml")));
+        assert!(!is_appstream_file(Path::new("org.gnome.clocks.desktop")));
+    }
+
+    #[test]
+    pub fn test_find_components() {
+        let repo_dir = tempfile::tempdir().unwrap();
+        let data_dir = repo_dir.path().join("data");
+        let state_dir = repo_dir.path().join(".flatpak-builder/build/glib");
+        fs::create_dir_all(&data_dir).unwrap();
+        fs::create_dir_all(&state_dir).unwrap();
+        fs::write(data_dir.join("org.gnome.clocks.metainfo.xml"), METAINFO).unwrap();
+        fs::write(state_dir.join("org.gtk.glib.metainfo.xml"), METAINFO).unwrap();
+
+        let components = find_components(repo_dir.path());
+        assert_eq!(components.len(), 1);
+        assert_eq!(components[0].0, data_dir.join("org.gnome.clocks.metainfo.xml"));
+
+        // Symlinks to a parent directory are not followed.
+        std::os::unix::fs::symlink(repo_dir.path(), data_dir.join("loop")).unwrap();
+        assert_eq!(find_components(repo_dir.path()).len(), 1);
+    }
+
+    #[test]
+    pub fn test_find_app_components() {
+        let repo_dir = tempfile::tempdir().unwrap();
+        let metainfo_paths = [
+            "data/org.gnome.clocks.metainfo.xml",
+            "build-aux/org.gnome.Clocks.Devel.appdata.xml",
+            "build-aux/clocks.appdata.xml",
+            "subprojects/libgweather/data/org.gnome.Weather.metainfo.xml",
+            "tests/fixtures/org.example.App.metainfo.xml",
+        ];
+        // The components are declaring the id that their file is named after.
+        for metainfo_path in &metainfo_paths {
+            let file_path = repo_dir.path().join(metainfo_path);
+            let file_name = file_path.file_name().unwrap().to_str().unwrap();
+            let component_id = file_name.trim_end_matches(".metainfo.xml").trim_end_matches(".appdata.xml");
+            fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+            fs::write(&file_path, METAINFO.replace("org.gnome.clocks", component_id)).unwrap();
+        }
+
+        let mut components: Vec<PathBuf> = find_app_components(repo_dir.path(), "org.gnome.Clocks").into_iter().map(|(p, _)| p).collect();
+        components.sort();
+        assert_eq!(
+            components,
+            vec![
+                repo_dir.path().join("build-aux/clocks.appdata.xml"),
+                repo_dir.path().join("data/org.gnome.clocks.metainfo.xml")
+            ]
+        );
+    }
+}
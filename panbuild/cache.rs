@@ -0,0 +1,465 @@
+// Local cache of the sources, shared across workspaces.
+//
+// Archives and files are stored by content, at `downloads/<sha256>/<file name>`.
+// Git repositories are mirrored at `git-mirrors/<reverse dns of the url>.git`.
+// The builds do not use the cache directly: the sources are copied to the state
+// directory of each workspace, using the layout expected by flatpak-builder.
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::modules::{AbstractSource, SourceType};
+
+pub const DEFAULT_DATA_DIR: &str = ".local/share/panbuild";
+pub const CACHE_SUBDIR: &str = "/cache";
+pub const DOWNLOADS_SUBDIR: &str = "/downloads";
+pub const GIT_MIRRORS_SUBDIR: &str = "/git-mirrors";
+// The directory of the git mirrors in the flatpak-builder state directory.
+const STATE_DIR_GIT_SUBDIR: &str = "git";
+// Lists the urls that a cached file was downloaded from, one per line.
+const URLS_FILE_NAME: &str = ".urls";
+
+#[derive(Debug, Default)]
+pub struct GcReport {
+    pub removed_paths: Vec<String>,
+    pub freed_bytes: u64,
+}
+
+pub fn get_data_dir_path() -> String {
+    if let Ok(path) = env::var("PB_DATA_DIR") {
+        return path.to_string();
+    }
+    if let Ok(path) = env::var("XDG_DATA_HOME") {
+        return path + "/panbuild";
+    }
+    if let Ok(path) = env::var("HOME") {
+        return path + "/" + DEFAULT_DATA_DIR;
+    }
+    DEFAULT_DATA_DIR.to_string()
+}
+
+pub fn get_cache_dir_path() -> String {
+    get_data_dir_path() + CACHE_SUBDIR
+}
+
+pub fn get_downloads_dir_path(cache_dir: &str) -> String {
+    cache_dir.to_string() + DOWNLOADS_SUBDIR
+}
+
+pub fn get_git_mirrors_dir_path(cache_dir: &str) -> String {
+    cache_dir.to_string() + GIT_MIRRORS_SUBDIR
+}
+
+fn get_cached_file_urls(cached_file_dir: &Path) -> Vec<String> {
+    match fs::read_to_string(cached_file_dir.join(URLS_FILE_NAME)) {
+        Ok(content) => content.lines().map(|l| l.to_string()).collect(),
+        Err(_) => vec![],
+    }
+}
+
+fn add_cached_file_url(cached_file_dir: &Path, url: &str) -> Result<(), String> {
+    let mut urls = get_cached_file_urls(cached_file_dir);
+    if urls.iter().any(|u| u == url) {
+        return Ok(());
+    }
+    urls.push(url.to_string());
+    match fs::write(cached_file_dir.join(URLS_FILE_NAME), urls.join("\n") + "\n") {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// Gets the cached copy of the file at the url. When the checksum is not known,
+// the file is looked up using the urls it was downloaded from.
+pub fn get_cached_file(cache_dir: &str, url: &str, sha256: Option<&str>) -> Option<PathBuf> {
+    let file_name = crate::checksums::get_file_name_from_url(url)?;
+    let downloads_dir = get_downloads_dir_path(cache_dir);
+    if let Some(sha256) = sha256 {
+        let file_path = Path::new(&downloads_dir).join(sha256.to_lowercase()).join(file_name);
+        if file_path.is_file() {
+            return Some(file_path);
+        }
+        return None;
+    }
+
+    let cached_file_dirs = match fs::read_dir(&downloads_dir) {
+        Ok(d) => d,
+        Err(_) => return None,
+    };
+    for cached_file_dir in cached_file_dirs {
+        let cached_file_dir = match cached_file_dir {
+            Ok(d) => d.path(),
+            Err(_) => continue,
+        };
+        let file_path = cached_file_dir.join(file_name);
+        if file_path.is_file() && get_cached_file_urls(&cached_file_dir).iter().any(|u| u == url) {
+            return Some(file_path);
+        }
+    }
+    None
+}
+
+// Gets the file at the url from the cache, downloading it first if needed.
+// The file is only added to the cache if it matches the checksum.
+pub fn fetch_file(cache_dir: &str, url: &str, sha256: Option<&str>, offline: bool) -> Result<PathBuf, String> {
+    if let Some(file_path) = get_cached_file(cache_dir, url, sha256) {
+        log::debug!("Using cached file {} for {}.", file_path.display(), url);
+        return Ok(file_path);
+    }
+    if offline {
+        return Err(format!("{} is not in the cache.", url));
+    }
+
+    let file_name = match crate::checksums::get_file_name_from_url(url) {
+        Some(n) => n,
+        None => return Err(format!("Could not get a file name from url {}.", url)),
+    };
+    let downloads_dir = get_downloads_dir_path(cache_dir);
+    if let Err(e) = fs::create_dir_all(&downloads_dir) {
+        return Err(format!("Could not create downloads directory {}: {}", downloads_dir, e));
+    }
+    // Downloading to a temporary file first, so that an interrupted
+    // download is not mistaken for a complete file.
+    let partial_file_path = Path::new(&downloads_dir).join(format!("{}.part", file_name));
+
+    println!("Getting file at {}", url);
+    let output = Command::new("wget")
+        .arg("-q")
+        .arg("-O")
+        .arg(&partial_file_path)
+        .arg(url)
+        .stdout(Stdio::piped())
+        .spawn();
+    let output = match output {
+        Ok(o) => o,
+        Err(e) => return Err(e.to_string()),
+    };
+    let output = match output.wait_with_output() {
+        Ok(o) => o,
+        Err(e) => return Err(e.to_string()),
+    };
+    if !output.status.success() {
+        fs::remove_file(&partial_file_path).unwrap_or(());
+        return Err(format!("Could not fetch file at {}.", url));
+    }
+
+    let checksum = crate::checksums::get_sha256(&partial_file_path)?;
+    if let Some(sha256) = sha256 {
+        if sha256.to_lowercase() != checksum {
+            fs::remove_file(&partial_file_path).unwrap_or(());
+            return Err(format!("Checksum mismatch for {}: expected sha256 {}, got {}.", url, sha256, checksum));
+        }
+    }
+    let cached_file_dir = Path::new(&downloads_dir).join(&checksum);
+    if let Err(e) = fs::create_dir_all(&cached_file_dir) {
+        return Err(e.to_string());
+    }
+    let file_path = cached_file_dir.join(file_name);
+    if let Err(e) = fs::rename(&partial_file_path, &file_path) {
+        return Err(e.to_string());
+    }
+    add_cached_file_url(&cached_file_dir, url)?;
+    Ok(file_path)
+}
+
+pub fn get_git_mirror_path(cache_dir: &str, repo_url: &str) -> PathBuf {
+    Path::new(&get_git_mirrors_dir_path(cache_dir)).join(get_git_mirror_name(repo_url))
+}
+
+// Gets the name of the mirror of a git repository. Urls that can't be converted
+// to a reverse dns, for example ssh urls, get their special characters replaced.
+fn get_git_mirror_name(repo_url: &str) -> String {
+    if repo_url.starts_with("https://") && repo_url.ends_with(".git") {
+        return format!("{}.git", crate::utils::repo_url_to_reverse_dns(repo_url));
+    }
+    let repo_url = repo_url.split("://").last().unwrap_or(repo_url).trim_end_matches('/');
+    let repo_url = repo_url.strip_suffix(".git").unwrap_or(repo_url);
+    let mirror_name: String = repo_url
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+        .collect();
+    format!("{}.git", mirror_name.trim_matches('.'))
+}
+
+// Creates or updates the mirror of a git repository.
+pub fn mirror_git_repo(cache_dir: &str, repo_url: &str, offline: bool) -> Result<PathBuf, String> {
+    let mirror_path = get_git_mirror_path(cache_dir, repo_url);
+    if mirror_path.is_dir() {
+        if offline {
+            return Ok(mirror_path);
+        }
+        println!("Updating mirror of {}", repo_url);
+        crate::git::fetch_repo(&mirror_path)?;
+        return Ok(mirror_path);
+    }
+    if offline {
+        return Err(format!("{} is not in the cache.", repo_url));
+    }
+    if let Err(e) = fs::create_dir_all(get_git_mirrors_dir_path(cache_dir)) {
+        return Err(e.to_string());
+    }
+    println!("Mirroring repo {}", repo_url);
+    if let Err(e) = crate::git::mirror_repo(repo_url, &mirror_path) {
+        fs::remove_dir_all(&mirror_path).unwrap_or(());
+        return Err(e);
+    }
+    Ok(mirror_path)
+}
+
+// Makes sure that a source is available in the cache. When offline, the
+// source is only checked to be in the cache.
+pub fn fetch_source(cache_dir: &str, source: &AbstractSource, offline: bool) -> Result<(), String> {
+    if source.url.is_empty() {
+        return Ok(());
+    }
+    if source.source_type == SourceType::Git {
+        let mirror_path = mirror_git_repo(cache_dir, &source.url, offline)?;
+        if let Some(commit) = &source.commit {
+            if !crate::git::has_commit(&mirror_path, commit) {
+                return Err(format!("Commit {} was not found in {}.", commit, source.url));
+            }
+        }
+        return Ok(());
+    }
+    if source.source_type.is_archive() || source.source_type == SourceType::File {
+        fetch_file(cache_dir, &source.url, source.sha256.as_deref(), offline)?;
+        return Ok(());
+    }
+    log::debug!("Not caching source {} of type {:?}.", source.url, source.source_type);
+    Ok(())
+}
+
+// Gets the name of the mirror of a git repository in the flatpak-builder state
+// directory. flatpak-builder replaces the slashes of the url with underscores,
+// except for the first run of slashes, which is removed.
+fn get_state_dir_git_mirror_name(repo_url: &str) -> String {
+    let mut mirror_name = String::new();
+    let mut saw_slash = false;
+    let mut saw_after_slash = false;
+    for c in repo_url.chars() {
+        if c == '/' {
+            saw_slash = true;
+            if saw_after_slash {
+                mirror_name.push('_');
+            }
+            continue;
+        }
+        if saw_slash {
+            saw_after_slash = true;
+        }
+        mirror_name.push(c);
+    }
+    mirror_name
+}
+
+// Copies the cached sources to a flatpak-builder state directory, so that the
+// build does not have to download them again. The sources missing from the
+// cache are left for flatpak-builder to download.
+pub fn populate_state_dir(cache_dir: &str, sources: &[AbstractSource], state_dir: &Path) -> Result<(), String> {
+    for source in sources {
+        if source.url.is_empty() {
+            continue;
+        }
+        if source.source_type == SourceType::Git {
+            let mirror_path = get_git_mirror_path(cache_dir, &source.url);
+            if !mirror_path.is_dir() {
+                log::debug!("{} is not in the cache.", source.url);
+                continue;
+            }
+            let state_dir_mirror_path = state_dir.join(STATE_DIR_GIT_SUBDIR).join(get_state_dir_git_mirror_name(&source.url));
+            if state_dir_mirror_path.is_dir() {
+                crate::git::fetch_mirror_from(&state_dir_mirror_path, &mirror_path)?;
+                continue;
+            }
+            if let Err(e) = fs::create_dir_all(state_dir.join(STATE_DIR_GIT_SUBDIR)) {
+                return Err(e.to_string());
+            }
+            if let Err(e) = crate::git::mirror_repo_from(&source.url, &mirror_path, &state_dir_mirror_path) {
+                fs::remove_dir_all(&state_dir_mirror_path).unwrap_or(());
+                return Err(e);
+            }
+            continue;
+        }
+        if !source.source_type.is_archive() && source.source_type != SourceType::File {
+            continue;
+        }
+        // flatpak-builder only looks up the downloads by checksum.
+        let sha256 = match &source.sha256 {
+            Some(s) => s.to_lowercase(),
+            None => continue,
+        };
+        let cached_file_path = match get_cached_file(cache_dir, &source.url, Some(&sha256)) {
+            Some(p) => p,
+            None => {
+                log::debug!("{} is not in the cache.", source.url);
+                continue;
+            }
+        };
+        let state_dir_file_dir = state_dir.join(DOWNLOADS_SUBDIR.trim_start_matches('/')).join(&sha256);
+        let state_dir_file_path = state_dir_file_dir.join(cached_file_path.file_name().unwrap());
+        if state_dir_file_path.is_file() {
+            continue;
+        }
+        if let Err(e) = fs::create_dir_all(&state_dir_file_dir) {
+            return Err(e.to_string());
+        }
+        // Hard links avoid duplicating the archives, but are not possible
+        // across file systems.
+        if fs::hard_link(&cached_file_path, &state_dir_file_path).is_err() {
+            if let Err(e) = fs::copy(&cached_file_path, &state_dir_file_path) {
+                return Err(format!("Could not copy {}: {}", cached_file_path.display(), e));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn get_path_size(path: &Path) -> u64 {
+    if path.is_file() {
+        return fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    }
+    let entries = match fs::read_dir(path) {
+        Ok(e) => e,
+        Err(_) => return 0,
+    };
+    entries.filter_map(|e| e.ok()).map(|e| get_path_size(&e.path())).sum()
+}
+
+fn remove_cache_path(path: &Path, report: &mut GcReport) {
+    let size = get_path_size(path);
+    let result = if path.is_dir() { fs::remove_dir_all(path) } else { fs::remove_file(path) };
+    match result {
+        Ok(_) => {
+            report.removed_paths.push(path.display().to_string());
+            report.freed_bytes += size;
+        }
+        Err(e) => log::warn!("Could not remove {}: {}", path.display(), e),
+    }
+}
+
+// Removes the cached sources that are not used anymore, as well as the partial downloads.
+pub fn gc(cache_dir: &str, used_sources: &[AbstractSource]) -> GcReport {
+    let mut report = GcReport::default();
+
+    let used_checksums: Vec<String> = used_sources.iter().filter_map(|s| s.sha256.as_ref()).map(|c| c.to_lowercase()).collect();
+    let used_urls: Vec<&String> = used_sources.iter().map(|s| &s.url).collect();
+    if let Ok(cached_file_dirs) = fs::read_dir(get_downloads_dir_path(cache_dir)) {
+        for cached_file_dir in cached_file_dirs.filter_map(|e| e.ok()).map(|e| e.path()) {
+            if !cached_file_dir.is_dir() {
+                // Partial downloads.
+                remove_cache_path(&cached_file_dir, &mut report);
+                continue;
+            }
+            let checksum = cached_file_dir.file_name().unwrap().to_str().unwrap_or("").to_string();
+            if used_checksums.contains(&checksum) {
+                continue;
+            }
+            if get_cached_file_urls(&cached_file_dir).iter().any(|u| used_urls.contains(&u)) {
+                continue;
+            }
+            remove_cache_path(&cached_file_dir, &mut report);
+        }
+    }
+
+    let used_mirror_paths: Vec<PathBuf> = used_sources
+        .iter()
+        .filter(|s| s.source_type == SourceType::Git)
+        .map(|s| get_git_mirror_path(cache_dir, &s.url))
+        .collect();
+    if let Ok(mirror_paths) = fs::read_dir(get_git_mirrors_dir_path(cache_dir)) {
+        for mirror_path in mirror_paths.filter_map(|e| e.ok()).map(|e| e.path()) {
+            if !used_mirror_paths.contains(&mirror_path) {
+                remove_cache_path(&mirror_path, &mut report);
+            }
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add_cached_file(cache_dir: &str, sha256: &str, file_name: &str, url: &str) {
+        let cached_file_dir = Path::new(&get_downloads_dir_path(cache_dir)).join(sha256);
+        fs::create_dir_all(&cached_file_dir).unwrap();
+        fs::write(cached_file_dir.join(file_name), "content").unwrap();
+        add_cached_file_url(&cached_file_dir, url).unwrap();
+    }
+
+    #[test]
+    pub fn test_cached_files_and_gc() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let cache_dir = data_dir.path().display().to_string() + CACHE_SUBDIR;
+
+        add_cached_file(&cache_dir, "abc", "v1.0.tar.gz", "https://example.com/foo/v1.0.tar.gz");
+        add_cached_file(&cache_dir, "def", "v1.0.tar.gz", "https://example.com/bar/v1.0.tar.gz");
+        fs::write(Path::new(&get_downloads_dir_path(&cache_dir)).join("baz.tar.gz.part"), "partial").unwrap();
+
+        let file_path = get_cached_file(&cache_dir, "https://example.com/bar/v1.0.tar.gz", None).unwrap();
+        assert!(file_path.ends_with("def/v1.0.tar.gz"));
+        assert!(get_cached_file(&cache_dir, "https://example.com/foo/v1.0.tar.gz", Some("ABC")).is_some());
+        assert!(get_cached_file(&cache_dir, "https://example.com/foo/v1.0.tar.gz", Some("123")).is_none());
+        assert!(get_cached_file(&cache_dir, "https://example.com/baz/v1.0.tar.gz", None).is_none());
+        assert!(fetch_file(&cache_dir, "https://example.com/baz/v1.0.tar.gz", None, true).is_err());
+
+        let used_sources = vec![AbstractSource {
+            url: "https://example.com/foo/v1.0.tar.gz".to_string(),
+            source_type: SourceType::Tarball,
+            ..Default::default()
+        }];
+        let report = gc(&cache_dir, &used_sources);
+        assert_eq!(report.removed_paths.len(), 2);
+        assert!(get_cached_file(&cache_dir, "https://example.com/foo/v1.0.tar.gz", None).is_some());
+        assert!(get_cached_file(&cache_dir, "https://example.com/bar/v1.0.tar.gz", None).is_none());
+    }
+
+    #[test]
+    pub fn test_populate_state_dir() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let cache_dir = data_dir.path().display().to_string() + CACHE_SUBDIR;
+        add_cached_file(&cache_dir, "abc", "v1.0.tar.gz", "https://example.com/foo/v1.0.tar.gz");
+
+        let sources = vec![
+            AbstractSource {
+                url: "https://example.com/foo/v1.0.tar.gz".to_string(),
+                source_type: SourceType::Tarball,
+                sha256: Some("ABC".to_string()),
+                ..Default::default()
+            },
+            AbstractSource {
+                url: "https://example.com/bar/v1.0.tar.gz".to_string(),
+                source_type: SourceType::Tarball,
+                sha256: Some("def".to_string()),
+                ..Default::default()
+            },
+        ];
+        let state_dir = data_dir.path().join(".flatpak-builder");
+        populate_state_dir(&cache_dir, &sources, &state_dir).unwrap();
+        assert!(state_dir.join("downloads/abc/v1.0.tar.gz").is_file());
+        assert!(!state_dir.join("downloads/def").exists());
+        // Populating the state directory again is a no-op.
+        populate_state_dir(&cache_dir, &sources, &state_dir).unwrap();
+    }
+
+    #[test]
+    pub fn test_get_git_mirror_name() {
+        assert_eq!(get_git_mirror_name("https://github.com/louib/panbuild.git"), "com.github.louib.panbuild.git");
+        assert_eq!(get_git_mirror_name("git@github.com:louib/panbuild.git"), "git_github.com_louib_panbuild.git");
+        assert_eq!(get_git_mirror_name("https://git.sr.ht/~louib/panbuild"), "git.sr.ht__louib_panbuild.git");
+    }
+
+    #[test]
+    pub fn test_get_state_dir_git_mirror_name() {
+        assert_eq!(
+            get_state_dir_git_mirror_name("https://github.com/louib/panbuild.git"),
+            "https:github.com_louib_panbuild.git"
+        );
+        assert_eq!(
+            get_state_dir_git_mirror_name("git@github.com:louib/panbuild.git"),
+            "git@github.com:louibpanbuild.git"
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+// The result of checking the checksums of the sources of a manifest.
+#[derive(Debug, Default)]
+pub struct ChecksumReport {
+    // The urls of the sources that got their missing checksum filled.
+    pub filled: Vec<String>,
+    // The urls of the sources without a checksum, for which
+    // no local file was available to compute it.
+    pub missing: Vec<String>,
+    // The sources for which the checksum does not match the local file.
+    pub mismatches: Vec<String>,
+}
+
+// A local directory containing copies of the source archives, for
+// example a mirror of a distribution's sources. The files are stored
+// at <mirror dir>/<host>/<path of the url>, which is the layout
+// created by `wget --mirror`.
+pub fn get_mirror_dir_path() -> Option<String> {
+    match env::var("PB_MIRROR_DIR_PATH") {
+        Ok(path) => Some(path.to_string()),
+        Err(_) => None,
+    }
+}
+
+pub fn get_sha256(file_path: &Path) -> Result<String, String> {
+    let mut file = match fs::File::open(file_path) {
+        Ok(f) => f,
+        Err(e) => return Err(format!("Could not open file {}: {}", file_path.display(), e)),
+    };
+    let mut hasher = Sha256::new();
+    if let Err(e) = io::copy(&mut file, &mut hasher) {
+        return Err(format!("Could not read file {}: {}", file_path.display(), e));
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+pub fn get_file_name_from_url(url: &str) -> Option<&str> {
+    let url = url.split(['?', '#']).next().unwrap_or(url);
+    match url.trim_end_matches('/').rsplit('/').next() {
+        Some(n) if !n.is_empty() && !n.contains(':') => Some(n),
+        _ => None,
+    }
+}
+
+// Finds a local copy of the file at the url, either in the
+// source cache or in the mirror directory.
+pub fn find_local_file(url: &str, checksum: Option<&str>) -> Option<PathBuf> {
+    let cache_dir = crate::cache::get_cache_dir_path();
+    if let Some(file_path) = crate::cache::get_cached_file(&cache_dir, url, checksum) {
+        return Some(file_path);
+    }
+    if let Some(file_path) = crate::cache::get_cached_file(&cache_dir, url, None) {
+        return Some(file_path);
+    }
+    let mirror_dir = get_mirror_dir_path()?;
+    let file_path = get_mirror_file_path(Path::new(&mirror_dir), url)?;
+    if file_path.is_file() {
+        return Some(file_path);
+    }
+    None
+}
+
+// Gets the path of the file at the url in a mirror directory. The full url
+// is used, since different projects often use the same file names.
+pub fn get_mirror_file_path(mirror_dir: &Path, url: &str) -> Option<PathBuf> {
+    let url = url.split(['?', '#']).next().unwrap_or(url);
+    let (_, url_path) = url.split_once("://")?;
+    let mut file_path = mirror_dir.to_path_buf();
+    for part in url_path.split('/') {
+        if part.is_empty() || part == "." {
+            continue;
+        }
+        // The file must stay inside the mirror directory.
+        if part == ".." {
+            return None;
+        }
+        file_path.push(part);
+    }
+    if file_path == mirror_dir {
+        return None;
+    }
+    Some(file_path)
+}
+
+// Checks the checksum of the source at the url against a local copy of the source.
+// When fix is true, missing sources are downloaded and missing checksums are filled.
+pub fn check_source_checksum(url: &str, checksum: &mut Option<String>, fix: bool, report: &mut ChecksumReport) {
+    let local_file_path = match find_local_file(url, checksum.as_deref()) {
+        Some(p) => Some(p),
+        None if fix => match crate::cache::fetch_file(&crate::cache::get_cache_dir_path(), url, None, false) {
+            Ok(p) => Some(p),
+            Err(e) => {
+                log::warn!("{}", e);
+                None
+            }
+        },
+        None => None,
+    };
+    let local_file_path = match local_file_path {
+        Some(p) => p,
+        None => {
+            if checksum.is_none() {
+                report.missing.push(url.to_string());
+            }
+            return;
+        }
+    };
+
+    let local_checksum = match get_sha256(&local_file_path) {
+        Ok(c) => c,
+        Err(e) => {
+            log::warn!("{}", e);
+            if checksum.is_none() {
+                report.missing.push(url.to_string());
+            }
+            return;
+        }
+    };
+    match checksum {
+        Some(c) => {
+            if c.to_lowercase() != local_checksum {
+                report.mismatches.push(format!(
+                    "{}: expected sha256 {}, got {} from {}.",
+                    url,
+                    c,
+                    local_checksum,
+                    local_file_path.display()
+                ));
+            }
+        }
+        None => {
+            if fix {
+                *checksum = Some(local_checksum);
+                report.filled.push(url.to_string());
+            } else {
+                report.missing.push(url.to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_get_file_name_from_url() {
+        assert_eq!(
+            get_file_name_from_url("https://ftp.gnu.org/gnu/make/make-4.3.tar.gz").unwrap(),
+            "make-4.3.tar.gz"
+        );
+        assert_eq!(get_file_name_from_url("https://example.com/archive.zip?raw=true").unwrap(), "archive.zip");
+        assert!(get_file_name_from_url("https://").is_none());
+    }
+
+    #[test]
+    pub fn test_get_mirror_file_path() {
+        let mirror_dir = Path::new("/srv/mirror");
+        assert_eq!(
+            get_mirror_file_path(mirror_dir, "https://ftp.gnu.org/gnu/make/make-4.3.tar.gz?raw=true").unwrap(),
+            Path::new("/srv/mirror/ftp.gnu.org/gnu/make/make-4.3.tar.gz")
+        );
+        assert!(get_mirror_file_path(mirror_dir, "https://example.com/../etc/passwd").is_none());
+        assert!(get_mirror_file_path(mirror_dir, "https://").is_none());
+        assert!(get_mirror_file_path(mirror_dir, "make-4.3.tar.gz").is_none());
+    }
+
+    #[test]
+    pub fn test_get_sha256() {
+        let file_path = env::temp_dir().join("panbuild-test-get-sha256.txt");
+        fs::write(&file_path, "panbuild").unwrap();
+        let checksum = get_sha256(&file_path).unwrap();
+        fs::remove_file(&file_path).unwrap();
+        assert_eq!(checksum, "5cec80f978feb52caf4eda8649f6a84230e7675e302c840b90cdcf4294d4fc26");
+    }
+}
@@ -3,17 +3,25 @@ use std::env;
 use std::fs;
 use std::path;
 
-use crate::manifests::flatpak::FlatpakModule;
-use crate::projects::SoftwareProject;
+use crate::manifests::flatpak::{FlatpakModule, ModuleRevision};
+use crate::projects::{ProjectVersion, SoftwareProject};
+use crate::version::Version;
 
 pub const DEFAULT_DB_PATH: &str = ".panbuild-db";
 pub const MODULES_DB_SUBDIR: &str = "/modules";
 pub const PROJECTS_DB_SUBDIR: &str = "/projects";
+pub const VERSIONS_DB_SUBDIR: &str = "/versions";
+pub const RUNTIMES_DB_SUBDIR: &str = "/runtimes";
+pub const MODULE_REVISIONS_DB_SUBDIR: &str = "/module-revisions";
+// The links between the modules and the projects they are built from.
+pub const MODULE_PROJECTS_DB_FILE: &str = "/module-projects.yaml";
 
 pub struct Database {
     pub projects: Vec<SoftwareProject>,
     pub modules: Vec<FlatpakModule>,
     pub indexed_projects: BTreeMap<String, SoftwareProject>,
+    // The ids of the projects of the modules, indexed by the hash of the modules.
+    pub module_projects: BTreeMap<String, String>,
 }
 impl Database {
     pub fn get_database() -> Database {
@@ -23,6 +31,15 @@ impl Database {
         if let Err(e) = fs::create_dir_all(Database::get_projects_db_path()) {
             panic!("Could not initialize database directory: {}.", e);
         }
+        if let Err(e) = fs::create_dir_all(Database::get_versions_db_path()) {
+            panic!("Could not initialize database directory: {}.", e);
+        }
+        if let Err(e) = fs::create_dir_all(Database::get_runtimes_db_path()) {
+            panic!("Could not initialize database directory: {}.", e);
+        }
+        if let Err(e) = fs::create_dir_all(Database::get_module_revisions_db_path()) {
+            panic!("Could not initialize database directory: {}.", e);
+        }
         let mut indexed_projects: BTreeMap<String, SoftwareProject> = BTreeMap::new();
         for project in Database::get_all_projects() {
             indexed_projects.insert(project.id.clone(), project);
@@ -32,6 +49,7 @@ impl Database {
             projects: Database::get_all_projects(),
             modules: Database::get_all_modules(),
             indexed_projects: indexed_projects,
+            module_projects: Database::get_module_projects(),
         }
     }
 
@@ -53,6 +71,18 @@ impl Database {
         Database::get_db_path() + PROJECTS_DB_SUBDIR
     }
 
+    pub fn get_versions_db_path() -> String {
+        Database::get_db_path() + VERSIONS_DB_SUBDIR
+    }
+
+    pub fn get_runtimes_db_path() -> String {
+        Database::get_db_path() + RUNTIMES_DB_SUBDIR
+    }
+
+    pub fn get_module_revisions_db_path() -> String {
+        Database::get_db_path() + MODULE_REVISIONS_DB_SUBDIR
+    }
+
     pub fn get_all_projects() -> Vec<SoftwareProject> {
         let projects_path = Database::get_projects_db_path();
         let projects_path = path::Path::new(&projects_path);
@@ -92,6 +122,57 @@ impl Database {
         projects
     }
 
+    pub fn get_module_projects_path() -> String {
+        Database::get_db_path() + MODULE_PROJECTS_DB_FILE
+    }
+
+    pub fn get_module_projects() -> BTreeMap<String, String> {
+        let module_projects_path = Database::get_module_projects_path();
+        let module_projects_content = match fs::read_to_string(&module_projects_path) {
+            Ok(content) => content,
+            Err(_) => return BTreeMap::new(),
+        };
+        match serde_yaml::from_str(&module_projects_content) {
+            Ok(m) => m,
+            Err(e) => {
+                log::warn!("Could not parse module projects at {}: {}.", module_projects_path, e);
+                BTreeMap::new()
+            }
+        }
+    }
+
+    // Records the project that a module is built from.
+    pub fn link_module(&mut self, module: &FlatpakModule, project_id: &str) {
+        let module_hash = module.get_hash().to_string();
+        if self.module_projects.get(&module_hash).map(|p| p == project_id).unwrap_or(false) {
+            return;
+        }
+        log::info!("Linking module {} to project {}", module.name, project_id);
+        self.module_projects.insert(module_hash, project_id.to_string());
+        let module_projects_path = Database::get_module_projects_path();
+        match fs::write(path::Path::new(&module_projects_path), serde_yaml::to_string(&self.module_projects).unwrap()) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("Could not write module projects at {}: {}", module_projects_path, e);
+            }
+        };
+    }
+
+    pub fn get_module_project_id(&self, module: &FlatpakModule) -> Option<String> {
+        self.module_projects.get(&module.get_hash().to_string()).map(|p| p.to_string())
+    }
+
+    // Gets all the modules of the db, with the project they are built from when it is known.
+    pub fn get_software_modules(&self) -> Vec<crate::modules::SoftwareModule> {
+        let mut software_modules = vec![];
+        for module in &self.modules {
+            let mut software_module = module.to_module();
+            software_module.project_id = self.get_module_project_id(module);
+            software_modules.push(software_module);
+        }
+        software_modules
+    }
+
     pub fn get_all_modules() -> Vec<FlatpakModule> {
         let modules_path = Database::get_modules_db_path();
         let modules_path = path::Path::new(&modules_path);
@@ -184,6 +265,13 @@ impl Database {
                 eprintln!("Could not write new project at {}: {}", new_project_path.to_string(), e);
             }
         };
+        let updated_project = existing_project.clone();
+        for project in &mut self.projects {
+            if project.id == updated_project.id {
+                *project = updated_project;
+                break;
+            }
+        }
     }
 
     pub fn add_project(&mut self, mut project: SoftwareProject) {
@@ -207,9 +295,13 @@ impl Database {
         self.projects.push(project);
     }
 
+    // Archived forks are not returned, since they are mostly abandoned copies of other projects.
     pub fn search_projects(&self, search_term: &str) -> Vec<&SoftwareProject> {
         let mut projects: Vec<&SoftwareProject> = vec![];
         for project in &self.projects {
+            if project.is_archived && project.is_fork {
+                continue;
+            }
             if project.name.contains(&search_term) {
                 projects.push(&project);
             }
@@ -218,7 +310,132 @@ impl Database {
     }
 
     pub fn get_project(&self, project_id: &str) -> Option<SoftwareProject> {
-        None
+        match self.indexed_projects.get(project_id) {
+            Some(p) => Some(p.clone()),
+            None => None,
+        }
+    }
+
+    pub fn remove_project(&mut self, project_id: &str) {
+        let projects_path = Database::get_projects_db_path();
+        let project_path = format!("{}/{}.yaml", projects_path, project_id);
+        log::info!("Removing project at {}", project_path);
+        let project_fs_path = path::Path::new(&project_path);
+        if project_fs_path.exists() {
+            if let Err(e) = fs::remove_file(project_fs_path) {
+                eprintln!("Could not remove project at {}: {}", project_path, e);
+                return;
+            }
+        }
+        self.indexed_projects.remove(project_id);
+        self.projects.retain(|p| p.id != project_id);
+    }
+
+    // The versions are stored in a single file per project, and are
+    // only loaded when requested.
+    pub fn get_project_versions(project_id: &str) -> Vec<ProjectVersion> {
+        let versions_path = format!("{}/{}.yaml", Database::get_versions_db_path(), project_id);
+        let versions_path = path::Path::new(&versions_path);
+        if !versions_path.is_file() {
+            return vec![];
+        }
+        let versions_content = match fs::read_to_string(versions_path) {
+            Ok(content) => content,
+            Err(e) => {
+                log::debug!("Could not read versions file for project {}: {}.", project_id, e);
+                return vec![];
+            }
+        };
+        match serde_yaml::from_str(&versions_content) {
+            Ok(v) => v,
+            Err(e) => {
+                log::debug!("Could not parse versions file for project {}: {}.", project_id, e);
+                vec![]
+            }
+        }
+    }
+
+    pub fn add_project_versions(&mut self, project_id: &str, new_versions: Vec<ProjectVersion>) {
+        if project_id.len() == 0 {
+            panic!("Trying to add versions to the db without a project id!");
+        }
+        let mut versions = Database::get_project_versions(project_id);
+        for new_version in new_versions {
+            match versions.iter().position(|v| v.name == new_version.name) {
+                Some(i) => versions[i] = new_version,
+                None => versions.push(new_version),
+            }
+        }
+        versions.sort_by(|a, b| match (Version::parse(&a.name), Version::parse(&b.name)) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            (Some(_), None) => std::cmp::Ordering::Greater,
+            (None, Some(_)) => std::cmp::Ordering::Less,
+            (None, None) => a.name.cmp(&b.name),
+        });
+
+        let versions_path = format!("{}/{}.yaml", Database::get_versions_db_path(), project_id);
+        log::info!("Writing {} versions at {}", versions.len(), versions_path);
+        match fs::write(path::Path::new(&versions_path), serde_yaml::to_string(&versions).unwrap()) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("Could not write versions at {}: {}", versions_path, e);
+            }
+        };
+    }
+
+    fn get_module_revisions_path(module_name: &str) -> String {
+        // Module names are free-form, and could contain path separators.
+        let file_name = module_name.replace('/', "_");
+        format!("{}/{}.yaml", Database::get_module_revisions_db_path(), file_name)
+    }
+
+    // The revisions are stored in a single file per module name, and are
+    // only loaded when requested.
+    pub fn get_module_revisions(module_name: &str) -> Vec<ModuleRevision> {
+        let revisions_path = Database::get_module_revisions_path(module_name);
+        let revisions_content = match fs::read_to_string(&revisions_path) {
+            Ok(content) => content,
+            Err(_) => return vec![],
+        };
+        match serde_yaml::from_str(&revisions_content) {
+            Ok(r) => r,
+            Err(e) => {
+                log::debug!("Could not parse the revisions of module {}: {}.", module_name, e);
+                vec![]
+            }
+        }
+    }
+
+    // Adds revisions of modules, from any number of modules. A revision is ignored
+    // if the same version of the module is already known, since the oldest commit
+    // introducing a version of a module is the most interesting one.
+    pub fn add_module_revisions(&mut self, new_revisions: Vec<ModuleRevision>) {
+        let mut new_revisions_by_name: BTreeMap<String, Vec<ModuleRevision>> = BTreeMap::new();
+        for new_revision in new_revisions {
+            new_revisions_by_name
+                .entry(new_revision.module.name.to_string())
+                .or_default()
+                .push(new_revision);
+        }
+
+        for (module_name, new_revisions) in new_revisions_by_name {
+            let mut revisions = Database::get_module_revisions(&module_name);
+            for new_revision in new_revisions {
+                let module_hash = new_revision.module.get_hash();
+                match revisions.iter().position(|r| r.module.get_hash() == module_hash) {
+                    Some(i) if revisions[i].timestamp <= new_revision.timestamp => continue,
+                    Some(i) => revisions[i] = new_revision,
+                    None => revisions.push(new_revision),
+                }
+            }
+            revisions.sort_by_key(|r| r.timestamp);
+
+            let revisions_path = Database::get_module_revisions_path(&module_name);
+            log::info!("Writing {} revisions at {}", revisions.len(), revisions_path);
+            if let Err(e) = fs::write(path::Path::new(&revisions_path), serde_yaml::to_string(&revisions).unwrap()) {
+                eprintln!("Could not write revisions at {}: {}", revisions_path, e);
+            }
+        }
     }
 
     pub fn has_project(&self, project_id: &str) -> bool {
@@ -0,0 +1,240 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::manifests::flatpak::{FlatpakManifest, FlatpakModule};
+use crate::permissions::PermissionDiff;
+
+// The fields that are compared separately from the other fields of a manifest or of a module.
+const MANIFEST_SKIPPED_FIELDS: &[&str] = &["modules", "build-options", "finish-args"];
+const MODULE_SKIPPED_FIELDS: &[&str] = &["name", "modules", "build-options", "sources"];
+
+// A field that has a different value in the two manifests compared. The field is
+// missing from one of the manifests when its value is None.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ModuleDiff {
+    pub name: String,
+    pub changes: Vec<FieldChange>,
+    // The changes to the sources, like the tag, commit or sha256 that they are pinned to.
+    pub source_changes: Vec<FieldChange>,
+    pub build_options_changes: Vec<FieldChange>,
+}
+
+// The semantic differences between two manifests, independently of the order of their keys
+// and of their format. The modules are matched by name, including the nested modules.
+#[derive(Debug, Default, Serialize)]
+pub struct ManifestDiff {
+    pub changes: Vec<FieldChange>,
+    pub build_options_changes: Vec<FieldChange>,
+    pub added_modules: Vec<String>,
+    pub removed_modules: Vec<String>,
+    pub changed_modules: Vec<ModuleDiff>,
+    pub permissions: PermissionDiff,
+}
+impl ManifestDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+            && self.build_options_changes.is_empty()
+            && self.added_modules.is_empty()
+            && self.removed_modules.is_empty()
+            && self.changed_modules.is_empty()
+            && self.permissions.is_empty()
+    }
+}
+
+fn to_value<T: Serialize>(item: &T) -> Result<Value, String> {
+    match serde_json::to_value(item) {
+        Ok(v) => Ok(v),
+        Err(e) => Err(format!("Could not compare the manifests: {}", e)),
+    }
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.to_string(),
+        _ => value.to_string(),
+    }
+}
+
+// Compares the fields of two serialized objects. The missing and empty fields are equivalent,
+// since they are not serialized for most of the fields of the manifests.
+fn compare_fields(prefix: &str, before: &Value, after: &Value, skipped_fields: &[&str], changes: &mut Vec<FieldChange>) {
+    let empty_object = serde_json::Map::new();
+    let before = before.as_object().unwrap_or(&empty_object);
+    let after = after.as_object().unwrap_or(&empty_object);
+    let mut field_names: Vec<&String> = before.keys().chain(after.keys()).collect();
+    field_names.sort();
+    field_names.dedup();
+    for field_name in field_names {
+        if skipped_fields.contains(&field_name.as_str()) {
+            continue;
+        }
+        let before_value = before.get(field_name).filter(|v| !v.is_null());
+        let after_value = after.get(field_name).filter(|v| !v.is_null());
+        if before_value == after_value {
+            continue;
+        }
+        changes.push(FieldChange {
+            field: format!("{}{}", prefix, field_name),
+            before: before_value.map(value_to_string),
+            after: after_value.map(value_to_string),
+        });
+    }
+}
+
+fn flatten_modules<'a>(modules: &'a [FlatpakModule], flattened_modules: &mut Vec<&'a FlatpakModule>) {
+    for module in modules {
+        flattened_modules.push(module);
+        flatten_modules(&module.modules, flattened_modules);
+    }
+}
+
+fn diff_modules(before: &FlatpakModule, after: &FlatpakModule) -> Result<ModuleDiff, String> {
+    let mut module_diff = ModuleDiff {
+        name: before.name.to_string(),
+        ..Default::default()
+    };
+    compare_fields("", &to_value(before)?, &to_value(after)?, MODULE_SKIPPED_FIELDS, &mut module_diff.changes);
+
+    // The sources don't have a name, so they are matched by position.
+    let source_count = before.sources.len().max(after.sources.len());
+    for i in 0..source_count {
+        let before_source = match before.sources.get(i) {
+            Some(s) => to_value(s)?,
+            None => Value::Null,
+        };
+        let after_source = match after.sources.get(i) {
+            Some(s) => to_value(s)?,
+            None => Value::Null,
+        };
+        compare_fields(&format!("sources[{}].", i), &before_source, &after_source, &[], &mut module_diff.source_changes);
+    }
+
+    compare_fields(
+        "build-options.",
+        &to_value(&before.build_options)?,
+        &to_value(&after.build_options)?,
+        &[],
+        &mut module_diff.build_options_changes,
+    );
+    Ok(module_diff)
+}
+
+pub fn diff_flatpak_manifests(before: &FlatpakManifest, after: &FlatpakManifest) -> Result<ManifestDiff, String> {
+    let mut manifest_diff = ManifestDiff::default();
+    compare_fields("", &to_value(before)?, &to_value(after)?, MANIFEST_SKIPPED_FIELDS, &mut manifest_diff.changes);
+    compare_fields(
+        "build-options.",
+        &to_value(&before.build_options)?,
+        &to_value(&after.build_options)?,
+        &[],
+        &mut manifest_diff.build_options_changes,
+    );
+
+    let mut before_modules: Vec<&FlatpakModule> = vec![];
+    flatten_modules(&before.modules, &mut before_modules);
+    let mut after_modules: Vec<&FlatpakModule> = vec![];
+    flatten_modules(&after.modules, &mut after_modules);
+    for before_module in &before_modules {
+        let after_module = match after_modules.iter().find(|m| m.name == before_module.name) {
+            Some(m) => m,
+            None => {
+                manifest_diff.removed_modules.push(before_module.name.to_string());
+                continue;
+            }
+        };
+        let module_diff = diff_modules(before_module, after_module)?;
+        if !module_diff.changes.is_empty() || !module_diff.source_changes.is_empty() || !module_diff.build_options_changes.is_empty() {
+            manifest_diff.changed_modules.push(module_diff);
+        }
+    }
+    for after_module in &after_modules {
+        if !before_modules.iter().any(|m| m.name == after_module.name) {
+            manifest_diff.added_modules.push(after_module.name.to_string());
+        }
+    }
+
+    manifest_diff.permissions = before.diff_permissions(after)?;
+    Ok(manifest_diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YAML_MANIFEST: &str = r###"
+        app-id: org.gnome.Clocks
+        runtime: org.gnome.Platform
+        runtime-version: "40"
+        sdk: org.gnome.Sdk
+        command: gnome-clocks
+        finish-args:
+          - --share=ipc
+          - --socket=wayland
+        build-options:
+          cflags: -O2
+        modules:
+          - name: gnome-clocks
+            buildsystem: meson
+            sources:
+              - type: git
+                url: https://gitlab.gnome.org/GNOME/gnome-clocks.git
+                tag: "40.0"
+            modules:
+              - name: libgweather
+                buildsystem: meson
+                sources:
+                  - type: archive
+                    url: https://download.gnome.org/sources/libgweather/40/libgweather-40.0.tar.xz
+                    sha256: ca4e8f2a4baaa9fc6d75d625fa4d1fa5a1e3d3e8b6c82f4e1c1b3f2e9b4a1c7d
+    "###;
+
+    fn parse(manifest_content: &str) -> FlatpakManifest {
+        FlatpakManifest::parse(&manifest_content.to_string()).unwrap()
+    }
+
+    #[test]
+    pub fn test_diff_across_formats() {
+        let before = parse(YAML_MANIFEST);
+        let json_manifest = before.dump(&crate::manifests::manifest::ManifestFormat::JSON).unwrap();
+        let after = parse(&json_manifest);
+        assert!(diff_flatpak_manifests(&before, &after).unwrap().is_empty());
+    }
+
+    #[test]
+    pub fn test_diff() {
+        let before = parse(YAML_MANIFEST);
+        let after = parse(
+            &YAML_MANIFEST
+                .replace("runtime-version: \"40\"", "runtime-version: \"41\"")
+                .replace("tag: \"40.0\"", "tag: \"41.0\"")
+                .replace("cflags: -O2", "cflags: -O3")
+                .replace("- --socket=wayland", "- --socket=x11")
+                .replace("name: libgweather", "name: geoclue"),
+        );
+        let diff = diff_flatpak_manifests(&before, &after).unwrap();
+        assert_eq!(
+            diff.changes,
+            vec![FieldChange {
+                field: "runtime-version".to_string(),
+                before: Some("40".to_string()),
+                after: Some("41".to_string()),
+            }]
+        );
+        assert_eq!(diff.build_options_changes[0].field, "build-options.cflags");
+        assert_eq!(diff.added_modules, vec!["geoclue"]);
+        assert_eq!(diff.removed_modules, vec!["libgweather"]);
+        assert_eq!(diff.changed_modules.len(), 1);
+        assert_eq!(diff.changed_modules[0].name, "gnome-clocks");
+        assert_eq!(diff.changed_modules[0].source_changes[0].field, "sources[0].tag");
+        assert_eq!(diff.changed_modules[0].source_changes[0].after.as_ref().unwrap(), "41.0");
+        assert_eq!(diff.permissions.added, vec![crate::permissions::Permission::Socket("x11".to_string())]);
+        assert_eq!(diff.permissions.removed, vec![crate::permissions::Permission::Socket("wayland".to_string())]);
+    }
+}
@@ -0,0 +1,393 @@
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::manifests::flatpak::FlatpakManifest;
+
+// The icons of the applications published on Flathub must be scalable, or at least 128x128.
+// See https://docs.flathub.org/docs/for-app-authors/requirements
+const MIN_ICON_SIZE: u32 = 128;
+
+const DESKTOP_ENTRY_GROUP: &str = "[Desktop Entry]";
+const DESKTOP_FILE_EXTENSION: &str = ".desktop";
+const ICON_EXTENSIONS: &[&str] = &[".png", ".svg", ".svgz"];
+
+// The result of checking the files exported by a build. The exports are the files
+// installed in share/applications and share/icons, that are visible from the host.
+#[derive(Debug, Default)]
+pub struct ExportReport {
+    // The problems that prevent the application from being launched or displayed properly.
+    pub errors: Vec<String>,
+    // The files that are not exported, or that are left over after a rename.
+    pub warnings: Vec<String>,
+}
+
+// The keys of the main group of a desktop file.
+// See https://specifications.freedesktop.org/desktop-entry-spec/latest/
+#[derive(Debug, Clone, Default)]
+pub struct DesktopEntry {
+    pub entry_type: String,
+    pub name: String,
+    pub exec: String,
+    pub icon: String,
+}
+impl DesktopEntry {
+    pub fn parse(content: &str) -> Result<DesktopEntry, String> {
+        let mut desktop_entry = DesktopEntry::default();
+        let mut group_found = false;
+        let mut in_main_group = false;
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') {
+                in_main_group = line == DESKTOP_ENTRY_GROUP;
+                group_found = group_found || in_main_group;
+                continue;
+            }
+            if !in_main_group {
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some((k, v)) => (k.trim(), v.trim().to_string()),
+                None => return Err(format!("Invalid desktop entry line {}.", line)),
+            };
+            // The localized keys, like Name[fr], are ignored.
+            match key {
+                "Type" => desktop_entry.entry_type = value,
+                "Name" => desktop_entry.name = value,
+                "Exec" => desktop_entry.exec = value,
+                "Icon" => desktop_entry.icon = value,
+                _ => continue,
+            }
+        }
+        if !group_found {
+            return Err(format!("No {} group found in the desktop file.", DESKTOP_ENTRY_GROUP));
+        }
+        Ok(desktop_entry)
+    }
+
+    pub fn load_from_file(path: &Path) -> Result<DesktopEntry, String> {
+        let content = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) => return Err(format!("Could not read desktop file {}: {}.", path.display(), e)),
+        };
+        DesktopEntry::parse(&content)
+    }
+
+    // Gets the name of the program launched by the Exec key, without its arguments
+    // and its field codes. The program can be prefixed by env and environment variables.
+    pub fn get_program_name(&self) -> Option<String> {
+        let program = self
+            .exec
+            .split_whitespace()
+            .map(|w| w.trim_matches(|c| c == '"' || c == '\''))
+            .find(|w| *w != "env" && !w.contains('='))?;
+        Some(get_file_name(program).to_string())
+    }
+}
+
+fn get_file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+// Gets the size of a PNG image from its header.
+pub fn get_png_size(path: &Path) -> Result<(u32, u32), String> {
+    let mut header = [0u8; 24];
+    let mut file = match fs::File::open(path) {
+        Ok(f) => f,
+        Err(e) => return Err(format!("Could not open image {}: {}.", path.display(), e)),
+    };
+    if let Err(e) = file.read_exact(&mut header) {
+        return Err(format!("Could not read image {}: {}.", path.display(), e));
+    }
+    if &header[..8] != b"\x89PNG\r\n\x1a\n" || &header[12..16] != b"IHDR" {
+        return Err(format!("{} is not a PNG image.", path.display()));
+    }
+    let width = u32::from_be_bytes([header[16], header[17], header[18], header[19]]);
+    let height = u32::from_be_bytes([header[20], header[21], header[22], header[23]]);
+    Ok((width, height))
+}
+
+// Parses the size of an icon theme directory, for example 64x64 or 64x64@2.
+// The size of the scaled directories is multiplied by their scale.
+fn parse_icon_dir_size(dir_name: &str) -> Option<u32> {
+    let (size, scale) = match dir_name.split_once('@') {
+        Some((s, scale)) => (s, scale.parse::<u32>().ok()?),
+        None => (dir_name, 1),
+    };
+    let (width, height) = size.split_once('x')?;
+    if width != height {
+        return None;
+    }
+    Some(width.parse::<u32>().ok()? * scale)
+}
+
+fn list_files(dir_path: &Path) -> Vec<PathBuf> {
+    let mut file_paths: Vec<PathBuf> = match fs::read_dir(dir_path) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.is_file()).collect(),
+        Err(_) => return vec![],
+    };
+    file_paths.sort();
+    file_paths
+}
+
+// Gets the name of an exported file without its extension.
+fn get_stem<'a>(file_name: &'a str, extensions: &[&str]) -> Option<&'a str> {
+    extensions.iter().find_map(|e| file_name.strip_suffix(e))
+}
+
+// Only the files named after the application id are exported by flatpak.
+fn is_exported(stem: &str, app_id: &str) -> bool {
+    stem == app_id || stem.starts_with(&format!("{}.", app_id))
+}
+
+fn check_desktop_files(manifest: &FlatpakManifest, files_dir: &Path, report: &mut ExportReport) {
+    let app_id = manifest.get_id();
+    let applications_dir = files_dir.join("share").join("applications");
+    let mut main_desktop_file: Option<PathBuf> = None;
+    for file_path in list_files(&applications_dir) {
+        let file_name = file_path.file_name().unwrap().to_str().unwrap_or("");
+        let stem = match get_stem(file_name, &[DESKTOP_FILE_EXTENSION]) {
+            Some(s) => s,
+            None => continue,
+        };
+        if stem == app_id {
+            main_desktop_file = Some(file_path.to_path_buf());
+        } else if !manifest.rename_desktop_file.is_empty() && file_name == manifest.rename_desktop_file {
+            report
+                .warnings
+                .push(format!("Desktop file {} was not renamed to {}.desktop.", file_name, app_id));
+        } else if !is_exported(stem, app_id) {
+            report
+                .warnings
+                .push(format!("Desktop file {} is not exported, since it is not prefixed by {}.", file_name, app_id));
+        }
+    }
+
+    let main_desktop_file = match main_desktop_file {
+        Some(p) => p,
+        None => {
+            let hint = match manifest.rename_desktop_file.is_empty() {
+                true => "Use rename-desktop-file to rename the installed desktop file.".to_string(),
+                false => format!("rename-desktop-file {} did not match any installed file.", manifest.rename_desktop_file),
+            };
+            report.errors.push(format!("No {}.desktop desktop file found in the build. {}", app_id, hint));
+            return;
+        }
+    };
+    let desktop_entry = match DesktopEntry::load_from_file(&main_desktop_file) {
+        Ok(e) => e,
+        Err(e) => {
+            report.errors.push(e);
+            return;
+        }
+    };
+
+    if !manifest.command.is_empty() {
+        let command_name = get_file_name(&manifest.command);
+        match desktop_entry.get_program_name() {
+            Some(program_name) if program_name == command_name => {}
+            Some(program_name) => report.errors.push(format!(
+                "The desktop file launches {}, but the command of the manifest is {}.",
+                program_name, manifest.command
+            )),
+            None => report.errors.push(format!("The desktop file of {} does not have an Exec key.", app_id)),
+        };
+    }
+    // flatpak-builder rewrites the Icon key when renaming the icon.
+    if desktop_entry.icon != app_id {
+        report.errors.push(format!(
+            "The desktop file uses the icon {}, which is not named after {}.",
+            desktop_entry.icon, app_id
+        ));
+    }
+    if !desktop_entry.name.starts_with(&manifest.desktop_file_name_prefix) || !desktop_entry.name.ends_with(&manifest.desktop_file_name_suffix) {
+        report.warnings.push(format!(
+            "The name {} of the desktop file does not have the desktop-file-name-prefix or suffix of the manifest.",
+            desktop_entry.name
+        ));
+    }
+}
+
+fn check_icons(manifest: &FlatpakManifest, files_dir: &Path, report: &mut ExportReport) {
+    let app_id = manifest.get_id();
+    let hicolor_dir = files_dir.join("share").join("icons").join("hicolor");
+    let size_dirs: Vec<PathBuf> = match fs::read_dir(&hicolor_dir) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.is_dir()).collect(),
+        Err(_) => vec![],
+    };
+
+    let mut has_required_icon = false;
+    for size_dir in size_dirs {
+        let size_dir_name = size_dir.file_name().unwrap().to_str().unwrap_or("").to_string();
+        for icon_path in list_files(&size_dir.join("apps")) {
+            let file_name = icon_path.file_name().unwrap().to_str().unwrap_or("");
+            let stem = match get_stem(file_name, ICON_EXTENSIONS) {
+                Some(s) => s,
+                None => continue,
+            };
+            if !manifest.rename_icon.is_empty() && stem == manifest.rename_icon {
+                if !manifest.copy_icon.unwrap_or(false) {
+                    report
+                        .warnings
+                        .push(format!("Icon {}/{} was not renamed to {}.", size_dir_name, file_name, app_id));
+                }
+                continue;
+            }
+            if !is_exported(stem, app_id) {
+                report.warnings.push(format!(
+                    "Icon {}/{} is not exported, since it is not prefixed by {}.",
+                    size_dir_name, file_name, app_id
+                ));
+                continue;
+            }
+            if stem != app_id {
+                continue;
+            }
+            if size_dir_name == "scalable" {
+                has_required_icon = true;
+                continue;
+            }
+            let dir_size = match parse_icon_dir_size(&size_dir_name) {
+                Some(s) => s,
+                None => {
+                    report
+                        .warnings
+                        .push(format!("Icon {}/{} is in an invalid size directory.", size_dir_name, file_name));
+                    continue;
+                }
+            };
+            if !file_name.ends_with(".png") {
+                continue;
+            }
+            match get_png_size(&icon_path) {
+                Ok((width, height)) if width == dir_size && height == dir_size => {
+                    has_required_icon = has_required_icon || dir_size >= MIN_ICON_SIZE;
+                }
+                Ok((width, height)) => report.errors.push(format!(
+                    "Icon {}/{} is {}x{}, which does not match the size of its directory.",
+                    size_dir_name, file_name, width, height
+                )),
+                Err(e) => report.errors.push(e),
+            };
+        }
+    }
+
+    if !has_required_icon {
+        let hint = match manifest.rename_icon.is_empty() {
+            true => "".to_string(),
+            false => format!(" Make sure that rename-icon {} matches the name of the installed icon.", manifest.rename_icon),
+        };
+        report.errors.push(format!(
+            "No scalable or {}x{} (or bigger) icon named {} found in the build.{}",
+            MIN_ICON_SIZE, MIN_ICON_SIZE, app_id, hint
+        ));
+    }
+}
+
+// Checks the desktop files and the icons installed in the files directory
+// of a build, against the application id and the command of the manifest.
+pub fn check_exports(manifest: &FlatpakManifest, files_dir: &Path) -> ExportReport {
+    let mut report = ExportReport::default();
+    // Only the applications export files, the runtimes and the extensions don't.
+    if manifest.get_id().is_empty() || manifest.build_runtime.unwrap_or(false) || manifest.build_extension.unwrap_or(false) {
+        return report;
+    }
+    check_desktop_files(manifest, files_dir, &mut report);
+    check_icons(manifest, files_dir, &mut report);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_png(path: &Path, size: u32) {
+        let mut content: Vec<u8> = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        content.extend_from_slice(&size.to_be_bytes());
+        content.extend_from_slice(&size.to_be_bytes());
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn write_desktop_file(path: &Path, exec: &str, icon: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let content = format!(
+            "[Desktop Entry]\nType=Application\nName=Clocks\nName[fr]=Horloges\nExec={}\nIcon={}\n",
+            exec, icon
+        );
+        fs::write(path, content).unwrap();
+    }
+
+    fn get_manifest(extra_fields: &str) -> FlatpakManifest {
+        let manifest_content = format!(
+            "app-id: org.gnome.Clocks\nruntime: org.gnome.Platform\nsdk: org.gnome.Sdk\ncommand: gnome-clocks\n{}modules: []\n",
+            extra_fields
+        );
+        FlatpakManifest::parse(&manifest_content).unwrap()
+    }
+
+    #[test]
+    pub fn test_parse_desktop_entry() {
+        let desktop_entry = DesktopEntry::parse(
+            "# A comment\n[Desktop Entry]\nName=Clocks\nExec=env GDK_BACKEND=x11 \"/app/bin/gnome-clocks\" %U\nIcon=org.gnome.Clocks\n\n[Desktop Action new]\nExec=other\n",
+        )
+        .unwrap();
+        assert_eq!(desktop_entry.name, "Clocks");
+        assert_eq!(desktop_entry.icon, "org.gnome.Clocks");
+        assert_eq!(desktop_entry.get_program_name().unwrap(), "gnome-clocks");
+        assert!(DesktopEntry::parse("[Desktop Action new]\nExec=other\n").is_err());
+    }
+
+    #[test]
+    pub fn test_parse_icon_dir_size() {
+        assert_eq!(parse_icon_dir_size("128x128"), Some(128));
+        assert_eq!(parse_icon_dir_size("64x64@2"), Some(128));
+        assert_eq!(parse_icon_dir_size("scalable"), None);
+    }
+
+    #[test]
+    pub fn test_check_exports() {
+        let files_dir = tempfile::tempdir().unwrap();
+        let share_dir = files_dir.path().join("share");
+        write_desktop_file(&share_dir.join("applications/org.gnome.Clocks.desktop"), "gnome-clocks %U", "org.gnome.Clocks");
+        write_png(&share_dir.join("icons/hicolor/128x128/apps/org.gnome.Clocks.png"), 128);
+        write_png(&share_dir.join("icons/hicolor/64x64/apps/org.gnome.Clocks.png"), 64);
+        let report = check_exports(&get_manifest(""), files_dir.path());
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+    }
+
+    #[test]
+    pub fn test_check_exports_errors() {
+        let files_dir = tempfile::tempdir().unwrap();
+        let share_dir = files_dir.path().join("share");
+        write_desktop_file(&share_dir.join("applications/org.gnome.Clocks.desktop"), "clocks", "org.gnome.Clocks");
+        write_desktop_file(&share_dir.join("applications/clocks-settings.desktop"), "clocks", "clocks");
+        write_png(&share_dir.join("icons/hicolor/128x128/apps/org.gnome.Clocks.png"), 64);
+        let report = check_exports(&get_manifest(""), files_dir.path());
+        assert_eq!(report.errors.len(), 3, "{:?}", report.errors);
+        assert!(report.errors[0].contains("launches clocks"));
+        assert!(report.errors[1].contains("64x64"));
+        assert_eq!(report.warnings.len(), 1);
+    }
+
+    #[test]
+    pub fn test_check_exports_renaming() {
+        let files_dir = tempfile::tempdir().unwrap();
+        let share_dir = files_dir.path().join("share");
+        write_desktop_file(&share_dir.join("applications/gnome-clocks.desktop"), "gnome-clocks", "gnome-clocks");
+        fs::create_dir_all(share_dir.join("icons/hicolor/scalable/apps")).unwrap();
+        fs::write(share_dir.join("icons/hicolor/scalable/apps/gnome-clocks.svg"), "<svg/>").unwrap();
+        let report = check_exports(
+            &get_manifest("rename-desktop-file: gnome-clocks.desktop\nrename-icon: gnome-clocks\n"),
+            files_dir.path(),
+        );
+        assert_eq!(report.errors.len(), 2, "{:?}", report.errors);
+        assert!(report.errors[1].contains("rename-icon gnome-clocks"));
+        assert_eq!(report.warnings.len(), 2, "{:?}", report.warnings);
+        assert!(report.warnings[0].contains("was not renamed"));
+    }
+}
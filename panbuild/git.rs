@@ -0,0 +1,598 @@
+// Git operations, using libgit2 instead of the git executable.
+use std::path::Path;
+
+use git2::{AutotagOption, FetchOptions, Oid, Repository, Sort};
+
+// The refspec used for mirrors, which replicates all the refs of the remote.
+const MIRROR_REFSPEC: &str = "+refs/*:refs/*";
+
+// A version of a file, as introduced by a commit.
+#[derive(Debug, Clone)]
+pub struct FileRevision {
+    // The path of the file, relative to the root of the repo.
+    pub path: String,
+    // The hash of the commit that introduced this version of the file.
+    pub commit: String,
+    // The time of the commit, in seconds since the epoch.
+    pub timestamp: i64,
+    pub content: String,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct CloneOptions {
+    // Creates a bare repository, without a working directory.
+    pub bare: bool,
+    // Only fetches the last `depth` commits of the history. Partial clones
+    // (clones without some of the blobs) are not supported by libgit2, so
+    // shallow clones are the only way to avoid downloading the whole history.
+    // The depth is ignored when cloning from a local path.
+    pub depth: Option<i32>,
+    // The branch to checkout, instead of the default branch of the remote.
+    pub branch: Option<String>,
+}
+
+fn open_repo(repo_path: &Path) -> Result<Repository, String> {
+    match Repository::open(repo_path) {
+        Ok(r) => Ok(r),
+        Err(e) => Err(format!("Could not open git repo at {}: {}", repo_path.display(), e.message())),
+    }
+}
+
+fn get_fetch_options(depth: Option<i32>) -> FetchOptions<'static> {
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.download_tags(AutotagOption::All);
+    if let Some(depth) = depth {
+        fetch_options.depth(depth);
+    }
+    fetch_options
+}
+
+pub fn clone_repo(repo_url: &str, repo_path: &Path, options: &CloneOptions) -> Result<(), String> {
+    let mut repo_builder = git2::build::RepoBuilder::new();
+    repo_builder.bare(options.bare);
+    repo_builder.fetch_options(get_fetch_options(options.depth));
+    if let Some(branch) = &options.branch {
+        repo_builder.branch(branch);
+    }
+    match repo_builder.clone(repo_url, repo_path) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Could not clone {}: {}", repo_url, e.message())),
+    }
+}
+
+// Creates a bare repository replicating all the refs of the remote repository.
+pub fn mirror_repo(repo_url: &str, repo_path: &Path) -> Result<(), String> {
+    init_mirror(repo_url, repo_path)?;
+    fetch_repo(repo_path)
+}
+
+// Creates a mirror of the remote repository, getting the refs and the objects
+// from an existing local mirror of the same repository.
+pub fn mirror_repo_from(repo_url: &str, source_repo_path: &Path, repo_path: &Path) -> Result<(), String> {
+    init_mirror(repo_url, repo_path)?;
+    fetch_mirror_from(repo_path, source_repo_path)
+}
+
+// Updates a mirror with the refs of another local mirror. The origin remote
+// of the mirror is left unchanged.
+pub fn fetch_mirror_from(repo_path: &Path, source_repo_path: &Path) -> Result<(), String> {
+    let repo = open_repo(repo_path)?;
+    let source_repo_url = source_repo_path.display().to_string();
+    let mut remote = match repo.remote_anonymous(&source_repo_url) {
+        Ok(r) => r,
+        Err(e) => return Err(format!("Could not add remote {}: {}", source_repo_url, e.message())),
+    };
+    let mut fetch_options = get_fetch_options(None);
+    fetch_options.prune(git2::FetchPrune::On);
+    match remote.fetch(&[MIRROR_REFSPEC], Some(&mut fetch_options), None) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Could not fetch {} from {}: {}", repo_path.display(), source_repo_url, e.message())),
+    }
+}
+
+fn init_mirror(repo_url: &str, repo_path: &Path) -> Result<(), String> {
+    let repo = match Repository::init_bare(repo_path) {
+        Ok(r) => r,
+        Err(e) => return Err(format!("Could not create git repo at {}: {}", repo_path.display(), e.message())),
+    };
+    if let Err(e) = repo.remote_with_fetch("origin", repo_url, MIRROR_REFSPEC) {
+        return Err(format!("Could not add remote {}: {}", repo_url, e.message()));
+    }
+    if let Err(e) = repo.config().and_then(|mut c| c.set_bool("remote.origin.mirror", true)) {
+        return Err(format!("Could not configure remote {}: {}", repo_url, e.message()));
+    }
+    Ok(())
+}
+
+// Fetches the new commits and tags from the origin remote. The remote
+// branches of a mirror are also pruned.
+pub fn fetch_repo(repo_path: &Path) -> Result<(), String> {
+    let repo = open_repo(repo_path)?;
+    let mut remote = match repo.find_remote("origin") {
+        Ok(r) => r,
+        Err(e) => return Err(format!("Could not find remote origin of {}: {}", repo_path.display(), e.message())),
+    };
+    let mut fetch_options = get_fetch_options(None);
+    let is_mirror = remote.refspecs().any(|r| r.str() == Some(MIRROR_REFSPEC));
+    if is_mirror {
+        fetch_options.prune(git2::FetchPrune::On);
+    }
+    // An empty list of refspecs uses the refspecs configured for the remote.
+    let refspecs: Vec<&str> = vec![];
+    match remote.fetch(&refspecs, Some(&mut fetch_options), None) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Could not fetch {}: {}", repo_path.display(), e.message())),
+    }
+}
+
+// Fetches the remote and fast-forwards the current branch to its upstream branch.
+pub fn update_repo(repo_path: &Path) -> Result<(), String> {
+    fetch_repo(repo_path)?;
+    let repo = open_repo(repo_path)?;
+    if repo.is_bare() {
+        return Ok(());
+    }
+    let head = match repo.head() {
+        Ok(h) => h,
+        Err(e) => return Err(format!("Could not get HEAD of {}: {}", repo_path.display(), e.message())),
+    };
+    if !head.is_branch() {
+        log::debug!("HEAD of {} is detached, not updating.", repo_path.display());
+        return Ok(());
+    }
+    let branch = git2::Branch::wrap(head);
+    let upstream_commit = match branch.upstream().and_then(|u| u.get().peel_to_commit()) {
+        Ok(c) => c,
+        Err(e) => return Err(format!("Could not get the upstream branch of {}: {}", repo_path.display(), e.message())),
+    };
+    let annotated_commit = match repo.find_annotated_commit(upstream_commit.id()) {
+        Ok(c) => c,
+        Err(e) => return Err(e.message().to_string()),
+    };
+    let (analysis, _) = match repo.merge_analysis(&[&annotated_commit]) {
+        Ok(a) => a,
+        Err(e) => return Err(e.message().to_string()),
+    };
+    if analysis.is_up_to_date() {
+        return Ok(());
+    }
+    if !analysis.is_fast_forward() {
+        return Err(format!(
+            "Cannot fast-forward {}, the branch has diverged from its upstream.",
+            repo_path.display()
+        ));
+    }
+
+    let mut branch_ref = branch.into_reference();
+    let result = branch_ref
+        .set_target(upstream_commit.id(), "panbuild: fast-forward")
+        .and_then(|_| repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force())));
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Could not fast-forward {}: {}", repo_path.display(), e.message())),
+    }
+}
+
+// Gets the tags of a git repo, with the hash of the commit they point to.
+// Tags that don't point to a commit are ignored.
+pub fn get_tags(repo_path: &Path) -> Result<Vec<(String, String)>, String> {
+    let repo = open_repo(repo_path)?;
+    let tag_names = match repo.tag_names(None) {
+        Ok(t) => t,
+        Err(e) => return Err(format!("Could not list the tags of {}: {}", repo_path.display(), e.message())),
+    };
+    let mut tags: Vec<(String, String)> = vec![];
+    for tag_name in tag_names.iter().flatten() {
+        let commit = match repo.revparse_single(&format!("refs/tags/{}", tag_name)).and_then(|o| o.peel_to_commit()) {
+            Ok(c) => c,
+            Err(e) => {
+                log::debug!("Tag {} does not point to a commit: {}", tag_name, e.message());
+                continue;
+            }
+        };
+        tags.push((tag_name.to_string(), commit.id().to_string()));
+    }
+    Ok(tags)
+}
+
+// Gets the tags of a remote git repo, with the hash of the commit
+// they point to, without cloning the repo.
+pub fn get_remote_tags(repo_url: &str) -> Result<Vec<(String, String)>, String> {
+    let mut remote = match git2::Remote::create_detached(repo_url) {
+        Ok(r) => r,
+        Err(e) => return Err(format!("Invalid git remote {}: {}", repo_url, e.message())),
+    };
+    if let Err(e) = remote.connect(git2::Direction::Fetch) {
+        return Err(format!("Could not connect to {}: {}", repo_url, e.message()));
+    }
+    let remote_heads = match remote.list() {
+        Ok(h) => h,
+        Err(e) => return Err(format!("Could not get tags for {}: {}", repo_url, e.message())),
+    };
+
+    let mut tags: Vec<(String, String)> = vec![];
+    for remote_head in remote_heads {
+        let tag_name = match remote_head.name().strip_prefix("refs/tags/") {
+            Some(n) => n,
+            None => continue,
+        };
+        // For annotated tags, the peeled ref (ending with ^{}) has the hash of the commit.
+        if let Some(tag_name) = tag_name.strip_suffix("^{}") {
+            match tags.iter_mut().find(|(name, _)| name == tag_name) {
+                Some(tag) => tag.1 = remote_head.oid().to_string(),
+                None => tags.push((tag_name.to_string(), remote_head.oid().to_string())),
+            }
+            continue;
+        }
+        if !tags.iter().any(|(name, _)| name == tag_name) {
+            tags.push((tag_name.to_string(), remote_head.oid().to_string()));
+        }
+    }
+    Ok(tags)
+}
+
+// Gets the hashes of the commits without parents that are reachable from HEAD.
+// There can be more than 1 root commit, in the case of a merger of unrelated histories.
+pub fn get_root_hashes(repo_path: &Path) -> Result<Vec<String>, String> {
+    let repo = open_repo(repo_path)?;
+    let mut revwalk = match repo.revwalk() {
+        Ok(r) => r,
+        Err(e) => return Err(e.message().to_string()),
+    };
+    if let Err(e) = revwalk.push_head() {
+        return Err(format!("Could not walk the history of {}: {}", repo_path.display(), e.message()));
+    }
+    revwalk.set_sorting(Sort::TOPOLOGICAL).unwrap_or(());
+
+    let mut root_hashes: Vec<String> = vec![];
+    for commit_id in revwalk {
+        let commit = match commit_id.and_then(|id| repo.find_commit(id)) {
+            Ok(c) => c,
+            Err(e) => return Err(e.message().to_string()),
+        };
+        if commit.parent_count() == 0 {
+            root_hashes.push(commit.id().to_string());
+        }
+    }
+    Ok(root_hashes)
+}
+
+// Gets all the versions of the files selected by is_selected in the history
+// of HEAD, from the oldest to the newest. A version of a file is only returned
+// once, for the first commit in which it appears. Binary files are ignored.
+// Every commit is compared to its first parent, so only the files changed
+// by a commit are inspected.
+pub fn get_file_revisions(repo_path: &Path, is_selected: &dyn Fn(&str) -> bool) -> Result<Vec<FileRevision>, String> {
+    let repo = open_repo(repo_path)?;
+    let mut revwalk = match repo.revwalk() {
+        Ok(r) => r,
+        Err(e) => return Err(e.message().to_string()),
+    };
+    if let Err(e) = revwalk.push_head() {
+        return Err(format!("Could not walk the history of {}: {}", repo_path.display(), e.message()));
+    }
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME | Sort::REVERSE).unwrap_or(());
+
+    let mut file_revisions: Vec<FileRevision> = vec![];
+    let mut seen_blobs: std::collections::HashSet<(String, Oid)> = std::collections::HashSet::new();
+    for commit_id in revwalk {
+        let commit = match commit_id.and_then(|id| repo.find_commit(id)) {
+            Ok(c) => c,
+            Err(e) => return Err(e.message().to_string()),
+        };
+        let tree = match commit.tree() {
+            Ok(t) => t,
+            Err(e) => return Err(e.message().to_string()),
+        };
+        // The root commits are compared to an empty tree.
+        let parent_tree = match commit.parents().next().map(|p| p.tree()) {
+            Some(Ok(t)) => Some(t),
+            Some(Err(e)) => return Err(e.message().to_string()),
+            None => None,
+        };
+        let diff = match repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None) {
+            Ok(d) => d,
+            Err(e) => return Err(format!("Could not diff commit {}: {}", commit.id(), e.message())),
+        };
+
+        for delta in diff.deltas() {
+            if delta.status() == git2::Delta::Deleted {
+                continue;
+            }
+            let new_file = delta.new_file();
+            if new_file.mode() != git2::FileMode::Blob && new_file.mode() != git2::FileMode::BlobExecutable {
+                continue;
+            }
+            let file_path = match new_file.path().and_then(|p| p.to_str()) {
+                Some(p) => p.to_string(),
+                None => continue,
+            };
+            if !is_selected(&file_path) || !seen_blobs.insert((file_path.to_string(), new_file.id())) {
+                continue;
+            }
+            let blob = match repo.find_blob(new_file.id()) {
+                Ok(b) => b,
+                Err(e) => return Err(e.message().to_string()),
+            };
+            let content = match std::str::from_utf8(blob.content()) {
+                Ok(c) => c.to_string(),
+                Err(_) => continue,
+            };
+            file_revisions.push(FileRevision {
+                path: file_path,
+                commit: commit.id().to_string(),
+                timestamp: commit.time().seconds(),
+                content,
+            });
+        }
+    }
+    Ok(file_revisions)
+}
+
+// Gets the root of the working directory of the git repo containing a path, if any.
+pub fn get_repo_root(path: &Path) -> Option<std::path::PathBuf> {
+    let repo = Repository::discover(path).ok()?;
+    repo.workdir().map(|p| p.to_path_buf())
+}
+
+// Resolves a revision (a branch, a tag, an abbreviated hash, HEAD~2 etc.) to the hash of a commit.
+pub fn resolve_rev(repo_path: &Path, rev: &str) -> Result<String, String> {
+    let repo = open_repo(repo_path)?;
+    let commit_id = match repo.revparse_single(rev).and_then(|o| o.peel_to_commit()) {
+        Ok(c) => c.id(),
+        Err(e) => return Err(format!("Could not resolve {} in {}: {}", rev, repo_path.display(), e.message())),
+    };
+    Ok(commit_id.to_string())
+}
+
+pub fn has_commit(repo_path: &Path, commit_hash: &str) -> bool {
+    let repo = match open_repo(repo_path) {
+        Ok(r) => r,
+        Err(_) => return false,
+    };
+    let oid = match Oid::from_str(commit_hash) {
+        Ok(o) => o,
+        Err(_) => return false,
+    };
+    let commit = repo.find_commit(oid);
+    commit.is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::transport::{Service, SmartSubtransport, SmartSubtransportStream, Transport};
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::process::{Command, Stdio};
+
+    // The local transport of libgit2 ignores the depth, so the shallow clones are tested
+    // with the smart protocol, served by git upload-pack in the stateless mode used by the
+    // http servers. The repository at `upload-pack://<path>` is served from `<path>`.
+    const UPLOAD_PACK_SCHEME: &str = "upload-pack";
+
+    // Buffers the request until the response is read, since the stateless upload-pack
+    // only answers once its input is closed.
+    struct UploadPackStream {
+        repo_path: String,
+        advertise_refs: bool,
+        request: Vec<u8>,
+        response: Option<Box<dyn Read + Send>>,
+    }
+    impl Read for UploadPackStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.response.is_none() {
+                let mut command = Command::new("git");
+                command.arg("upload-pack").arg("--stateless-rpc");
+                if self.advertise_refs {
+                    command.arg("--advertise-refs");
+                }
+                let mut child = command.arg(&self.repo_path).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
+                child.stdin.take().unwrap().write_all(&self.request)?;
+                // The refs advertised by the http servers start with the name of the service.
+                let header: &[u8] = match self.advertise_refs {
+                    true => b"001e# service=git-upload-pack\n0000",
+                    false => b"",
+                };
+                self.response = Some(Box::new(std::io::Cursor::new(header).chain(child.stdout.take().unwrap())));
+            }
+            self.response.as_mut().unwrap().read(buf)
+        }
+    }
+    impl Write for UploadPackStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.request.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct UploadPackTransport {}
+    impl SmartSubtransport for UploadPackTransport {
+        fn action(&self, url: &str, action: Service) -> Result<Box<dyn SmartSubtransportStream>, git2::Error> {
+            let advertise_refs = match action {
+                Service::UploadPackLs => true,
+                Service::UploadPack => false,
+                _ => return Err(git2::Error::from_str("Only fetching is supported.")),
+            };
+            Ok(Box::new(UploadPackStream {
+                repo_path: url.trim_start_matches(&format!("{}://", UPLOAD_PACK_SCHEME)).to_string(),
+                advertise_refs,
+                request: vec![],
+                response: None,
+            }))
+        }
+
+        fn close(&self) -> Result<(), git2::Error> {
+            Ok(())
+        }
+    }
+
+    fn register_upload_pack_transport() {
+        static REGISTER: std::sync::Once = std::sync::Once::new();
+        // The transports have to be registered only once, before being used.
+        REGISTER.call_once(|| unsafe {
+            git2::transport::register(UPLOAD_PACK_SCHEME, |remote| Transport::smart(remote, true, UploadPackTransport {})).unwrap();
+        });
+    }
+
+    fn commit_file(repo: &Repository, file_name: &str, content: &str) -> Oid {
+        let repo_path = repo.workdir().unwrap();
+        fs::write(repo_path.join(file_name), content).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(file_name)).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("panbuild", "panbuild@example.com").unwrap();
+        let parents = match repo.head() {
+            Ok(h) => vec![h.peel_to_commit().unwrap()],
+            Err(_) => vec![],
+        };
+        let parents: Vec<&git2::Commit> = parents.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, file_name, &tree, &parents).unwrap()
+    }
+
+    fn tag_commit(repo: &Repository, tag_name: &str, commit_id: Oid, annotated: bool) {
+        let commit = repo.find_object(commit_id, None).unwrap();
+        if annotated {
+            let signature = git2::Signature::now("panbuild", "panbuild@example.com").unwrap();
+            repo.tag(tag_name, &commit, &signature, tag_name, false).unwrap();
+        } else {
+            repo.tag_lightweight(tag_name, &commit, false).unwrap();
+        }
+    }
+
+    // Creates a repository with 2 commits, and a bare clone of it to use as a remote.
+    fn get_test_repos(dir: &Path) -> (Repository, String, Oid) {
+        let repo = Repository::init(dir.join("upstream")).unwrap();
+        let root_commit_id = commit_file(&repo, "README.md", "panbuild");
+        tag_commit(&repo, "v1.0.0", root_commit_id, false);
+        let commit_id = commit_file(&repo, "Cargo.toml", "[package]");
+        tag_commit(&repo, "v1.1.0", commit_id, true);
+
+        let remote_path = dir.join("remote.git");
+        let clone_options = CloneOptions {
+            bare: true,
+            ..Default::default()
+        };
+        clone_repo(dir.join("upstream").to_str().unwrap(), &remote_path, &clone_options).unwrap();
+        let remote_url = format!("file://{}", remote_path.display());
+        (repo, remote_url, root_commit_id)
+    }
+
+    #[test]
+    pub fn test_clone_and_inspect() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, remote_url, root_commit_id) = get_test_repos(dir.path());
+
+        let repo_path = dir.path().join("clone");
+        clone_repo(&remote_url, &repo_path, &CloneOptions::default()).unwrap();
+        assert!(repo_path.join("Cargo.toml").is_file());
+
+        let mut tags = get_tags(&repo_path).unwrap();
+        tags.sort();
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags[0], ("v1.0.0".to_string(), root_commit_id.to_string()));
+        // The annotated tag is resolved to its commit.
+        assert_eq!(tags[1].1, resolve_rev(&repo_path, "HEAD").unwrap());
+
+        let mut remote_tags = get_remote_tags(&remote_url).unwrap();
+        remote_tags.sort();
+        assert_eq!(remote_tags, tags);
+
+        assert_eq!(get_root_hashes(&repo_path).unwrap(), vec![root_commit_id.to_string()]);
+        assert_eq!(resolve_rev(&repo_path, "HEAD~1").unwrap(), root_commit_id.to_string());
+        assert!(has_commit(&repo_path, &root_commit_id.to_string()));
+        assert!(!has_commit(&repo_path, "0123456789012345678901234567890123456789"));
+        assert!(resolve_rev(&repo_path, "v3.0.0").is_err());
+    }
+
+    #[test]
+    pub fn test_shallow_clone() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, remote_url, root_commit_id) = get_test_repos(dir.path());
+        register_upload_pack_transport();
+        let remote_url = remote_url.replace("file://", &format!("{}://", UPLOAD_PACK_SCHEME));
+
+        let repo_path = dir.path().join("clone");
+        let clone_options = CloneOptions {
+            depth: Some(1),
+            ..Default::default()
+        };
+        clone_repo(&remote_url, &repo_path, &clone_options).unwrap();
+        assert!(repo_path.join("Cargo.toml").is_file());
+        assert!(Repository::open(&repo_path).unwrap().is_shallow());
+        assert!(resolve_rev(&repo_path, "HEAD~1").is_err());
+
+        let repo_path = dir.path().join("full-clone");
+        clone_repo(&remote_url, &repo_path, &CloneOptions::default()).unwrap();
+        assert!(!Repository::open(&repo_path).unwrap().is_shallow());
+        assert_eq!(resolve_rev(&repo_path, "HEAD~1").unwrap(), root_commit_id.to_string());
+    }
+
+    #[test]
+    pub fn test_update_and_mirror() {
+        let dir = tempfile::tempdir().unwrap();
+        let (upstream_repo, remote_url, _) = get_test_repos(dir.path());
+
+        let repo_path = dir.path().join("clone");
+        clone_repo(&remote_url, &repo_path, &CloneOptions::default()).unwrap();
+        let mirror_path = dir.path().join("mirror.git");
+        mirror_repo(&remote_url, &mirror_path).unwrap();
+        let mirror_copy_path = dir.path().join("mirror-copy.git");
+        mirror_repo_from(&remote_url, &mirror_path, &mirror_copy_path).unwrap();
+        let mirror_copy = Repository::open(&mirror_copy_path).unwrap();
+        assert_eq!(mirror_copy.find_remote("origin").unwrap().url(), Some(remote_url.as_str()));
+
+        // Pushing a new commit and a new tag to the remote.
+        let commit_id = commit_file(&upstream_repo, "main.rs", "fn main() {}");
+        tag_commit(&upstream_repo, "v1.2.0", commit_id, false);
+        let mut remote = upstream_repo.remote_anonymous(&remote_url).unwrap();
+        let branch_name = upstream_repo.head().unwrap().shorthand().unwrap().to_string();
+        let refspec = format!("refs/heads/{}:refs/heads/{}", branch_name, branch_name);
+        remote.push(&[refspec.as_str(), "refs/tags/v1.2.0:refs/tags/v1.2.0"], None).unwrap();
+
+        update_repo(&repo_path).unwrap();
+        assert!(repo_path.join("main.rs").is_file());
+        assert_eq!(resolve_rev(&repo_path, "HEAD").unwrap(), commit_id.to_string());
+        assert_eq!(get_tags(&repo_path).unwrap().len(), 3);
+
+        assert!(!has_commit(&mirror_path, &commit_id.to_string()));
+        fetch_repo(&mirror_path).unwrap();
+        assert!(has_commit(&mirror_path, &commit_id.to_string()));
+        assert_eq!(resolve_rev(&mirror_path, "v1.2.0").unwrap(), commit_id.to_string());
+
+        assert!(!has_commit(&mirror_copy_path, &commit_id.to_string()));
+        fetch_mirror_from(&mirror_copy_path, &mirror_path).unwrap();
+        assert!(has_commit(&mirror_copy_path, &commit_id.to_string()));
+    }
+
+    #[test]
+    pub fn test_get_file_revisions() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let first_commit_id = commit_file(&repo, "manifest.json", "{}");
+        commit_file(&repo, "README.md", "panbuild");
+        // Reverting to a previous version of the file does not create a new revision.
+        let second_commit_id = commit_file(&repo, "manifest.json", "{\"modules\": []}");
+        commit_file(&repo, "manifest.json", "{}");
+
+        let file_revisions = get_file_revisions(dir.path(), &|p| p.ends_with(".json")).unwrap();
+        assert_eq!(file_revisions.len(), 2);
+        assert_eq!(file_revisions[0].path, "manifest.json");
+        assert_eq!(file_revisions[0].commit, first_commit_id.to_string());
+        assert_eq!(file_revisions[0].content, "{}");
+        assert_eq!(file_revisions[1].commit, second_commit_id.to_string());
+    }
+
+    #[test]
+    pub fn test_clone_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(clone_repo("file:///does/not/exist", &dir.path().join("clone"), &CloneOptions::default()).is_err());
+        assert!(get_tags(&dir.path().join("not-a-repo")).is_err());
+        assert!(fetch_repo(&dir.path().join("not-a-repo")).is_err());
+    }
+}
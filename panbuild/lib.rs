@@ -3,16 +3,26 @@
 //! `panbuild` is the universal builder.
 use std::collections::HashMap;
 
+pub mod appstream;
+pub mod cache;
+pub mod checksums;
 pub mod db;
+pub mod diff;
+pub mod exports;
+pub mod git;
 pub mod logger;
 pub mod manifests;
 pub mod modules;
+pub mod outdated;
+pub mod permissions;
 pub mod projects;
+pub mod resolver;
+pub mod runtimes;
 pub mod utils;
+pub mod version;
 
 mod config;
 mod developers;
-mod version;
 
 pub use manifests::flatpak::FlatpakModule;
 pub use manifests::manifest::AbstractManifest;
@@ -52,20 +62,95 @@ pub fn run(command_name: &str, args: HashMap<String, String>) -> i32 {
             None => return 1,
         };
 
-        let manifest_dump = match abstract_manifest.dump() {
-            Ok(d) => d,
-            Err(e) => return 1,
+        // Bundling a library that the runtime already ships is most likely a mistake.
+        if let (Ok(modules), Ok(runtime_libraries)) = (abstract_manifest.get_modules(), abstract_manifest.get_runtime_libraries()) {
+            for module in &modules {
+                if let Some(library) = runtime_libraries.iter().find(|l| l.matches(&module.name)) {
+                    eprintln!(
+                        "Warning: module {} is already provided by the runtime ({} {}).",
+                        module.name, library.name, library.version
+                    );
+                }
+            }
+        }
+
+        // The errors are collected, so that all the problems of the manifest are reported at once.
+        let mut has_errors = false;
+
+        // The AppStream metadata of the application is usually in the same repo as its manifest,
+        // but not always in the same directory, for example with a manifest in build-aux/flatpak/.
+        if let Some(crate::manifests::manifest::NativeManifest::Flatpak(flatpak_manifest)) = &abstract_manifest.native_manifest {
+            let manifest_dir = path::Path::new(manifest_file_path).parent().unwrap_or(path::Path::new("."));
+            let manifest_dir = if manifest_dir.as_os_str().is_empty() {
+                path::Path::new(".")
+            } else {
+                manifest_dir
+            };
+            let search_dir = crate::git::get_repo_root(manifest_dir).unwrap_or(manifest_dir.to_path_buf());
+            let components = crate::appstream::find_app_components(&search_dir, flatpak_manifest.get_id());
+            let mismatches = crate::manifests::flatpak::check_appstream_ids(flatpak_manifest, &components);
+            for mismatch in &mismatches {
+                eprintln!("Error: {}.", mismatch);
+            }
+            if !mismatches.is_empty() {
+                has_errors = true;
+            }
+        }
+
+        // Only fixing the checksums on demand, since it can require downloading the sources.
+        let fix = args.contains_key("fix");
+        if let Ok(report) = abstract_manifest.check_checksums(fix) {
+            if !print_checksum_report(&report) {
+                has_errors = true;
+            }
+        }
+
+        // Only reformatting the whole manifest on demand, to keep the comments and the formatting of the file.
+        match abstract_manifest.save(args.contains_key("reformat")) {
+            Ok(true) => eprintln!("Updated the manifest!"),
+            Ok(false) => {}
+            Err(e) => {
+                eprintln!("{}", e);
+                return 1;
+            }
         };
+        if has_errors {
+            return 1;
+        }
+        return 0;
+    }
 
-        match fs::write(path::Path::new(manifest_file_path), manifest_dump) {
-            Ok(content) => content,
+    if command_name == "diff" {
+        let manifest_file_path = args.get("manifest_file_path").expect("a manifest file is required!");
+        let other_manifest_file_path = args.get("other_manifest_file_path").expect("a manifest file to compare with is required!");
+
+        let abstract_manifest = match crate::manifests::manifest::AbstractManifest::load_with_includes(manifest_file_path.to_string()) {
+            Some(m) => m,
+            None => return 1,
+        };
+        let other_manifest = match crate::manifests::manifest::AbstractManifest::load_with_includes(other_manifest_file_path.to_string()) {
+            Some(m) => m,
+            None => return 1,
+        };
+        let manifest_diff = match abstract_manifest.diff(&other_manifest) {
+            Ok(d) => d,
             Err(e) => {
-                eprintln!("could not write file {}.", manifest_file_path);
+                eprintln!("Could not compare the manifests: {}", e);
                 return 1;
             }
         };
 
-        eprintln!("Dumped the manifest!");
+        if args.contains_key("json") {
+            match serde_json::to_string_pretty(&manifest_diff) {
+                Ok(d) => println!("{}", d),
+                Err(e) => {
+                    eprintln!("Could not dump the manifest diff: {}", e);
+                    return 1;
+                }
+            };
+            return 0;
+        }
+        print_manifest_diff(&manifest_diff);
         return 0;
     }
 
@@ -129,50 +214,257 @@ pub fn run(command_name: &str, args: HashMap<String, String>) -> i32 {
         };
 
         let package_name = args.get("package_name").expect("A package name to install is required!");
+        // Packages can be requested at a specific version, using `name@version`.
+        let (package_name, version_range) = match package_name.split_once('@') {
+            Some((name, range)) => match crate::version::VersionRange::parse(range) {
+                Some(r) => (name, r),
+                None => {
+                    eprintln!("Invalid version {} for package {}.", range, name);
+                    return 1;
+                }
+            },
+            None => (package_name.as_str(), crate::version::VersionRange::default()),
+        };
         if package_name.len() < 3 {
             eprintln!("{} is too short for a package name!", package_name);
             return 1;
         }
         eprintln!("Installing module {:#?}", &package_name);
 
-        let packages: Vec<SoftwareModule> = crate::projects::get_modules();
-        let mut installed_package: Option<&SoftwareModule> = None;
-        eprintln!("Searching in {:#?} packages for installation candidates 🕰", packages.len());
-        for package in &packages {
-            if package.name.contains(package_name) {
-                println!("found candidate artifact in {}.", package.name);
-                let question = format!("Do you want to install {} ({})", package.name, package.url);
-                if crate::utils::ask_yes_no_question(question) {
-                    println!("installing {}.", package.name);
-                    abstract_manifest.add_module(package);
-                    installed_package = Some(package);
-                    break;
-                }
+        let provided_names = match abstract_manifest.get_provided_names() {
+            Ok(n) => n,
+            Err(e) => {
+                eprintln!("Could not install {}: {}", package_name, e);
+                return 1;
+            }
+        };
+        let install_plan = match crate::resolver::get_db_install_plan(package_name, &version_range, &provided_names) {
+            Ok(p) => p,
+            Err(e) => {
+                eprintln!("Could not install {}: {}", package_name, e);
+                return 1;
             }
+        };
+        println!("{}", install_plan);
+        if args.contains_key("dry_run") {
+            return 0;
         }
 
-        let installed_package_name = match installed_package {
-            Some(p) => p,
-            None => {
-                println!("Did not install any package.");
+        // The modules are inserted at the beginning of the manifest, so we add
+        // them in reverse order to keep the dependencies before their dependents.
+        for package in install_plan.modules.iter().rev() {
+            if let Err(e) = abstract_manifest.add_module(package) {
+                eprintln!("Could not install {}: {}", package.name, e);
+                return 1;
+            }
+        }
+        println!("Installed {} package(s).", install_plan.modules.len());
+
+        // Only the installed modules are checked, so that installing a module
+        // does not download all the sources of the manifest.
+        let installed_module_names: Vec<String> = install_plan.modules.iter().map(|m| m.name.to_string()).collect();
+        if let Ok(report) = abstract_manifest.fill_modules_checksums(&installed_module_names) {
+            if !print_checksum_report(&report) {
                 return 1;
             }
+        }
+
+        if let Err(e) = abstract_manifest.save(args.contains_key("reformat")) {
+            eprintln!("{}", e);
+            return 1;
+        }
+        return 0;
+    }
+
+    if command_name == "uninstall" {
+        let mut abstract_manifest = match crate::config::load_manifest_from_config() {
+            Some(m) => m,
+            None => return 1,
         };
-        let installed_package_name = &installed_package_name.name;
-        println!("Installed package {}.", installed_package_name);
 
-        let manifest_dump = match abstract_manifest.dump() {
-            Ok(d) => d,
-            Err(e) => return 1,
+        let package_name = args.get("package_name").expect("A package name to uninstall is required!");
+
+        // We warn about the modules that would be broken by the removal.
+        if let Some(parent_module_name) = abstract_manifest.get_parent_module_name(package_name) {
+            eprintln!("Warning: {} is nested in module {}.", package_name, parent_module_name);
+        }
+        if let Ok(modules) = abstract_manifest.get_modules() {
+            let projects = crate::db::Database::get_all_projects();
+            let dependent_modules = crate::resolver::get_dependent_modules(&projects, &crate::db::Database::get_project_versions, &modules, package_name);
+            if !dependent_modules.is_empty() {
+                eprintln!("Warning: {} is required by {}.", package_name, dependent_modules.join(", "));
+            }
+        }
+
+        let removed_modules = match abstract_manifest.remove_module(package_name) {
+            Ok(m) => m,
+            Err(e) => {
+                eprintln!("Could not uninstall {}: {}", package_name, e);
+                return 1;
+            }
         };
+        println!("Uninstalled {}.", removed_modules.join(", "));
 
-        match fs::write(path::Path::new(&abstract_manifest.path), manifest_dump) {
-            Ok(content) => content,
+        if let Err(e) = abstract_manifest.save(args.contains_key("reformat")) {
+            eprintln!("{}", e);
+            return 1;
+        }
+        return 0;
+    }
+
+    if command_name == "upgrade" {
+        let mut abstract_manifest = match crate::config::load_manifest_from_config() {
+            Some(m) => m,
+            None => return 1,
+        };
+
+        let modules = match abstract_manifest.get_modules() {
+            Ok(m) => m,
+            Err(e) => {
+                eprintln!("Could not get the modules of the manifest: {}", e);
+                return 1;
+            }
+        };
+        // Without a package name, all the modules are upgraded.
+        let package_name = args.get("package_name").map(|n| n.as_str()).unwrap_or("");
+        if !package_name.is_empty() && !modules.iter().any(|m| m.name == package_name) {
+            eprintln!("No module named {} in the manifest.", package_name);
+            return 1;
+        }
+
+        let projects = crate::db::Database::get_all_projects();
+        let mut upgraded_modules_count = 0;
+        for module in &modules {
+            if !package_name.is_empty() && module.name != package_name {
+                continue;
+            }
+            let module_upgrade = match crate::resolver::get_module_upgrade(&projects, &crate::db::Database::get_project_versions, module) {
+                Some(u) => u,
+                None => continue,
+            };
+            if let Err(e) = abstract_manifest.update_module(&module_upgrade.module) {
+                eprintln!("Could not upgrade {}: {}", module.name, e);
+                return 1;
+            }
+            println!(
+                "Upgraded {} from {} to {}.",
+                module.name, module_upgrade.current_version, module_upgrade.new_version
+            );
+            upgraded_modules_count += 1;
+        }
+        if upgraded_modules_count == 0 {
+            println!("All the modules are up to date.");
+            return 0;
+        }
+
+        if let Err(e) = abstract_manifest.save(args.contains_key("reformat")) {
+            eprintln!("{}", e);
+            return 1;
+        }
+        return 0;
+    }
+
+    if command_name == "outdated" {
+        let abstract_manifest = match crate::config::load_manifest_from_config() {
+            Some(m) => m,
+            None => return 1,
+        };
+
+        let modules = match abstract_manifest.get_modules() {
+            Ok(m) => m,
             Err(e) => {
-                eprintln!("could not write file {}.", &abstract_manifest.path);
+                eprintln!("Could not get the modules of the manifest: {}", e);
                 return 1;
             }
         };
+        let modules_status = crate::outdated::get_modules_status(&modules);
+
+        if args.contains_key("json") {
+            match serde_json::to_string_pretty(&modules_status) {
+                Ok(d) => println!("{}", d),
+                Err(e) => {
+                    eprintln!("Could not dump the modules status: {}", e);
+                    return 1;
+                }
+            };
+            return 0;
+        }
+
+        let unknown = "unknown".to_string();
+        for module_status in &modules_status {
+            let bump = match &module_status.bump {
+                Some(b) => b.to_string(),
+                None => "".to_string(),
+            };
+            println!(
+                "{:<30} {:<20} {:<20} {}",
+                module_status.module_name,
+                module_status.current_version.as_ref().unwrap_or(&unknown),
+                module_status.latest_version.as_ref().unwrap_or(&unknown),
+                bump
+            );
+        }
+        return 0;
+    }
+
+    if command_name == "fetch" {
+        let abstract_manifest = match crate::config::load_manifest_from_config() {
+            Some(m) => m,
+            None => return 1,
+        };
+
+        let sources = match abstract_manifest.get_sources() {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Could not get the sources of manifest file {}: {}", &abstract_manifest.path, e);
+                return 1;
+            }
+        };
+        let offline = args.contains_key("offline");
+        let cache_dir = crate::cache::get_cache_dir_path();
+        let mut failed_sources_count = 0;
+        for source in &sources {
+            if let Err(e) = crate::cache::fetch_source(&cache_dir, source, offline) {
+                eprintln!("Could not fetch {}: {}", source.url, e);
+                failed_sources_count += 1;
+            }
+        }
+        println!(
+            "{} of {} source(s) available in the cache at {}.",
+            sources.len() - failed_sources_count,
+            sources.len(),
+            cache_dir
+        );
+        if failed_sources_count != 0 {
+            return 1;
+        }
+        return 0;
+    }
+
+    if command_name == "gc" {
+        // The sources used by any of the workspaces are kept.
+        let mut used_sources: Vec<crate::modules::AbstractSource> = vec![];
+        for (workspace_name, manifest_file_path) in &config.workspaces {
+            let abstract_manifest = match crate::manifests::manifest::AbstractManifest::load_from_file(manifest_file_path.to_string()) {
+                Some(m) => m,
+                None => {
+                    eprintln!("Could not load the manifest of workspace {}, not collecting garbage.", workspace_name);
+                    return 1;
+                }
+            };
+            match abstract_manifest.get_sources() {
+                Ok(s) => used_sources.extend(s),
+                Err(e) => {
+                    eprintln!("Could not get the sources of workspace {}: {}", workspace_name, e);
+                    return 1;
+                }
+            };
+        }
+        let report = crate::cache::gc(&crate::cache::get_cache_dir_path(), &used_sources);
+        for removed_path in &report.removed_paths {
+            log::info!("Removed {}.", removed_path);
+        }
+        println!("Removed {} cache entries, freeing {} bytes.", report.removed_paths.len(), report.freed_bytes);
         return 0;
     }
 
@@ -182,14 +474,45 @@ pub fn run(command_name: &str, args: HashMap<String, String>) -> i32 {
             None => return 1,
         };
 
-        match abstract_manifest.run_build() {
-            Ok(content) => content,
-            Err(e) => {
-                eprintln!("could not run build for manifest file {}: {}", &abstract_manifest.path, e);
+        let offline = args.contains_key("offline");
+        if offline {
+            // Making sure that all the sources are available before starting the build.
+            let sources = match abstract_manifest.get_sources() {
+                Ok(s) => s,
+                Err(e) => {
+                    eprintln!("Could not get the sources of manifest file {}: {}", &abstract_manifest.path, e);
+                    return 1;
+                }
+            };
+            let cache_dir = crate::cache::get_cache_dir_path();
+            let mut missing_sources_count = 0;
+            for source in &sources {
+                if let Err(e) = crate::cache::fetch_source(&cache_dir, source, true) {
+                    eprintln!("{}", e);
+                    missing_sources_count += 1;
+                }
+            }
+            if missing_sources_count != 0 {
+                eprintln!("{} source(s) missing from the cache. Run `panbuild fetch` first.", missing_sources_count);
                 return 1;
             }
+        }
+
+        if let Err(e) = abstract_manifest.run_build(offline) {
+            eprintln!("could not run build for manifest file {}: {}", &abstract_manifest.path, e);
+            return 1;
+        }
+        // The desktop file and the icons are only installed at the end of the build,
+        // so problems with them are easy to miss. The files of a failed build are
+        // missing or stale, so they are only checked after a successful build.
+        match abstract_manifest.check_exports() {
+            Ok(report) => {
+                if !print_export_report(&report) {
+                    return 1;
+                }
+            }
+            Err(e) => log::debug!("Could not check the exported files: {}", e),
         };
-        // TODO report on the build!
         return 0;
     }
 
@@ -340,3 +663,74 @@ pub fn run(command_name: &str, args: HashMap<String, String>) -> i32 {
     log::debug!("Finishing...");
     return 0;
 }
+
+// Prints the changes to the fields of a manifest, a module or a source,
+// one change per line, with the value before and after the change.
+fn print_field_changes(field_changes: &Vec<crate::diff::FieldChange>, indent: &str) {
+    let none = "(none)".to_string();
+    for field_change in field_changes {
+        println!(
+            "{}~ {}: {} -> {}",
+            indent,
+            field_change.field,
+            field_change.before.as_ref().unwrap_or(&none),
+            field_change.after.as_ref().unwrap_or(&none)
+        );
+    }
+}
+
+// Prints the semantic diff of 2 manifests, prefixing the added items
+// with `+`, the removed items with `-` and the changed items with `~`.
+fn print_manifest_diff(manifest_diff: &crate::diff::ManifestDiff) {
+    if manifest_diff.is_empty() {
+        println!("The manifests are equivalent.");
+        return;
+    }
+    print_field_changes(&manifest_diff.changes, "");
+    print_field_changes(&manifest_diff.build_options_changes, "");
+    for module_name in &manifest_diff.added_modules {
+        println!("+ module {}", module_name);
+    }
+    for module_name in &manifest_diff.removed_modules {
+        println!("- module {}", module_name);
+    }
+    for module_diff in &manifest_diff.changed_modules {
+        println!("~ module {}", module_diff.name);
+        print_field_changes(&module_diff.changes, "    ");
+        print_field_changes(&module_diff.source_changes, "    ");
+        print_field_changes(&module_diff.build_options_changes, "    ");
+    }
+    for permission in &manifest_diff.permissions.added {
+        println!("+ permission {}", permission.to_finish_arg());
+    }
+    for permission in &manifest_diff.permissions.removed {
+        println!("- permission {}", permission.to_finish_arg());
+    }
+}
+
+// Prints the problems found with the desktop files and the icons of a build.
+// Returns false if one of the problems is an error.
+fn print_export_report(report: &crate::exports::ExportReport) -> bool {
+    for warning in &report.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    for error in &report.errors {
+        eprintln!("Error: {}", error);
+    }
+    report.errors.is_empty()
+}
+
+// Prints the result of a checksum verification.
+// Returns false if a checksum did not match.
+fn print_checksum_report(report: &crate::checksums::ChecksumReport) -> bool {
+    for url in &report.filled {
+        println!("Added sha256 checksum for {}.", url);
+    }
+    for url in &report.missing {
+        eprintln!("Warning: missing sha256 checksum for {}.", url);
+    }
+    for mismatch in &report.mismatches {
+        eprintln!("Error: checksum mismatch for {}", mismatch);
+    }
+    report.mismatches.is_empty()
+}
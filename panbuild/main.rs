@@ -52,6 +52,9 @@ fn main() {
     arguments
         .entry("manifest_file_path".to_string())
         .or_insert(subcommand_matches.value_of("manifest_file_path").unwrap_or("").to_string());
+    arguments
+        .entry("other_manifest_file_path".to_string())
+        .or_insert(subcommand_matches.value_of("other_manifest_file_path").unwrap_or("").to_string());
     arguments
         .entry("search_term".to_string())
         .or_insert(subcommand_matches.value_of("search_term").unwrap_or("").to_string());
@@ -68,6 +71,22 @@ fn main() {
         .entry("command".to_string())
         .or_insert(subcommand_matches.value_of("command").unwrap_or("").to_string());
 
+    if subcommand_matches.is_present("dry_run") {
+        arguments.insert("dry_run".to_string(), "true".to_string());
+    }
+    if subcommand_matches.is_present("offline") {
+        arguments.insert("offline".to_string(), "true".to_string());
+    }
+    if subcommand_matches.is_present("fix") {
+        arguments.insert("fix".to_string(), "true".to_string());
+    }
+    if subcommand_matches.is_present("json") {
+        arguments.insert("json".to_string(), "true".to_string());
+    }
+    if subcommand_matches.is_present("reformat") {
+        arguments.insert("reformat".to_string(), "true".to_string());
+    }
+
     let exit_code = panbuild::run(command_name, arguments);
     exit(exit_code);
 }
@@ -1,6 +1,7 @@
 pub mod arch;
 pub mod cargo;
 pub mod debian;
+pub mod editor;
 pub mod flatpak;
 pub mod javascript;
 pub mod manifest;
@@ -171,7 +171,9 @@ impl DebianManifest {
     }
 }
 
-fn parse_paragraphs(content: &String) -> Vec<String> {
+// Splits a file in the deb822 format (control files, Sources and Packages indices etc.)
+// into its paragraphs, which are separated by empty lines.
+pub fn parse_paragraphs(content: &String) -> Vec<String> {
     let mut paragraphs = vec![];
     let lines = content.split("\n");
     let mut paragraph: String = String::from("");
@@ -192,7 +194,8 @@ fn parse_paragraphs(content: &String) -> Vec<String> {
     paragraphs
 }
 
-fn parse_paragraph(paragraph: &String) -> HashMap<String, String> {
+// Parses the fields of a deb822 paragraph. The lines of multi-line fields are joined.
+pub fn parse_paragraph(paragraph: &String) -> HashMap<String, String> {
     let mut fields: HashMap<String, String> = HashMap::new();
     let lines = paragraph.split("\n");
 
@@ -237,6 +240,32 @@ fn parse_paragraph(paragraph: &String) -> HashMap<String, String> {
     fields
 }
 
+// Parses a field declaring relationships with other packages, like Depends or Build-Depends,
+// into the names of the packages and their version constraints. Only the first alternative
+// of a dependency is kept, and the architecture and build profile restrictions are ignored.
+// See https://www.debian.org/doc/debian-policy/ch-relationships.html
+pub fn parse_relationships(field_value: &str) -> Vec<(String, String)> {
+    let mut relationships: Vec<(String, String)> = vec![];
+    for dependency in field_value.split(',') {
+        let dependency = dependency.split('|').next().unwrap_or("");
+        let dependency = match dependency.find(|c| c == '[' || c == '<') {
+            Some(i) => &dependency[..i],
+            None => dependency,
+        };
+        let (package_name, version_constraint) = match dependency.find('(') {
+            Some(i) => (&dependency[..i], dependency[i..].trim().trim_start_matches('(').trim_end_matches(')')),
+            None => (dependency, ""),
+        };
+        // The architecture qualifiers, like :any or :native, are not part of the name.
+        let package_name = package_name.trim().split(':').next().unwrap_or("");
+        if package_name.is_empty() || package_name.starts_with('$') {
+            continue;
+        }
+        relationships.push((package_name.to_string(), version_constraint.trim().to_string()));
+    }
+    relationships
+}
+
 fn is_field_start(line: &str) -> bool {
     for c in line.chars() {
         if c.is_alphanumeric() {
@@ -402,6 +431,20 @@ mod tests {
         }
     }
 
+    #[test]
+    pub fn test_parse_relationships() {
+        let relationships = parse_relationships(
+            "debhelper-compat (= 13), libglib2.0-dev (>= 2.66) [linux-any], python3:any, \
+             libgtk-3-dev | libgtk2.0-dev, gtk-doc-tools <!nodoc>, ${misc:Depends},",
+        );
+        assert_eq!(relationships.len(), 5);
+        assert_eq!(relationships[0], ("debhelper-compat".to_string(), "= 13".to_string()));
+        assert_eq!(relationships[1], ("libglib2.0-dev".to_string(), ">= 2.66".to_string()));
+        assert_eq!(relationships[2], ("python3".to_string(), "".to_string()));
+        assert_eq!(relationships[3].0, "libgtk-3-dev");
+        assert_eq!(relationships[4].0, "gtk-doc-tools");
+    }
+
     #[test]
     pub fn test_file_path_matches() {
         assert!(DebianManifest::file_path_matches("debian/control"));
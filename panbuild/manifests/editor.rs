@@ -0,0 +1,893 @@
+// Applies the changes made to a manifest to the original text of the manifest file, instead
+// of dumping the whole manifest again. This keeps the comments, the order of the keys and
+// the indentation of the parts of the manifest that were not changed.
+//
+// The changes are found by comparing the serialized manifest before and after the changes,
+// and the values that changed are located in the original text using the positions
+// reported by the YAML parser. JSON being a subset of YAML, both formats are edited the same way.
+use serde::Serialize;
+use serde_yaml::Value;
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::{Marker, TScalarStyle};
+
+use crate::manifests::manifest::ManifestFormat;
+
+#[derive(Debug)]
+enum NodeKind {
+    Scalar,
+    Sequence(Vec<Node>),
+    Mapping(Vec<Entry>),
+}
+
+// A value of the manifest, with its position in the original text.
+#[derive(Debug)]
+struct Node {
+    start: usize,
+    end: usize,
+    // Whether the node is a flow collection, like [] and {} or the JSON arrays and objects.
+    flow: bool,
+    // Whether the node is inside a flow collection.
+    in_flow: bool,
+    kind: NodeKind,
+}
+
+#[derive(Debug)]
+struct Entry {
+    key: String,
+    key_start: usize,
+    value: Node,
+}
+
+// A replacement of a range of the original text.
+#[derive(Debug)]
+struct Edit {
+    start: usize,
+    end: usize,
+    text: String,
+}
+
+struct EventCollector {
+    // The events, with the byte offset where they were found.
+    events: Vec<(Event, usize)>,
+    // The byte offsets of the chars, since the parser reports the positions in chars.
+    char_offsets: Vec<usize>,
+}
+impl MarkedEventReceiver for EventCollector {
+    fn on_event(&mut self, event: Event, marker: Marker) {
+        let offset = match self.char_offsets.get(marker.index()) {
+            Some(o) => *o,
+            None => *self.char_offsets.last().unwrap(),
+        };
+        self.events.push((event, offset));
+    }
+}
+
+fn get_line_start(text: &str, position: usize) -> usize {
+    text[..position].rfind('\n').map(|i| i + 1).unwrap_or(0)
+}
+
+fn get_line_end(text: &str, position: usize) -> usize {
+    text[position..].find('\n').map(|i| position + i).unwrap_or(text.len())
+}
+
+fn get_next_line_start(text: &str, position: usize) -> usize {
+    text[position..].find('\n').map(|i| position + i + 1).unwrap_or(text.len())
+}
+
+fn get_column(text: &str, position: usize) -> usize {
+    text[get_line_start(text, position)..position].chars().count()
+}
+
+fn get_indentation(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+// Strips the comment at the end of a line. A # only starts a comment after a space.
+fn strip_comment(line: &str) -> &str {
+    let mut previous_char = ' ';
+    for (i, c) in line.char_indices() {
+        if c == '#' && (previous_char == ' ' || previous_char == '\t') {
+            return &line[..i];
+        }
+        previous_char = c;
+    }
+    line
+}
+
+fn is_complex(value: &Value) -> bool {
+    match value {
+        Value::Sequence(s) => !s.is_empty(),
+        Value::Mapping(m) => !m.is_empty(),
+        _ => false,
+    }
+}
+
+// Gets the end of the lines following a position that are indented more than the parent.
+// The blank lines and the comments are only part of the block scalars.
+fn get_indented_lines_end(text: &str, position: usize, parent_indent: isize, is_block_scalar: bool) -> usize {
+    let mut end = position;
+    let mut line_start = get_next_line_start(text, position);
+    while line_start < text.len() {
+        let line = &text[line_start..get_line_end(text, line_start)];
+        if line.trim().is_empty() && is_block_scalar {
+            line_start = get_next_line_start(text, line_start);
+            continue;
+        }
+        if line.trim().is_empty() || get_indentation(line) as isize <= parent_indent {
+            break;
+        }
+        if is_block_scalar {
+            end = line_start + line.trim_end().len();
+        } else if line.trim_start().starts_with('#') {
+            break;
+        } else {
+            end = line_start + strip_comment(line).trim_end().len();
+        }
+        line_start = get_next_line_start(text, line_start);
+    }
+    end
+}
+
+struct Document<'a> {
+    text: &'a str,
+    events: Vec<(Event, usize)>,
+    is_json: bool,
+    // The indentation used in the JSON files, reused for the values added to them.
+    json_indent: String,
+}
+impl<'a> Document<'a> {
+    fn parse(text: &'a str, format: &ManifestFormat) -> Result<Document<'a>, String> {
+        let mut char_offsets: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+        char_offsets.push(text.len());
+        let mut collector = EventCollector { events: vec![], char_offsets };
+        let mut parser = Parser::new(text.chars());
+        if let Err(e) = parser.load(&mut collector, false) {
+            return Err(format!("Could not parse the manifest: {}.", e));
+        }
+        let json_indent = match text.lines().map(|l| &l[..get_indentation(l)]).find(|i| !i.is_empty()) {
+            Some(i) => i.to_string(),
+            None => "    ".to_string(),
+        };
+        Ok(Document {
+            text,
+            events: collector.events,
+            is_json: matches!(format, ManifestFormat::JSON),
+            json_indent,
+        })
+    }
+
+    fn get_root(&self) -> Result<Node, String> {
+        let mut event_index = 0;
+        while let Some((Event::StreamStart, _)) | Some((Event::DocumentStart, _)) = self.events.get(event_index) {
+            event_index += 1;
+        }
+        self.parse_node(&mut event_index, -1, false, None)
+    }
+
+    fn get_scalar_end(&self, start: usize, style: &TScalarStyle, parent_indent: isize, in_flow: bool, is_key: bool) -> usize {
+        let text = self.text;
+        match style {
+            TScalarStyle::SingleQuoted => {
+                let mut chars = text[start + 1..].char_indices().peekable();
+                while let Some((i, c)) = chars.next() {
+                    if c != '\'' {
+                        continue;
+                    }
+                    // The single quotes are escaped by doubling them.
+                    if let Some((_, '\'')) = chars.peek() {
+                        chars.next();
+                        continue;
+                    }
+                    return start + i + 2;
+                }
+                text.len()
+            }
+            TScalarStyle::DoubleQuoted => {
+                let mut chars = text[start + 1..].char_indices();
+                while let Some((i, c)) = chars.next() {
+                    if c == '\\' {
+                        chars.next();
+                    } else if c == '"' {
+                        return start + i + 2;
+                    }
+                }
+                text.len()
+            }
+            TScalarStyle::Literal | TScalarStyle::Foled => get_indented_lines_end(text, start, parent_indent, true),
+            _ => {
+                let line = &text[start..get_line_end(text, start)];
+                if is_key {
+                    let key_end = match line.find(": ") {
+                        Some(i) => i,
+                        None => line.trim_end().trim_end_matches(':').len(),
+                    };
+                    return start + line[..key_end].trim_end().len();
+                }
+                if in_flow {
+                    let value_end = line.find(&[',', ']', '}'][..]).unwrap_or(line.len());
+                    return start + strip_comment(&line[..value_end]).trim_end().len();
+                }
+                // The plain scalars can continue on the following lines.
+                let end = start + strip_comment(line).trim_end().len();
+                get_indented_lines_end(text, end, parent_indent, false)
+            }
+        }
+    }
+
+    fn parse_node(&self, event_index: &mut usize, parent_indent: isize, in_flow: bool, empty_value_position: Option<usize>) -> Result<Node, String> {
+        let (event, position) = match self.events.get(*event_index) {
+            Some((e, p)) => (e, *p),
+            None => return Err("Unexpected end of the manifest.".to_string()),
+        };
+        *event_index += 1;
+        match event {
+            Event::Scalar(value, style, _, _) => {
+                // The empty values are reported as ~, at the position of the next token.
+                if value == "~" && *style == TScalarStyle::Plain && !self.text[position..].starts_with('~') {
+                    let position = empty_value_position.unwrap_or(position);
+                    return Ok(Node {
+                        start: position,
+                        end: position,
+                        flow: false,
+                        in_flow,
+                        kind: NodeKind::Scalar,
+                    });
+                }
+                let mut start = position;
+                // The block scalars are reported at the start of their content, after their indicator.
+                if *style == TScalarStyle::Literal || *style == TScalarStyle::Foled {
+                    if let Some(indicator_position) = self.text[..position].rfind(&['|', '>'][..]) {
+                        start = indicator_position;
+                    }
+                }
+                Ok(Node {
+                    start,
+                    end: self.get_scalar_end(start, style, parent_indent, in_flow, false),
+                    flow: false,
+                    in_flow,
+                    kind: NodeKind::Scalar,
+                })
+            }
+            Event::SequenceStart(_) => {
+                let flow = in_flow || self.text[position..].starts_with('[');
+                let item_indent = get_column(self.text, position) as isize;
+                let mut items: Vec<Node> = vec![];
+                loop {
+                    if let Some((Event::SequenceEnd, end_position)) = self.events.get(*event_index) {
+                        *event_index += 1;
+                        let end = match flow {
+                            true => end_position + 1,
+                            false => items.last().map(|n| n.end).unwrap_or(position),
+                        };
+                        return Ok(Node {
+                            start: position,
+                            end,
+                            flow,
+                            in_flow,
+                            kind: NodeKind::Sequence(items),
+                        });
+                    }
+                    items.push(self.parse_node(event_index, item_indent, flow, None)?);
+                }
+            }
+            Event::MappingStart(_) => {
+                let flow = in_flow || self.text[position..].starts_with('{');
+                let mut entries: Vec<Entry> = vec![];
+                loop {
+                    if let Some((Event::MappingEnd, end_position)) = self.events.get(*event_index) {
+                        *event_index += 1;
+                        // The position of the block mappings is not reliable, so the position of their first key is used.
+                        let (start, end) = match flow {
+                            true => (position, end_position + 1),
+                            false => (
+                                entries.first().map(|e| e.key_start).unwrap_or(position),
+                                entries.last().map(|e| e.value.end).unwrap_or(position),
+                            ),
+                        };
+                        return Ok(Node {
+                            start,
+                            end,
+                            flow,
+                            in_flow,
+                            kind: NodeKind::Mapping(entries),
+                        });
+                    }
+                    let (key, key_start, style) = match self.events.get(*event_index) {
+                        Some((Event::Scalar(key, style, _, _), key_start)) => (key.to_string(), *key_start, style),
+                        _ => return Err("Only the scalar keys are supported.".to_string()),
+                    };
+                    *event_index += 1;
+                    let key_end = self.get_scalar_end(key_start, style, parent_indent, flow, true);
+                    let colon_position = match self.text[key_end..].find(':') {
+                        Some(i) => key_end + i + 1,
+                        None => key_end,
+                    };
+                    let key_indent = get_column(self.text, key_start) as isize;
+                    let value = self.parse_node(event_index, key_indent, flow, Some(colon_position))?;
+                    entries.push(Entry { key, key_start, value });
+                }
+            }
+            _ => Err("The anchors and aliases are not supported.".to_string()),
+        }
+    }
+
+    // Renders a value in the block style, indenting the lines after the first one.
+    fn render_yaml(&self, value: &Value, indent: usize) -> Result<String, String> {
+        let rendered_value = match serde_yaml::to_string(value) {
+            Ok(v) => v,
+            Err(e) => return Err(format!("Could not render the value: {}.", e)),
+        };
+        let rendered_value = rendered_value.trim_start_matches("---").trim_start_matches('\n').trim_end();
+        Ok(rendered_value.replace('\n', &format!("\n{}", " ".repeat(indent))))
+    }
+
+    // Renders a value in the flow style. In the JSON files, the values are indented like
+    // the line where they are inserted.
+    fn render_json(&self, value: &Value, position: usize) -> Result<String, String> {
+        if !self.is_json || !is_complex(value) {
+            return match serde_json::to_string(value) {
+                Ok(v) => Ok(v),
+                Err(e) => Err(format!("Could not render the value: {}.", e)),
+            };
+        }
+        let mut rendered_value: Vec<u8> = vec![];
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(self.json_indent.as_bytes());
+        let mut serializer = serde_json::Serializer::with_formatter(&mut rendered_value, formatter);
+        if let Err(e) = value.serialize(&mut serializer) {
+            return Err(format!("Could not render the value: {}.", e));
+        }
+        let line = &self.text[get_line_start(self.text, position)..];
+        let indent = &line[..get_indentation(line)];
+        Ok(String::from_utf8_lossy(&rendered_value).replace('\n', &format!("\n{}", indent)))
+    }
+
+    fn render_key(&self, key: &str, flow: bool) -> Result<String, String> {
+        let key = Value::String(key.to_string());
+        match flow {
+            true => self.render_json(&key, 0),
+            false => self.render_yaml(&key, 0),
+        }
+    }
+
+    // Gets the text separating the items of a flow collection, keeping the items
+    // on separate lines if they were on separate lines.
+    fn get_flow_separator(&self, collection_start: usize, item_start: usize) -> String {
+        let line_start = get_line_start(self.text, item_start);
+        if line_start == get_line_start(self.text, collection_start) {
+            return " ".to_string();
+        }
+        format!("\n{}", &self.text[line_start..item_start])
+    }
+
+    // Extends a range of removed lines over the comments directly above it, which describe the
+    // removed value. The blank lines separating it from the next value are removed too, or the ones
+    // separating it from the previous value when it is the last one, so that no stray blank line is left.
+    fn extend_removed_lines(&self, start: usize, end: usize, is_last: bool) -> (usize, usize) {
+        let start = self.get_comment_lines_start(start);
+        if !is_last {
+            let mut end = end;
+            while end < self.text.len() && self.text[end..get_line_end(self.text, end)].trim().is_empty() {
+                end = get_next_line_start(self.text, end);
+            }
+            return (start, end);
+        }
+        let mut extended_start = start;
+        while extended_start > 0 {
+            let previous_line_start = get_line_start(self.text, extended_start - 1);
+            if !self.text[previous_line_start..extended_start].trim().is_empty() {
+                break;
+            }
+            extended_start = previous_line_start;
+        }
+        (extended_start, end)
+    }
+
+    // Gets the start of the comment lines directly above a line, with the same indentation.
+    // These comments describe the value starting on that line.
+    fn get_comment_lines_start(&self, line_start: usize) -> usize {
+        let mut start = line_start;
+        let indentation = get_indentation(&self.text[start..]);
+        while start > 0 {
+            let previous_line_start = get_line_start(self.text, start - 1);
+            let previous_line = &self.text[previous_line_start..start];
+            if !previous_line.trim_start().starts_with('#') || get_indentation(previous_line) != indentation {
+                break;
+            }
+            start = previous_line_start;
+        }
+        start
+    }
+
+    // Gets the start of the line of the dash of a block sequence item.
+    fn get_item_start(&self, item: &Node) -> usize {
+        match self.text[..item.start].trim_end().strip_suffix('-') {
+            Some(t) => get_line_start(self.text, t.len()),
+            None => get_line_start(self.text, item.start),
+        }
+    }
+
+    fn replace_node(&self, node: &Node, new_value: &Value, edits: &mut Vec<Edit>) -> Result<(), String> {
+        let mut start = node.start;
+        let line_start = get_line_start(self.text, node.start);
+        let prefix = self.text[line_start..node.start].trim_end();
+        let text = if self.is_json || node.in_flow {
+            self.render_json(new_value, node.start)?
+        } else if !is_complex(new_value) {
+            let rendered_value = self.render_yaml(new_value, 0)?;
+            match node.start == node.end {
+                true => format!(" {}", rendered_value),
+                false => rendered_value,
+            }
+        } else if prefix.is_empty() || prefix.ends_with('-') {
+            self.render_yaml(new_value, get_column(self.text, node.start))?
+        } else {
+            // A block collection cannot start on the line of its key.
+            start = line_start + prefix.len();
+            let indent = get_indentation(&self.text[line_start..]) + 2;
+            format!("\n{}{}", " ".repeat(indent), self.render_yaml(new_value, indent)?)
+        };
+        edits.push(Edit { start, end: node.end, text });
+        Ok(())
+    }
+
+    fn reconcile(&self, node: &Node, old_value: &Value, new_value: &Value, edits: &mut Vec<Edit>) -> Result<(), String> {
+        if old_value == new_value {
+            return Ok(());
+        }
+        match (&node.kind, old_value, new_value) {
+            (NodeKind::Mapping(entries), Value::Mapping(old_mapping), Value::Mapping(new_mapping)) => {
+                self.reconcile_mapping(node, entries, old_mapping, new_mapping, edits)
+            }
+            (NodeKind::Sequence(items), Value::Sequence(old_items), Value::Sequence(new_items)) if items.len() == old_items.len() => {
+                self.reconcile_sequence(node, items, old_items, new_items, edits)
+            }
+            _ => self.replace_node(node, new_value, edits),
+        }
+    }
+
+    fn reconcile_mapping(
+        &self,
+        node: &Node,
+        entries: &[Entry],
+        old_mapping: &serde_yaml::Mapping,
+        new_mapping: &serde_yaml::Mapping,
+        edits: &mut Vec<Edit>,
+    ) -> Result<(), String> {
+        let mut added_entries: Vec<(&str, &Value)> = vec![];
+        let mut changed_entries: Vec<(&Entry, &Value)> = vec![];
+        for (key, new_entry_value) in new_mapping.iter() {
+            let key = match key.as_str() {
+                Some(k) => k,
+                None => return Err("Only the string keys are supported.".to_string()),
+            };
+            match entries.iter().find(|e| e.key == key) {
+                Some(entry) => changed_entries.push((entry, new_entry_value)),
+                None => added_entries.push((key, new_entry_value)),
+            }
+        }
+        // The keys that are in the file but were not serialized, for example because their
+        // value is empty, are kept.
+        let removed_entries: Vec<usize> = (0..entries.len())
+            .filter(|i| {
+                let key = Value::String(entries[*i].key.to_string());
+                old_mapping.contains_key(&key) && !new_mapping.contains_key(&key)
+            })
+            .collect();
+        let last_kept_entry = match (0..entries.len()).rev().find(|i| !removed_entries.contains(i)) {
+            Some(i) => &entries[i],
+            None => return self.replace_node(node, &Value::Mapping(new_mapping.clone()), edits),
+        };
+
+        for (entry, new_entry_value) in changed_entries {
+            match old_mapping.get(&Value::String(entry.key.to_string())) {
+                Some(old_entry_value) => self.reconcile(&entry.value, old_entry_value, new_entry_value, edits)?,
+                None => self.replace_node(&entry.value, new_entry_value, edits)?,
+            }
+        }
+
+        let removed_ranges = get_runs(&removed_entries)
+            .iter()
+            .map(|(first, last)| {
+                let (first_entry, last_entry, next_entry) = (&entries[*first], &entries[*last], entries.get(last + 1));
+                if node.flow {
+                    return match (*first > 0, next_entry) {
+                        (true, _) => (entries[first - 1].value.end, last_entry.value.end),
+                        (false, Some(next_entry)) => (first_entry.key_start, next_entry.key_start),
+                        (false, None) => (first_entry.key_start, last_entry.value.end),
+                    };
+                }
+                let line_start = get_line_start(self.text, first_entry.key_start);
+                match (self.text[line_start..first_entry.key_start].trim().is_empty(), next_entry) {
+                    // The first key of a mapping can be on the line of the dash of a sequence item.
+                    (false, Some(next_entry)) => (first_entry.key_start, next_entry.key_start),
+                    _ => self.extend_removed_lines(line_start, get_next_line_start(self.text, last_entry.value.end), next_entry.is_none()),
+                }
+            })
+            .collect();
+        remove_ranges(removed_ranges, edits);
+
+        if added_entries.is_empty() {
+            return Ok(());
+        }
+        let mut text = "".to_string();
+        let position = match node.flow {
+            true => {
+                let separator = self.get_flow_separator(node.start, last_kept_entry.key_start);
+                for (key, value) in added_entries {
+                    let rendered_value = self.render_json(value, last_kept_entry.key_start)?;
+                    text.push_str(&format!(",{}{}: {}", separator, self.render_key(key, true)?, rendered_value));
+                }
+                last_kept_entry.value.end
+            }
+            false => {
+                let key_column = get_column(self.text, last_kept_entry.key_start);
+                for (key, value) in added_entries {
+                    let rendered_value = match is_complex(value) {
+                        true => format!("\n{}{}", " ".repeat(key_column + 2), self.render_yaml(value, key_column + 2)?),
+                        false => format!(" {}", self.render_yaml(value, 0)?),
+                    };
+                    text.push_str(&format!("\n{}{}:{}", " ".repeat(key_column), self.render_key(key, false)?, rendered_value));
+                }
+                get_line_end(self.text, last_kept_entry.value.end)
+            }
+        };
+        edits.push(Edit {
+            start: position,
+            end: position,
+            text,
+        });
+        Ok(())
+    }
+
+    fn reconcile_sequence(&self, node: &Node, items: &[Node], old_items: &[Value], new_items: &[Value], edits: &mut Vec<Edit>) -> Result<(), String> {
+        let operations = align_sequences(old_items, new_items);
+        let last_kept_item = operations
+            .iter()
+            .filter_map(|o| match o {
+                SequenceOperation::Keep(old_index, _) => Some(*old_index),
+                _ => None,
+            })
+            .max();
+        let last_kept_item = match last_kept_item {
+            Some(i) => &items[i],
+            None => return self.replace_node(node, &Value::Sequence(new_items.to_vec()), edits),
+        };
+
+        let mut removed_items: Vec<usize> = vec![];
+        let mut pending_items: Vec<&Value> = vec![];
+        for operation in operations {
+            match operation {
+                SequenceOperation::Keep(old_index, new_index) => {
+                    if !pending_items.is_empty() {
+                        self.insert_items(node, &items[old_index], true, &pending_items, edits)?;
+                        pending_items.clear();
+                    }
+                    self.reconcile(&items[old_index], &old_items[old_index], &new_items[new_index], edits)?;
+                }
+                SequenceOperation::Remove(old_index) => removed_items.push(old_index),
+                SequenceOperation::Insert(new_index) => pending_items.push(&new_items[new_index]),
+            }
+        }
+        if !pending_items.is_empty() {
+            self.insert_items(node, last_kept_item, false, &pending_items, edits)?;
+        }
+
+        removed_items.sort();
+        let removed_ranges = match node.flow {
+            true => get_runs(&removed_items)
+                .iter()
+                .map(|(first, last)| match (*first > 0, items.get(last + 1)) {
+                    (true, _) => (items[first - 1].end, items[*last].end),
+                    (false, Some(next_item)) => (items[*first].start, next_item.start),
+                    (false, None) => (items[*first].start, items[*last].end),
+                })
+                .collect(),
+            false => get_runs(&removed_items)
+                .iter()
+                .map(|(first, last)| {
+                    let (start, end) = (self.get_item_start(&items[*first]), get_next_line_start(self.text, items[*last].end));
+                    self.extend_removed_lines(start, end, last + 1 == items.len())
+                })
+                .collect(),
+        };
+        remove_ranges(removed_ranges, edits);
+        Ok(())
+    }
+
+    // Inserts items in a sequence, before or after one of the items of the sequence.
+    fn insert_items(&self, node: &Node, item: &Node, before: bool, values: &[&Value], edits: &mut Vec<Edit>) -> Result<(), String> {
+        let mut rendered_items: Vec<String> = vec![];
+        let (position, text) = match node.flow {
+            true => {
+                let separator = self.get_flow_separator(node.start, item.start);
+                for value in values {
+                    rendered_items.push(self.render_json(value, item.start)?);
+                }
+                let rendered_items = rendered_items.join(&format!(",{}", separator));
+                match before {
+                    true => (item.start, format!("{},{}", rendered_items, separator)),
+                    false => (item.end, format!(",{}{}", separator, rendered_items)),
+                }
+            }
+            false => {
+                let item_start = self.get_item_start(item);
+                let dash_column = get_indentation(&self.text[item_start..]);
+                for value in values {
+                    rendered_items.push(format!("{}- {}", " ".repeat(dash_column), self.render_yaml(value, dash_column + 2)?));
+                }
+                // The items inserted before an item must not separate it from its comments.
+                match before {
+                    true => (self.get_comment_lines_start(item_start), format!("{}\n", rendered_items.join("\n"))),
+                    false => (get_line_end(self.text, item.end), format!("\n{}", rendered_items.join("\n"))),
+                }
+            }
+        };
+        edits.push(Edit {
+            start: position,
+            end: position,
+            text,
+        });
+        Ok(())
+    }
+}
+
+fn remove_ranges(ranges: Vec<(usize, usize)>, edits: &mut Vec<Edit>) {
+    for (start, end) in ranges {
+        edits.push(Edit {
+            start,
+            end,
+            text: "".to_string(),
+        });
+    }
+}
+
+// Groups sorted indexes into runs of consecutive indexes.
+fn get_runs(indexes: &[usize]) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = vec![];
+    for index in indexes {
+        match runs.last_mut() {
+            Some((_, last)) if *last + 1 == *index => *last = *index,
+            _ => runs.push((*index, *index)),
+        }
+    }
+    runs
+}
+
+#[derive(Debug, PartialEq)]
+enum SequenceOperation {
+    Keep(usize, usize),
+    Remove(usize),
+    Insert(usize),
+}
+
+// The items with a name, like the modules, are matched by name.
+fn get_item_name(value: &Value) -> Option<&str> {
+    value.get("name").and_then(|n| n.as_str())
+}
+
+fn is_same_item(old_item: &Value, new_item: &Value) -> bool {
+    match (get_item_name(old_item), get_item_name(new_item)) {
+        (Some(old_name), Some(new_name)) => old_name == new_name,
+        (None, None) => old_item == new_item,
+        _ => false,
+    }
+}
+
+// Aligns the items of two versions of a sequence, using the longest common subsequence of
+// the matching items. The unnamed items that were changed, like the sources pinned to
+// another commit, are paired in order so that they are edited instead of replaced.
+fn align_sequences(old_items: &[Value], new_items: &[Value]) -> Vec<SequenceOperation> {
+    let (old_len, new_len) = (old_items.len(), new_items.len());
+    let mut lengths = vec![vec![0usize; new_len + 1]; old_len + 1];
+    for i in (0..old_len).rev() {
+        for j in (0..new_len).rev() {
+            lengths[i][j] = match is_same_item(&old_items[i], &new_items[j]) {
+                true => lengths[i + 1][j + 1] + 1,
+                false => lengths[i + 1][j].max(lengths[i][j + 1]),
+            };
+        }
+    }
+
+    let mut operations: Vec<SequenceOperation> = vec![];
+    let mut removed: Vec<usize> = vec![];
+    let mut inserted: Vec<usize> = vec![];
+    let flush = |removed: &mut Vec<usize>, inserted: &mut Vec<usize>, operations: &mut Vec<SequenceOperation>| {
+        let unnamed_removed: Vec<usize> = removed.iter().cloned().filter(|i| get_item_name(&old_items[*i]).is_none()).collect();
+        let unnamed_inserted: Vec<usize> = inserted.iter().cloned().filter(|i| get_item_name(&new_items[*i]).is_none()).collect();
+        for (old_index, new_index) in unnamed_removed.iter().zip(unnamed_inserted.iter()) {
+            operations.push(SequenceOperation::Keep(*old_index, *new_index));
+            removed.retain(|i| i != old_index);
+            inserted.retain(|i| i != new_index);
+        }
+        operations.extend(removed.drain(..).map(SequenceOperation::Remove));
+        operations.extend(inserted.drain(..).map(SequenceOperation::Insert));
+    };
+
+    let (mut i, mut j) = (0, 0);
+    while i < old_len || j < new_len {
+        if i < old_len && j < new_len && is_same_item(&old_items[i], &new_items[j]) {
+            flush(&mut removed, &mut inserted, &mut operations);
+            operations.push(SequenceOperation::Keep(i, j));
+            i += 1;
+            j += 1;
+        } else if i < old_len && (j == new_len || lengths[i + 1][j] >= lengths[i][j + 1]) {
+            removed.push(i);
+            i += 1;
+        } else {
+            inserted.push(j);
+            j += 1;
+        }
+    }
+    flush(&mut removed, &mut inserted, &mut operations);
+    operations
+}
+
+// Applies the differences between two serialized versions of a manifest to the original text of the manifest.
+pub fn apply_changes(content: &str, format: &ManifestFormat, old_value: &Value, new_value: &Value) -> Result<String, String> {
+    let document = Document::parse(content, format)?;
+    let root = document.get_root()?;
+    let mut edits: Vec<Edit> = vec![];
+    document.reconcile(&root, old_value, new_value, &mut edits)?;
+
+    // The edits are applied from the end of the file, so that the positions of the other edits stay valid.
+    // The insertions at the same position are applied in reverse, so that they keep their order.
+    let mut edits: Vec<(usize, Edit)> = edits.into_iter().enumerate().collect();
+    edits.sort_by(|(a_index, a), (b_index, b)| b.start.cmp(&a.start).then(b.end.cmp(&a.end)).then(b_index.cmp(a_index)));
+    let mut updated_content = content.to_string();
+    for (_, edit) in edits {
+        updated_content.replace_range(edit.start..edit.end, &edit.text);
+    }
+    Ok(updated_content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YAML_MANIFEST: &str = r###"# The manifest of GNOME Clocks.
+app-id: org.gnome.Clocks
+runtime: org.gnome.Platform
+runtime-version: "40"    # Keep in sync with the SDK.
+sdk: org.gnome.Sdk
+command: gnome-clocks
+modules:
+    # Needed for the weather.
+    - name: libgweather
+      buildsystem: meson
+      sources:
+          - type: git
+            url: https://gitlab.gnome.org/GNOME/libgweather.git
+            tag: "40.0"
+
+    - name: gnome-clocks
+      buildsystem: meson
+      sources:
+          - type: git
+            url: https://gitlab.gnome.org/GNOME/gnome-clocks.git
+"###;
+
+    const JSON_MANIFEST: &str = r###"{
+  "app-id": "org.gnome.Clocks",
+  "runtime": "org.gnome.Platform",
+  "runtime-version": "40",
+  "sdk": "org.gnome.Sdk",
+  "modules": [
+    {
+      "name": "libgweather",
+      "sources": [
+        {
+          "type": "git",
+          "url": "https://gitlab.gnome.org/GNOME/libgweather.git",
+          "tag": "40.0"
+        }
+      ]
+    },
+    {
+      "name": "gnome-clocks"
+    }
+  ]
+}
+"###;
+
+    fn to_value(content: &str) -> Value {
+        serde_yaml::from_str(content).unwrap()
+    }
+
+    // Applies a change to a manifest, and checks that the edited manifest has the change.
+    fn edit(content: &str, format: ManifestFormat, change: impl Fn(&mut Value)) -> String {
+        let old_value = to_value(content);
+        let mut new_value = old_value.clone();
+        change(&mut new_value);
+        let updated_content = apply_changes(content, &format, &old_value, &new_value).unwrap();
+        assert_eq!(to_value(&updated_content), new_value);
+        updated_content
+    }
+
+    fn get_modules(value: &mut Value) -> &mut Vec<Value> {
+        value.get_mut("modules").unwrap().as_sequence_mut().unwrap()
+    }
+
+    fn get_source(value: &mut Value) -> &mut serde_yaml::Mapping {
+        let module = &mut get_modules(value)[0];
+        module.get_mut("sources").unwrap().as_sequence_mut().unwrap()[0].as_mapping_mut().unwrap()
+    }
+
+    fn new_module(name: &str) -> Value {
+        to_value(&format!("name: {}\nbuildsystem: autotools\nconfig-opts:\n  - --disable-docs\n", name))
+    }
+
+    #[test]
+    pub fn test_change_source_pin() {
+        let updated_content = edit(YAML_MANIFEST, ManifestFormat::YAML, |v| {
+            get_source(v).insert(Value::from("tag"), Value::from("41.0"));
+        });
+        assert_eq!(updated_content, YAML_MANIFEST.replace("tag: \"40.0\"", "tag: \"41.0\""));
+
+        let updated_content = edit(YAML_MANIFEST, ManifestFormat::YAML, |v| {
+            let source = get_source(v);
+            source.remove(&Value::from("tag"));
+            source.insert(Value::from("commit"), Value::from("c0ffee"));
+        });
+        assert_eq!(updated_content, YAML_MANIFEST.replace("tag: \"40.0\"", "commit: c0ffee"));
+
+        let updated_content = edit(JSON_MANIFEST, ManifestFormat::JSON, |v| {
+            get_source(v).insert(Value::from("tag"), Value::from("41.0"));
+        });
+        assert_eq!(updated_content, JSON_MANIFEST.replace("\"tag\": \"40.0\"", "\"tag\": \"41.0\""));
+    }
+
+    #[test]
+    pub fn test_add_module() {
+        let updated_content = edit(YAML_MANIFEST, ManifestFormat::YAML, |v| get_modules(v).insert(0, new_module("libical")));
+        assert!(updated_content.starts_with("# The manifest of GNOME Clocks.\n"));
+        assert!(updated_content.contains("runtime-version: \"40\"    # Keep in sync with the SDK.\n"));
+        assert!(updated_content.contains("modules:\n    - name: libical\n      buildsystem: autotools\n"));
+        assert!(updated_content.contains("      config-opts:\n        - \"--disable-docs\"\n    # Needed for the weather.\n    - name: libgweather\n"));
+
+        let updated_content = edit(YAML_MANIFEST, ManifestFormat::YAML, |v| get_modules(v).push(new_module("libical")));
+        assert!(updated_content
+            .ends_with("gnome-clocks.git\n    - name: libical\n      buildsystem: autotools\n      config-opts:\n        - \"--disable-docs\"\n"));
+
+        let updated_content = edit(JSON_MANIFEST, ManifestFormat::JSON, |v| get_modules(v).push(to_value("name: libical")));
+        assert!(updated_content.ends_with("      \"name\": \"gnome-clocks\"\n    },\n    {\n      \"name\": \"libical\"\n    }\n  ]\n}\n"));
+    }
+
+    #[test]
+    pub fn test_remove_module() {
+        let updated_content = edit(YAML_MANIFEST, ManifestFormat::YAML, |v| {
+            get_modules(v).remove(1);
+        });
+        assert!(updated_content.ends_with("            tag: \"40.0\"\n"));
+
+        let updated_content = edit(YAML_MANIFEST, ManifestFormat::YAML, |v| {
+            get_modules(v).remove(0);
+        });
+        assert!(updated_content.contains("command: gnome-clocks\nmodules:\n    - name: gnome-clocks\n"));
+        assert!(updated_content.contains("# Keep in sync with the SDK."));
+
+        let updated_content = edit(JSON_MANIFEST, ManifestFormat::JSON, |v| {
+            get_modules(v).remove(0);
+        });
+        assert!(updated_content.contains("  \"modules\": [\n    {\n      \"name\": \"gnome-clocks\"\n    }\n  ]\n"));
+
+        let updated_content = edit(JSON_MANIFEST, ManifestFormat::JSON, |v| {
+            get_modules(v).remove(1);
+        });
+        assert!(updated_content.contains("        }\n      ]\n    }\n  ]\n}\n"));
+    }
+
+    #[test]
+    pub fn test_align_sequences() {
+        let old_items: Vec<Value> = vec![to_value("name: a"), to_value("name: b"), to_value("type: git")];
+        let new_items: Vec<Value> = vec![to_value("name: c"), to_value("name: a"), to_value("type: archive")];
+        assert_eq!(
+            align_sequences(&old_items, &new_items),
+            vec![
+                SequenceOperation::Insert(0),
+                SequenceOperation::Keep(0, 1),
+                SequenceOperation::Keep(2, 2),
+                SequenceOperation::Remove(1),
+            ]
+        );
+    }
+}
@@ -8,11 +8,20 @@ use std::time::SystemTime;
 
 use serde::{Deserialize, Serialize};
 
-use crate::modules::{BuildSystem, SoftwareModule};
+use crate::appstream::AppStreamComponent;
+use crate::checksums::ChecksumReport;
+use crate::exports::ExportReport;
+use crate::modules::{AbstractSource, BuildSystem, SoftwareModule, SourceType};
+use crate::permissions::{PermissionDiff, PermissionSet};
+use crate::runtimes::RuntimeLibrary;
 
 const DEFAULT_FLATPAK_BUILDER_CACHE_DIR: &str = ".flatpak-builder";
 const DEFAULT_FLATPAK_OUTPUT_DIR: &str = "build";
 
+// The AppStream component types that describe an application. The legacy
+// appdata files did not always declare a type.
+const APPLICATION_COMPONENT_TYPES: &[&str] = &["", "desktop", "desktop-application", "console-application"];
+
 // Other choices are org.gnome.Platform and org.kde.Platform
 const DEFAULT_RUNTIME: &str = "org.freedesktop.Platform";
 const DEFAULT_RUNTIME_VERSION: &str = "master";
@@ -255,6 +264,49 @@ impl FlatpakManifest {
         return "flatpak";
     }
 
+    // Gets the id of the application, which can be defined with either app-id or id.
+    pub fn get_id(&self) -> &str {
+        match self.app_id.is_empty() {
+            true => &self.id,
+            false => &self.app_id,
+        }
+    }
+
+    pub fn get_permissions(&self) -> Result<PermissionSet, String> {
+        PermissionSet::parse(&self.finish_args)
+    }
+
+    // Gets the permissions granted or revoked by another version of the manifest.
+    pub fn diff_permissions(&self, other_manifest: &FlatpakManifest) -> Result<PermissionDiff, String> {
+        Ok(self.get_permissions()?.diff(&other_manifest.get_permissions()?))
+    }
+
+    // Gets the libraries shipped in the sdk used by the manifest.
+    pub fn get_runtime_libraries(&self) -> Vec<RuntimeLibrary> {
+        let sdk = if self.sdk.is_empty() { DEFAULT_SDK } else { &self.sdk };
+        let runtime_version = if self.runtime_version.is_empty() {
+            DEFAULT_RUNTIME_VERSION
+        } else {
+            &self.runtime_version
+        };
+        let catalogs = crate::runtimes::get_all_catalogs();
+        crate::runtimes::get_runtime_libraries(&catalogs, sdk, runtime_version)
+    }
+
+    // Gets the names of the libraries and modules that are already provided to
+    // the modules of the manifest, either by the sdk or by another module.
+    pub fn get_provided_names(&self) -> Vec<String> {
+        let mut provided_names: Vec<String> = vec![];
+        for library in self.get_runtime_libraries() {
+            provided_names.push(library.name);
+            provided_names.extend(library.pkg_config_names);
+        }
+        for module in &self.modules {
+            provided_names.push(module.name.to_string());
+        }
+        provided_names
+    }
+
     pub fn file_path_matches(path: &str) -> bool {
         let parts: Vec<&str> = path.split("/").collect();
         if parts.len() == 0 {
@@ -303,6 +355,22 @@ impl FlatpakManifest {
         Some(flatpak_manifest)
     }
 
+    // Loads a manifest with its included module and source files inlined. The included files
+    // are resolved relative to the file including them, so the manifest should not be dumped
+    // over the original file.
+    pub fn load_with_includes(manifest_path: &path::Path) -> Result<FlatpakManifest, String> {
+        let mut manifest_value = load_yaml_value(manifest_path)?;
+        resolve_includes(&mut manifest_value, manifest_path.parent().unwrap_or(path::Path::new("")))?;
+        let manifest_content = match serde_yaml::to_string(&manifest_value) {
+            Ok(c) => c,
+            Err(e) => return Err(format!("Could not resolve the includes of {}: {}.", manifest_path.display(), e)),
+        };
+        match FlatpakManifest::parse(&manifest_content) {
+            Some(m) => Ok(m),
+            None => Err(format!("Could not parse Flatpak manifest {}.", manifest_path.display())),
+        }
+    }
+
     pub fn dump(&self, format: &crate::manifests::manifest::ManifestFormat) -> Result<String, String> {
         if let crate::manifests::manifest::ManifestFormat::JSON = format {
             return match serde_json::to_string_pretty(&self) {
@@ -323,8 +391,7 @@ impl FlatpakManifest {
 
     pub fn get_modules(&self) -> Vec<SoftwareModule> {
         let mut response = vec![];
-        // FIXME we should fetch those recursively.
-        for module in &self.modules {
+        for module in get_all_modules(&self.modules) {
             let software_module = module.to_module();
             // FIXME should we check for duplicates here??
             response.push(software_module);
@@ -484,10 +551,9 @@ impl FlatpakModule {
         if self.buildsystem == "meson" {
             software_module.build_system = BuildSystem::Meson;
         }
-        // FIXME not sure what to do with this one. Maybe we should support having a list
-        // of build systems?
+        // cmake-ninja is cmake, using ninja instead of make as the backend.
         if self.buildsystem == "cmake-ninja" {
-            software_module.build_system = BuildSystem::Meson;
+            software_module.build_system = BuildSystem::Cmake;
         }
         if self.buildsystem == "simple" {
             software_module.build_system = BuildSystem::Unknown;
@@ -499,7 +565,8 @@ impl FlatpakModule {
         // Skip the flatpak modules with more than 1 source, because those are harder
         // to map with a source code repository.
         if self.sources.len() == 0 {
-            panic!("Cannot convert flatpak module: no sources available.");
+            log::debug!("Flatpak module {} has no sources.", self.name);
+            return software_module;
         }
 
         let sources = &self.sources[0];
@@ -515,6 +582,15 @@ impl FlatpakModule {
         software_module.url = sources.url.as_ref().unwrap().to_string();
 
         software_module.tag = sources.tag.as_ref().unwrap_or(&"".to_string()).to_string();
+        software_module.commit = sources.commit.as_ref().unwrap_or(&"".to_string()).to_string();
+        software_module.archive_checksum = sources.sha256.as_ref().unwrap_or(&"".to_string()).to_string();
+        software_module.source_type = match sources.r#type.as_str() {
+            "git" => SourceType::Git,
+            "bzr" => SourceType::Bzr,
+            "svn" => SourceType::Svn,
+            "archive" => SourceType::Tarball,
+            _ => SourceType::Unknown,
+        };
 
         software_module.config_options = self.config_opts.to_owned();
         software_module.build_commands = self.build_commands.to_owned();
@@ -529,6 +605,18 @@ impl FlatpakModule {
     }
 }
 
+// A version of a module, as found in the history of a manifest.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ModuleRevision {
+    // The path of the manifest, relative to the root of the repo.
+    pub manifest_path: String,
+    // The hash of the commit that introduced this version of the module.
+    pub commit: String,
+    // The time of the commit, in seconds since the epoch.
+    pub timestamp: i64,
+    pub module: FlatpakModule,
+}
+
 pub const ALLOWED_SOURCE_TYPES: [&'static str; 10] = ["archive", "git", "bzr", "svn", "dir", "file", "script", "shell", "patch", "extra-data"];
 
 // The sources are a list pointer to the source code that needs to be extracted into
@@ -554,9 +642,18 @@ pub struct FlatpakSource {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tag: Option<String>,
 
+    // The commit to use from the git repository.
+    // If branch or tag is also specified, then it is verified that the branch/tag is at this specific commit.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+
     // The name of the branch to checkout.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub branch: Option<String>,
+
+    // The sha256 checksum of the file, for archive and file sources.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
 }
 
 // Extension define extension points in the app/runtime that can be implemented by extensions,
@@ -715,18 +812,331 @@ pub fn add_module(manifest: &mut FlatpakManifest, new_module: &SoftwareModule) -
     let mut new_flatpak_module = FlatpakModule::default();
     new_flatpak_module.name = new_module.name.to_string();
 
-    let mut flatpak_sources = FlatpakSource::default();
-    flatpak_sources.r#type = "git".to_string(); // FIXME use the url_type
-    flatpak_sources.url = Some(new_module.url.to_string());
-    // This is the default, unless a version is explicitely declared.
-    flatpak_sources.branch = Some("master".to_string());
-    new_flatpak_module.sources = vec![flatpak_sources];
+    let (buildsystem, config_opts) = get_build_system_options(&new_module.build_system);
+    new_flatpak_module.buildsystem = buildsystem.to_string();
+    new_flatpak_module.config_opts = config_opts;
+    if !new_module.config_options.is_empty() {
+        new_flatpak_module.config_opts = new_module.config_options.to_owned();
+    }
+    new_flatpak_module.build_commands = new_module.build_commands.to_owned();
+    if new_flatpak_module.buildsystem == "simple" && new_flatpak_module.build_commands.is_empty() {
+        new_flatpak_module.build_commands = get_default_build_commands(&new_module.build_system)?;
+    }
+
+    new_flatpak_module.sources = vec![get_module_source(new_module)];
 
     manifest.modules.insert(0, new_flatpak_module);
 
     Ok(manifest.get_modules())
 }
 
+// Gets a pinned source for the module. Git sources are pinned to a tag and
+// a commit when available, and archive sources to a checksum.
+pub fn get_module_source(module: &SoftwareModule) -> FlatpakSource {
+    let mut flatpak_source = FlatpakSource::default();
+    if module.source_type.is_archive() {
+        flatpak_source.r#type = "archive".to_string();
+    } else {
+        // FIXME handle the other source types.
+        flatpak_source.r#type = "git".to_string();
+    }
+    pin_source(&mut flatpak_source, module);
+    if flatpak_source.r#type == "git" && flatpak_source.tag.is_none() && flatpak_source.commit.is_none() {
+        // This is the default, unless a version is explicitely declared.
+        flatpak_source.branch = Some("master".to_string());
+    }
+    flatpak_source
+}
+
+// Pins an existing source to the version of the module, leaving the
+// other fields of the source untouched.
+fn pin_source(flatpak_source: &mut FlatpakSource, module: &SoftwareModule) {
+    if !module.url.is_empty() {
+        flatpak_source.url = Some(module.url.to_string());
+    }
+    if flatpak_source.r#type == "archive" {
+        // A checksum from another version of the archive would be wrong.
+        flatpak_source.sha256 = None;
+        if !module.archive_checksum.is_empty() {
+            flatpak_source.sha256 = Some(module.archive_checksum.to_string());
+        }
+        return;
+    }
+    if module.tag.is_empty() && module.commit.is_empty() {
+        return;
+    }
+    flatpak_source.tag = None;
+    if !module.tag.is_empty() {
+        flatpak_source.tag = Some(module.tag.to_string());
+    }
+    flatpak_source.commit = None;
+    if !module.commit.is_empty() {
+        flatpak_source.commit = Some(module.commit.to_string());
+    }
+    flatpak_source.branch = None;
+}
+
+// Gets all the modules of the manifest, including the nested modules.
+fn get_all_modules(modules: &Vec<FlatpakModule>) -> Vec<&FlatpakModule> {
+    let mut all_modules = vec![];
+    for module in modules {
+        all_modules.push(module);
+        all_modules.extend(get_all_modules(&module.modules));
+    }
+    all_modules
+}
+
+fn find_module_mut<'a>(modules: &'a mut Vec<FlatpakModule>, module_name: &str) -> Option<&'a mut FlatpakModule> {
+    for module in modules.iter_mut() {
+        if module.name == module_name {
+            return Some(module);
+        }
+        if let Some(m) = find_module_mut(&mut module.modules, module_name) {
+            return Some(m);
+        }
+    }
+    None
+}
+
+fn remove_module_from(modules: &mut Vec<FlatpakModule>, module_name: &str) -> Option<FlatpakModule> {
+    if let Some(position) = modules.iter().position(|m| m.name == module_name) {
+        return Some(modules.remove(position));
+    }
+    for module in modules.iter_mut() {
+        if let Some(m) = remove_module_from(&mut module.modules, module_name) {
+            return Some(m);
+        }
+    }
+    None
+}
+
+// Gets the name of the module in which a module is nested, if any.
+pub fn get_parent_module_name(manifest: &FlatpakManifest, module_name: &str) -> Option<String> {
+    for module in get_all_modules(&manifest.modules) {
+        if module.modules.iter().any(|m| m.name == module_name) {
+            return Some(module.name.to_string());
+        }
+    }
+    None
+}
+
+// Removes a module from the manifest, wherever it is nested.
+// The modules nested in the removed module are also removed.
+// Returns the names of all the modules that were removed.
+pub fn remove_module(manifest: &mut FlatpakManifest, module_name: &str) -> Result<Vec<String>, String> {
+    match remove_module_from(&mut manifest.modules, module_name) {
+        Some(removed_module) => Ok(get_all_modules(&vec![removed_module]).iter().map(|m| m.name.to_string()).collect()),
+        None => Err(format!("No module named {}.", module_name)),
+    }
+}
+
+// Gets the sources of all the modules that have to be downloaded.
+pub fn get_all_sources(manifest: &FlatpakManifest) -> Vec<AbstractSource> {
+    let mut sources = vec![];
+    for module in get_all_modules(&manifest.modules) {
+        for source in &module.sources {
+            let url = match &source.url {
+                Some(u) => u.to_string(),
+                None => continue,
+            };
+            let source_type = match source.r#type.as_str() {
+                "git" => SourceType::Git,
+                "archive" => SourceType::Tarball,
+                "file" => SourceType::File,
+                _ => continue,
+            };
+            sources.push(AbstractSource {
+                url: url,
+                source_type: source_type,
+                sha256: source.sha256.clone(),
+                commit: source.commit.clone(),
+            });
+        }
+    }
+    sources
+}
+
+fn load_yaml_value(file_path: &path::Path) -> Result<serde_yaml::Value, String> {
+    let content = match std::fs::read_to_string(file_path) {
+        Ok(c) => c,
+        Err(e) => return Err(format!("Could not read file {}: {}.", file_path.display(), e)),
+    };
+    // JSON is a subset of YAML, so both formats are parsed as YAML.
+    match serde_yaml::from_str(&content) {
+        Ok(v) => Ok(v),
+        Err(e) => return Err(format!("Could not parse file {}: {}.", file_path.display(), e)),
+    }
+}
+
+// Replaces the string members of the modules and sources arrays with the content
+// of the files they name. An included sources file can contain a single source or an array.
+fn resolve_includes(value: &mut serde_yaml::Value, base_dir: &path::Path) -> Result<(), String> {
+    let mapping = match value.as_mapping_mut() {
+        Some(m) => m,
+        None => return Ok(()),
+    };
+    if let Some(serde_yaml::Value::Sequence(sources)) = mapping.get_mut(&serde_yaml::Value::String("sources".to_string())) {
+        let mut resolved_sources: Vec<serde_yaml::Value> = vec![];
+        for source in sources.drain(..) {
+            let source_path = match source {
+                serde_yaml::Value::String(p) => base_dir.join(p),
+                _ => {
+                    resolved_sources.push(source);
+                    continue;
+                }
+            };
+            match load_yaml_value(&source_path)? {
+                serde_yaml::Value::Sequence(included_sources) => resolved_sources.extend(included_sources),
+                included_source => resolved_sources.push(included_source),
+            };
+        }
+        *sources = resolved_sources;
+    }
+    if let Some(serde_yaml::Value::Sequence(modules)) = mapping.get_mut(&serde_yaml::Value::String("modules".to_string())) {
+        for module in modules.iter_mut() {
+            let module_path = match module {
+                serde_yaml::Value::String(p) => base_dir.join(p),
+                _ => {
+                    resolve_includes(module, base_dir)?;
+                    continue;
+                }
+            };
+            *module = load_yaml_value(&module_path)?;
+            resolve_includes(module, module_path.parent().unwrap_or(base_dir))?;
+        }
+    }
+    Ok(())
+}
+
+// Verifies the checksums of the archive and file sources of all the modules,
+// filling the missing checksums if fix is true.
+pub fn check_checksums(manifest: &mut FlatpakManifest, fix: bool) -> ChecksumReport {
+    let mut report = ChecksumReport::default();
+    check_modules_checksums(&mut manifest.modules, fix, &mut report);
+    report
+}
+
+// Fills the missing checksums of the sources of the named modules, without
+// checking the sources that already have a checksum.
+pub fn fill_modules_checksums(manifest: &mut FlatpakManifest, module_names: &[String]) -> ChecksumReport {
+    let mut report = ChecksumReport::default();
+    fill_modules_checksums_recursive(&mut manifest.modules, module_names, &mut report);
+    report
+}
+
+fn fill_modules_checksums_recursive(modules: &mut [FlatpakModule], module_names: &[String], report: &mut ChecksumReport) {
+    for module in modules.iter_mut() {
+        if module_names.contains(&module.name) {
+            for source in module.sources.iter_mut() {
+                if source.r#type != "archive" && source.r#type != "file" {
+                    continue;
+                }
+                if source.sha256.is_some() {
+                    continue;
+                }
+                let url = match &source.url {
+                    Some(u) => u.to_string(),
+                    None => continue,
+                };
+                crate::checksums::check_source_checksum(&url, &mut source.sha256, true, report);
+            }
+        }
+        fill_modules_checksums_recursive(&mut module.modules, module_names, report);
+    }
+}
+
+fn check_modules_checksums(modules: &mut [FlatpakModule], fix: bool, report: &mut ChecksumReport) {
+    for module in modules.iter_mut() {
+        for source in module.sources.iter_mut() {
+            if source.r#type != "archive" && source.r#type != "file" {
+                continue;
+            }
+            let url = match &source.url {
+                Some(u) => u.to_string(),
+                None => continue,
+            };
+            crate::checksums::check_source_checksum(&url, &mut source.sha256, fix, report);
+        }
+        check_modules_checksums(&mut module.modules, fix, report);
+    }
+}
+
+// Checks that the AppStream metadata of the application declares the id of the application,
+// otherwise the metadata is not found by the software centers. Returns the mismatches found.
+pub fn check_appstream_ids(manifest: &FlatpakManifest, components: &[(path::PathBuf, AppStreamComponent)]) -> Vec<String> {
+    let mut mismatches: Vec<String> = vec![];
+    let app_id = manifest.get_id();
+    if app_id.is_empty() {
+        return mismatches;
+    }
+    for (file_path, component) in components {
+        // The metadata of the addons and of the libraries are not about the application.
+        if !APPLICATION_COMPONENT_TYPES.contains(&component.component_type.as_str()) {
+            continue;
+        }
+        if component.get_app_id() == app_id {
+            continue;
+        }
+        // The ids based on the name of a renamed desktop file are rewritten by flatpak-builder.
+        let renamed_desktop_file = manifest.rename_desktop_file.trim_end_matches(".desktop");
+        if !renamed_desktop_file.is_empty() && component.get_app_id() == renamed_desktop_file {
+            continue;
+        }
+        mismatches.push(format!(
+            "{} declares the id {}, but the app-id of the manifest is {}",
+            file_path.display(),
+            component.id,
+            app_id
+        ));
+    }
+    mismatches
+}
+
+// Updates the version that the source of a module is pinned to.
+pub fn update_module(manifest: &mut FlatpakManifest, module: &SoftwareModule) -> Result<(), String> {
+    let flatpak_module = match find_module_mut(&mut manifest.modules, &module.name) {
+        Some(m) => m,
+        None => return Err(format!("No module named {}.", module.name)),
+    };
+    // See FlatpakModule::to_module, only the first source is associated with the module.
+    let flatpak_source = match flatpak_module.sources.first_mut() {
+        Some(s) => s,
+        None => return Err(format!("Module {} has no sources to update.", module.name)),
+    };
+    pin_source(flatpak_source, module);
+    Ok(())
+}
+
+// Gets the Flatpak buildsystem and the default config-opts to use for a build system.
+pub fn get_build_system_options(build_system: &BuildSystem) -> (&str, Vec<String>) {
+    match build_system {
+        BuildSystem::Meson => ("meson", vec!["--buildtype=release".to_string()]),
+        BuildSystem::Cmake => ("cmake-ninja", vec!["-DCMAKE_BUILD_TYPE=Release".to_string()]),
+        BuildSystem::Autotools => ("autotools", vec!["--disable-static".to_string()]),
+        BuildSystem::Qmake => ("qmake", vec![]),
+        BuildSystem::Make | BuildSystem::Cargo | BuildSystem::Npm | BuildSystem::Pip3 => ("simple", vec![]),
+        // The default buildsystem is autotools for flatpak-builder.
+        _ => ("", vec![]),
+    }
+}
+
+// Gets the build commands for the build systems that are not supported
+// natively by flatpak-builder.
+fn get_default_build_commands(build_system: &BuildSystem) -> Result<Vec<String>, String> {
+    match build_system {
+        BuildSystem::Make => Ok(vec!["make".to_string(), "make install PREFIX=${FLATPAK_DEST}".to_string()]),
+        BuildSystem::Pip3 => Ok(vec!["pip3 install --no-index --no-build-isolation --prefix=${FLATPAK_DEST} .".to_string()]),
+        // The network is not available during the builds, so the dependencies
+        // of those modules have to be added as sources of the module.
+        BuildSystem::Cargo | BuildSystem::Npm => Err(format!(
+            "Modules built with {} need their dependencies vendored as sources, which is not supported yet. \
+             Add the module with its build commands instead.",
+            build_system.get_name()
+        )),
+        _ => Ok(vec![]),
+    }
+}
+
 /// Setup the system
 pub fn setup(abstract_manifest: &crate::manifests::manifest::AbstractManifest) -> Result<String, String> {
     let child = Command::new("flatpak")
@@ -768,30 +1178,41 @@ pub fn is_setup(abstract_manifest: &crate::manifests::manifest::AbstractManifest
     return true;
 }
 
-pub fn run_build(abstract_manifest: &crate::manifests::manifest::AbstractManifest) -> Result<String, String> {
+pub fn run_build(abstract_manifest: &crate::manifests::manifest::AbstractManifest, offline: bool) -> Result<String, String> {
     let flatpak_cache_dir = path::Path::new(DEFAULT_FLATPAK_BUILDER_CACHE_DIR);
     if flatpak_cache_dir.is_dir() {
         let timestamp = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH);
         let backup_folder_name = format!("{}-{}", DEFAULT_FLATPAK_BUILDER_CACHE_DIR.to_owned(), timestamp.unwrap().as_secs());
         println!("Making a backup of the flatpak-builder cache folder at {}", backup_folder_name);
 
-        let mut output = Command::new("cp")
+        // The backup has to be complete before the state directory is modified.
+        let status = Command::new("cp")
             .arg("-R")
             .arg(DEFAULT_FLATPAK_BUILDER_CACHE_DIR)
-            .arg(backup_folder_name)
-            .spawn();
-
-        let mut output = match output {
-            Ok(o) => o,
+            .arg(&backup_folder_name)
+            .status();
+        match status {
+            Ok(s) if s.success() => {}
+            Ok(s) => return Err(format!("Could not backup the flatpak-builder cache folder at {}: {}.", backup_folder_name, s)),
             Err(e) => return Err(e.to_string()),
         };
     }
 
-    let child = Command::new("flatpak-builder")
+    // The sources are copied from the cache to the state directory of the workspace,
+    // since flatpak-builder also keeps the builds of the workspace in its state directory.
+    let sources = abstract_manifest.get_sources()?;
+    crate::cache::populate_state_dir(&crate::cache::get_cache_dir_path(), &sources, flatpak_cache_dir)?;
+
+    let mut command = Command::new("flatpak-builder");
+    command
         .arg("--user")
         .arg("--force-clean")
         // .arg("-v")
-        .arg("--keep-build-dirs")
+        .arg("--keep-build-dirs");
+    if offline {
+        command.arg("--disable-download");
+    }
+    let child = command
         .arg(DEFAULT_FLATPAK_OUTPUT_DIR)
         .arg(&abstract_manifest.path)
         .stdout(Stdio::piped())
@@ -803,11 +1224,20 @@ pub fn run_build(abstract_manifest: &crate::manifests::manifest::AbstractManifes
         Err(e) => return Err(e.to_string()),
     };
     if !output.status.success() {
-        return Ok("it went ok".to_string());
+        return Err(format!("flatpak-builder failed with {}.", output.status));
     }
     Ok(String::from("lol"))
 }
 
+// Checks the desktop files and the icons installed by the last build of the manifest.
+pub fn check_exports(manifest: &FlatpakManifest) -> Result<ExportReport, String> {
+    let files_dir = path::Path::new(DEFAULT_FLATPAK_OUTPUT_DIR).join("files");
+    if !files_dir.is_dir() {
+        return Err("Looks like this workspace was not built. Run `panbuild make` first.".to_string());
+    }
+    Ok(crate::exports::check_exports(manifest, &files_dir))
+}
+
 pub fn run_command(abstract_manifest: &crate::manifests::manifest::AbstractManifest, command: &str) -> Result<String, String> {
     let flatpak_build_dir = path::Path::new(DEFAULT_FLATPAK_OUTPUT_DIR);
     if !flatpak_build_dir.is_dir() {
@@ -853,6 +1283,168 @@ mod tests {
         assert!(!FlatpakManifest::file_path_matches("/////////////"));
     }
 
+    #[test]
+    pub fn test_add_module_pinned_git_source() {
+        let mut manifest = FlatpakManifest::default();
+        let mut module = SoftwareModule::default();
+        module.name = "glib".to_string();
+        module.url = "https://gitlab.gnome.org/GNOME/glib.git".to_string();
+        module.source_type = crate::modules::SourceType::Git;
+        module.tag = "2.66.0".to_string();
+        module.commit = "b2a0b0b4f0d9d1a8b1a1b1b1b1b1b1b1b1b1b1b1".to_string();
+        module.build_system = BuildSystem::Meson;
+        add_module(&mut manifest, &module).unwrap();
+
+        let flatpak_module = &manifest.modules[0];
+        assert_eq!(flatpak_module.buildsystem, "meson");
+        assert_eq!(flatpak_module.config_opts, vec!["--buildtype=release"]);
+        let source = &flatpak_module.sources[0];
+        assert_eq!(source.r#type, "git");
+        assert_eq!(source.tag.as_ref().unwrap(), "2.66.0");
+        assert!(source.commit.is_some());
+        assert!(source.branch.is_none());
+
+        assert!(add_module(&mut manifest, &module).is_err());
+    }
+
+    #[test]
+    pub fn test_add_module_archive_source() {
+        let mut manifest = FlatpakManifest::default();
+        let mut module = SoftwareModule::default();
+        module.name = "make".to_string();
+        module.url = "https://ftp.gnu.org/gnu/make/make-4.3.tar.gz".to_string();
+        module.source_type = crate::modules::SourceType::Tarball;
+        module.archive_checksum = "e05fdde47c5f7ca45cb697e973894ff4f5d79e13b750ed57d7b66d8defc78e19".to_string();
+        add_module(&mut manifest, &module).unwrap();
+
+        let source = &manifest.modules[0].sources[0];
+        assert_eq!(source.r#type, "archive");
+        assert_eq!(source.sha256.as_ref().unwrap(), &module.archive_checksum);
+        assert!(source.tag.is_none());
+    }
+
+    #[test]
+    pub fn test_add_module_build_commands() {
+        let mut manifest = FlatpakManifest::default();
+        let mut module = SoftwareModule::default();
+        module.name = "ripgrep".to_string();
+        module.url = "https://github.com/BurntSushi/ripgrep.git".to_string();
+        module.build_system = BuildSystem::Cargo;
+        // The dependencies of the module could not be downloaded during the build.
+        assert!(add_module(&mut manifest, &module).is_err());
+
+        module.build_commands = vec!["./build.sh".to_string()];
+        add_module(&mut manifest, &module).unwrap();
+        assert_eq!(manifest.modules[0].buildsystem, "simple");
+        assert_eq!(manifest.modules[0].build_commands, vec!["./build.sh"]);
+
+        module.name = "make".to_string();
+        module.build_commands = vec![];
+        module.build_system = BuildSystem::Make;
+        add_module(&mut manifest, &module).unwrap();
+        assert_eq!(manifest.modules[0].build_commands.len(), 2);
+    }
+
+    #[test]
+    pub fn test_to_module_build_system() {
+        let mut flatpak_module = FlatpakModule::default();
+        flatpak_module.buildsystem = "cmake-ninja".to_string();
+        assert_eq!(flatpak_module.to_module().build_system, BuildSystem::Cmake);
+    }
+
+    #[test]
+    pub fn test_remove_nested_module() {
+        let mut manifest = FlatpakManifest::parse(
+            &r###"
+            app-id: net.louib.panbuild
+            runtime: org.gnome.Platform
+            sdk: org.gnome.Sdk
+            command: panbuild
+            modules:
+              - name: dependencies
+                modules:
+                  - name: libfoo
+                    modules:
+                      - name: libbar
+                  - name: libbaz
+              - name: panbuild
+        "###
+            .to_string(),
+        )
+        .unwrap();
+        assert_eq!(manifest.get_modules().len(), 5);
+        assert_eq!(get_parent_module_name(&manifest, "libfoo").unwrap(), "dependencies");
+
+        let removed_modules = remove_module(&mut manifest, "libfoo").unwrap();
+        assert_eq!(removed_modules, vec!["libfoo", "libbar"]);
+        assert_eq!(manifest.modules[0].modules.len(), 1);
+        assert!(remove_module(&mut manifest, "libfoo").is_err());
+    }
+
+    #[test]
+    pub fn test_fill_modules_checksums() {
+        let mut manifest = FlatpakManifest::parse(
+            &r###"
+            app-id: net.louib.panbuild
+            runtime: org.gnome.Platform
+            sdk: org.gnome.Sdk
+            command: panbuild
+            modules:
+              - name: dependencies
+                modules:
+                  - name: libfoo
+                    sources:
+                      - type: archive
+                        url: https://example.com/libfoo-1.0.tar.gz
+                        sha256: 5cec80f978feb52caf4eda8649f6a84230e7675e302c840b90cdcf4294d4fc26
+              - name: panbuild
+                sources:
+                  - type: archive
+                    url: https://example.com/panbuild-1.0.tar.gz
+        "###
+            .to_string(),
+        )
+        .unwrap();
+        // The sources of the other modules, and the sources with a checksum, are not checked.
+        let report = fill_modules_checksums(&mut manifest, &["libfoo".to_string()]);
+        assert!(report.filled.is_empty());
+        assert!(report.missing.is_empty());
+        assert!(report.mismatches.is_empty());
+        assert!(manifest.modules[1].sources[0].sha256.is_none());
+    }
+
+    #[test]
+    pub fn test_update_module() {
+        let mut manifest = FlatpakManifest::parse(
+            &r###"
+            app-id: net.louib.panbuild
+            runtime: org.gnome.Platform
+            sdk: org.gnome.Sdk
+            command: panbuild
+            modules:
+              - name: glib
+                sources:
+                  - type: git
+                    url: https://gitlab.gnome.org/GNOME/glib.git
+                    tag: 2.62.0
+                    commit: 0123456789abcdef
+                    disable-shallow-clone: true
+        "###
+            .to_string(),
+        )
+        .unwrap();
+        let mut module = manifest.get_modules().remove(0);
+        assert_eq!(module.commit, "0123456789abcdef");
+        module.tag = "2.66.0".to_string();
+        module.commit = "".to_string();
+        update_module(&mut manifest, &module).unwrap();
+
+        let source = &manifest.modules[0].sources[0];
+        assert_eq!(source.tag.as_ref().unwrap(), "2.66.0");
+        assert!(source.commit.is_none());
+        assert!(source.branch.is_none());
+    }
+
     #[test]
     #[should_panic]
     pub fn test_parse_invalid_yaml() {
@@ -903,4 +1495,62 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    pub fn test_check_appstream_ids() {
+        let manifest = FlatpakManifest::parse(
+            &r###"
+            app-id: org.gnome.Clocks
+            runtime: org.gnome.Platform
+            runtime-version: "40"
+            sdk: org.gnome.Sdk
+            command: gnome-clocks
+            rename-desktop-file: gnome-clocks.desktop
+            modules: []
+        "###
+            .to_string(),
+        )
+        .unwrap();
+        let components: Vec<(path::PathBuf, AppStreamComponent)> = [
+            "<component type=\"desktop-application\"><id>org.gnome.Clocks</id></component>",
+            "<application><id>gnome-clocks.desktop</id></application>",
+            "<component type=\"addon\"><id>org.gnome.Clocks.Plugin</id></component>",
+            "<component type=\"desktop-application\"><id>org.gnome.clocks</id></component>",
+        ]
+        .iter()
+        .map(|c| (path::PathBuf::from("clocks.metainfo.xml"), AppStreamComponent::parse(c).unwrap()))
+        .collect();
+        let mismatches = check_appstream_ids(&manifest, &components);
+        assert_eq!(mismatches.len(), 1);
+        assert!(mismatches[0].contains("org.gnome.clocks"));
+    }
+
+    #[test]
+    pub fn test_load_with_includes() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_path = dir.path().join("org.gnome.Clocks.yaml");
+        std::fs::write(
+            &manifest_path,
+            "app-id: org.gnome.Clocks\nruntime: org.gnome.Platform\nsdk: org.gnome.Sdk\nmodules:\n  - modules/libgweather.json\n  - name: gnome-clocks\n",
+        )
+        .unwrap();
+        std::fs::create_dir(dir.path().join("modules")).unwrap();
+        std::fs::write(
+            dir.path().join("modules/libgweather.json"),
+            r#"{"name": "libgweather", "sources": ["libgweather-sources.json", {"type": "patch", "path": "fix.patch"}]}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("modules/libgweather-sources.json"),
+            r#"[{"type": "git", "url": "https://gitlab.gnome.org/GNOME/libgweather.git", "tag": "40.0"}]"#,
+        )
+        .unwrap();
+
+        let manifest = FlatpakManifest::load_with_includes(&manifest_path).unwrap();
+        assert_eq!(manifest.modules.len(), 2);
+        assert_eq!(manifest.modules[0].name, "libgweather");
+        assert_eq!(manifest.modules[0].sources.len(), 2);
+        assert_eq!(manifest.modules[0].sources[0].tag.as_ref().unwrap(), "40.0");
+        assert_eq!(manifest.modules[1].name, "gnome-clocks");
+    }
 }
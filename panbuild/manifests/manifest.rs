@@ -32,16 +32,20 @@ pub enum Architecture {
 }
 pub const DEFAULT_ARCH: Architecture = Architecture::Any;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 /// Software license used for a package.
 /// See https://spdx.org/licenses/ For the complete list of commonly found
 /// free and open source licenses.
 pub enum License {
     Gpl2,
     Gpl3,
+    Lgpl2,
+    Lgpl3,
     Mit,
     Bsd2,
     Bsd3,
+    Apache2,
+    Mpl2,
     Proprietary,
     Unknown,
 }
@@ -52,9 +56,46 @@ impl License {
         }
         return License::Unknown;
     }
+
+    /// Gets a license from its SPDX identifier, or from the license keys
+    /// used by the software hubs, which are lowercase SPDX identifiers.
+    ///```
+    ///use panbuild::manifests::manifest::License;
+    ///assert_eq!(License::from_spdx_id("GPL-3.0-or-later"), License::Gpl3);
+    ///assert_eq!(License::from_spdx_id("mit"), License::Mit);
+    ///assert_eq!(License::from_spdx_id("NOASSERTION"), License::Unknown);
+    ///```
+    pub fn from_spdx_id(spdx_id: &str) -> License {
+        let spdx_id = spdx_id.trim().to_lowercase();
+        let spdx_id = spdx_id.trim_end_matches("-only").trim_end_matches("-or-later").trim_end_matches('+');
+        match spdx_id {
+            "gpl-2.0" | "gpl-2" => License::Gpl2,
+            "gpl-3.0" | "gpl-3" => License::Gpl3,
+            "lgpl-2.0" | "lgpl-2.1" | "lgpl-2" => License::Lgpl2,
+            "lgpl-3.0" | "lgpl-3" => License::Lgpl3,
+            "mit" | "expat" => License::Mit,
+            "bsd-2-clause" => License::Bsd2,
+            "bsd-3-clause" => License::Bsd3,
+            "apache-2.0" => License::Apache2,
+            "mpl-2.0" => License::Mpl2,
+            _ => License::Unknown,
+        }
+    }
 }
 pub const DEFAULT_LICENSE: License = License::Gpl2;
 
+fn get_manifest_format(path: &str) -> ManifestFormat {
+    if path.ends_with(".json") {
+        ManifestFormat::JSON
+    } else if path.ends_with(".yaml") || path.ends_with(".yml") {
+        ManifestFormat::YAML
+    } else if path.ends_with(".toml") {
+        ManifestFormat::TOML
+    } else {
+        ManifestFormat::TEXT
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub enum NativeManifest {
     Flatpak(crate::manifests::flatpak::FlatpakManifest),
@@ -71,6 +112,10 @@ pub struct AbstractManifest {
     pub path: String,
     pub format: ManifestFormat,
     pub native_manifest: Option<NativeManifest>,
+    // The content of the manifest file when it was loaded, used to save the
+    // changes made to the manifest without reformatting the whole file.
+    #[serde(skip)]
+    pub original_content: Option<String>,
 }
 impl AbstractManifest {
     pub fn get_type(&self) -> Option<&str> {
@@ -94,63 +139,36 @@ impl AbstractManifest {
             return None;
         }
 
+        let manifest_content = match fs::read_to_string(file_path) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("Could not read manifest file {}: {}.", path, e);
+                return None;
+            }
+        };
+
         let mut native_manifest: Option<NativeManifest> = None;
         if crate::manifests::flatpak::FlatpakManifest::file_path_matches(&file_path.to_str().unwrap()) {
-            let manifest_content = match fs::read_to_string(file_path) {
-                Ok(content) => content,
-                Err(e) => {
-                    eprintln!("Could not read manifest file {}: {}.", path, e);
-                    return None;
-                }
-            };
             native_manifest = match crate::manifests::flatpak::FlatpakManifest::parse(&manifest_content) {
                 Some(m) => Some(NativeManifest::Flatpak(m)),
                 None => return None,
             };
         } else if crate::manifests::snap::SnapcraftManifest::file_path_matches(&file_path.to_str().unwrap()) {
-            let manifest_content = match fs::read_to_string(file_path) {
-                Ok(content) => content,
-                Err(e) => {
-                    eprintln!("Could not read manifest file {}: {}.", path, e);
-                    return None;
-                }
-            };
             native_manifest = match crate::manifests::snap::SnapcraftManifest::parse(&manifest_content) {
                 Some(m) => Some(NativeManifest::Snapcraft(m)),
                 None => return None,
             };
         } else if crate::manifests::debian::DebianManifest::file_path_matches(&file_path.to_str().unwrap()) {
-            let manifest_content = match fs::read_to_string(file_path) {
-                Ok(content) => content,
-                Err(e) => {
-                    eprintln!("Could not read manifest file {}: {}.", path, e);
-                    return None;
-                }
-            };
             native_manifest = match crate::manifests::debian::DebianManifest::parse(&manifest_content) {
                 Some(m) => Some(NativeManifest::Debian(m)),
                 None => return None,
             };
         } else if crate::manifests::javascript::JavascriptPackageManifest::file_path_matches(&file_path.to_str().unwrap()) {
-            let manifest_content = match fs::read_to_string(file_path) {
-                Ok(content) => content,
-                Err(e) => {
-                    eprintln!("Could not read manifest file {}: {}.", path, e);
-                    return None;
-                }
-            };
             native_manifest = match crate::manifests::javascript::JavascriptPackageManifest::parse(&manifest_content) {
                 Some(m) => Some(NativeManifest::Javascript(m)),
                 None => return None,
             };
         } else if crate::manifests::cargo::CargoManifest::file_path_matches(&file_path.to_str().unwrap()) {
-            let manifest_content = match fs::read_to_string(file_path) {
-                Ok(content) => content,
-                Err(e) => {
-                    eprintln!("Could not read manifest file {}: {}.", path, e);
-                    return None;
-                }
-            };
             native_manifest = match crate::manifests::cargo::CargoManifest::parse(&manifest_content) {
                 Some(m) => Some(NativeManifest::Cargo(m)),
                 None => return None,
@@ -159,25 +177,47 @@ impl AbstractManifest {
             return None;
         }
 
-        let mut manifest_format = ManifestFormat::TEXT;
-        if path.ends_with(".json") {
-            manifest_format = ManifestFormat::JSON;
-        } else if path.ends_with(".yaml") || path.ends_with(".yml") {
-            manifest_format = ManifestFormat::YAML;
-        } else if path.ends_with(".toml") {
-            manifest_format = ManifestFormat::TOML;
-        }
-
         let manifest = AbstractManifest {
+            format: get_manifest_format(&path),
             path: path,
-            format: manifest_format,
             native_manifest: native_manifest,
+            original_content: Some(manifest_content),
         };
         log::info!("Successfully parsed manifest of type `{}`.", manifest.get_type().unwrap_or("unknown"));
         log::debug!("Parsed manifest. Resulting manifest is {:#?}", &manifest);
         Some(manifest)
     }
 
+    // Loads a manifest with the files it includes inlined, for inspecting or comparing it.
+    // Only the Flatpak manifests can include other files.
+    pub fn load_with_includes(path: String) -> Option<AbstractManifest> {
+        if !crate::manifests::flatpak::FlatpakManifest::file_path_matches(&path) {
+            return AbstractManifest::load_from_file(path);
+        }
+        let flatpak_manifest = match crate::manifests::flatpak::FlatpakManifest::load_with_includes(path::Path::new(&path)) {
+            Ok(m) => m,
+            Err(e) => {
+                eprintln!("{}", e);
+                return None;
+            }
+        };
+        Some(AbstractManifest {
+            format: get_manifest_format(&path),
+            path: path,
+            native_manifest: Some(NativeManifest::Flatpak(flatpak_manifest)),
+            original_content: None,
+        })
+    }
+
+    // Compares this manifest with another version of it.
+    pub fn diff(&self, other_manifest: &AbstractManifest) -> Result<crate::diff::ManifestDiff, String> {
+        match (&self.native_manifest, &other_manifest.native_manifest) {
+            (Some(NativeManifest::Flatpak(m)), Some(NativeManifest::Flatpak(other))) => crate::diff::diff_flatpak_manifests(m, other),
+            (Some(_), Some(_)) => Err("Comparing manifests is only supported for Flatpak manifests.".to_string()),
+            _ => Err("No manifest to compare!".to_string()),
+        }
+    }
+
     pub fn dump(&self) -> Result<String, String> {
         match &self.native_manifest {
             Some(n) => match n {
@@ -189,6 +229,68 @@ impl AbstractManifest {
         }
     }
 
+    // Parses a content with the parser of the native manifest, and serializes it back to a YAML value.
+    fn to_yaml_value(&self, manifest_content: &String) -> Result<serde_yaml::Value, String> {
+        let value = match &self.native_manifest {
+            Some(NativeManifest::Flatpak(_)) => match crate::manifests::flatpak::FlatpakManifest::parse(manifest_content) {
+                Some(m) => serde_yaml::to_value(m),
+                None => return Err("Could not parse the Flatpak manifest.".to_string()),
+            },
+            Some(NativeManifest::Snapcraft(_)) => match crate::manifests::snap::SnapcraftManifest::parse(manifest_content) {
+                Some(m) => serde_yaml::to_value(m),
+                None => return Err("Could not parse the Snapcraft manifest.".to_string()),
+            },
+            _ => return Err("Editing is not supported for this manifest format.".to_string()),
+        };
+        match value {
+            Ok(v) => Ok(v),
+            Err(e) => Err(format!("Could not serialize the manifest: {}.", e)),
+        }
+    }
+
+    // Applies the changes made to the manifest to the original content of the manifest file.
+    fn edit(&self) -> Result<String, String> {
+        let original_content = match &self.original_content {
+            Some(c) => c,
+            None => return Err("The original content of the manifest is not available.".to_string()),
+        };
+        let original_value = self.to_yaml_value(original_content)?;
+        let updated_value = match self.dump() {
+            Ok(d) => self.to_yaml_value(&d)?,
+            Err(e) => return Err(e),
+        };
+        if original_value == updated_value {
+            return Ok(original_content.to_string());
+        }
+        let updated_content = crate::manifests::editor::apply_changes(original_content, &self.format, &original_value, &updated_value)?;
+        // Making sure that the edited manifest is the same as the dumped manifest.
+        if self.to_yaml_value(&updated_content)? != updated_value {
+            return Err("The edited manifest does not match the updated manifest.".to_string());
+        }
+        Ok(updated_content)
+    }
+
+    // Saves the manifest to its file, and returns whether the file was modified. Unless the manifest
+    // is reformatted, only the parts of the file that changed are rewritten, keeping the comments
+    // and the formatting.
+    pub fn save(&self, reformat: bool) -> Result<bool, String> {
+        let manifest_content = match reformat {
+            true => self.dump()?,
+            false => match self.edit() {
+                Ok(c) => c,
+                Err(e) => return Err(format!("Could not edit the manifest: {} Use --reformat to rewrite the whole manifest.", e)),
+            },
+        };
+        if self.original_content.as_ref() == Some(&manifest_content) {
+            log::info!("The manifest {} was not modified.", self.path);
+            return Ok(false);
+        }
+        match fs::write(path::Path::new(&self.path), manifest_content) {
+            Ok(_) => Ok(true),
+            Err(e) => Err(format!("Could not write file {}: {}.", self.path, e)),
+        }
+    }
+
     pub fn get_modules(&self) -> Result<Vec<SoftwareModule>, String> {
         match &self.native_manifest {
             Some(n) => match n {
@@ -199,6 +301,26 @@ impl AbstractManifest {
         }
     }
 
+    pub fn get_runtime_libraries(&self) -> Result<Vec<crate::runtimes::RuntimeLibrary>, String> {
+        match &self.native_manifest {
+            Some(n) => match n {
+                NativeManifest::Flatpak(m) => Ok(m.get_runtime_libraries()),
+                _ => Err("Getting the runtime libraries is not supported for this manifest format.".to_string()),
+            },
+            None => Err("No manifest to get the runtime libraries from!".to_string()),
+        }
+    }
+
+    pub fn get_provided_names(&self) -> Result<Vec<String>, String> {
+        match &self.native_manifest {
+            Some(n) => match n {
+                NativeManifest::Flatpak(m) => Ok(m.get_provided_names()),
+                _ => Err("Getting the provided names is not supported for this manifest format.".to_string()),
+            },
+            None => Err("No manifest to get the provided names from!".to_string()),
+        }
+    }
+
     pub fn add_module(&mut self, module: &SoftwareModule) -> Result<Vec<SoftwareModule>, String> {
         match &mut self.native_manifest {
             Some(n) => match n {
@@ -209,10 +331,80 @@ impl AbstractManifest {
         }
     }
 
-    pub fn run_build(&self) -> Result<String, String> {
+    pub fn remove_module(&mut self, module_name: &str) -> Result<Vec<String>, String> {
+        match &mut self.native_manifest {
+            Some(n) => match n {
+                NativeManifest::Flatpak(m) => crate::manifests::flatpak::remove_module(m, module_name),
+                _ => Err("Removing a module is not supported for this manifest format.".to_string()),
+            },
+            None => Err("No manifest to remove the module from!".to_string()),
+        }
+    }
+
+    pub fn update_module(&mut self, module: &SoftwareModule) -> Result<(), String> {
+        match &mut self.native_manifest {
+            Some(n) => match n {
+                NativeManifest::Flatpak(m) => crate::manifests::flatpak::update_module(m, module),
+                _ => Err("Updating a module is not supported for this manifest format.".to_string()),
+            },
+            None => Err("No manifest to update the module in!".to_string()),
+        }
+    }
+
+    // Gets the name of the module that contains the module, for the
+    // manifest formats that support nested modules.
+    pub fn get_parent_module_name(&self, module_name: &str) -> Option<String> {
+        match &self.native_manifest {
+            Some(NativeManifest::Flatpak(m)) => crate::manifests::flatpak::get_parent_module_name(m, module_name),
+            _ => None,
+        }
+    }
+
+    pub fn check_checksums(&mut self, fix: bool) -> Result<crate::checksums::ChecksumReport, String> {
+        match &mut self.native_manifest {
+            Some(n) => match n {
+                NativeManifest::Flatpak(m) => Ok(crate::manifests::flatpak::check_checksums(m, fix)),
+                _ => Err("Checking the checksums is not supported for this manifest format.".to_string()),
+            },
+            None => Err("No manifest to check the checksums of!".to_string()),
+        }
+    }
+
+    pub fn fill_modules_checksums(&mut self, module_names: &[String]) -> Result<crate::checksums::ChecksumReport, String> {
+        match &mut self.native_manifest {
+            Some(n) => match n {
+                NativeManifest::Flatpak(m) => Ok(crate::manifests::flatpak::fill_modules_checksums(m, module_names)),
+                _ => Err("Filling the checksums is not supported for this manifest format.".to_string()),
+            },
+            None => Err("No manifest to fill the checksums of!".to_string()),
+        }
+    }
+
+    pub fn check_exports(&self) -> Result<crate::exports::ExportReport, String> {
+        match &self.native_manifest {
+            Some(n) => match n {
+                NativeManifest::Flatpak(m) => crate::manifests::flatpak::check_exports(m),
+                _ => Err("Checking the exported files is not supported for this manifest format.".to_string()),
+            },
+            None => Err("No manifest to check the exported files of!".to_string()),
+        }
+    }
+
+    pub fn get_sources(&self) -> Result<Vec<crate::modules::AbstractSource>, String> {
+        match &self.native_manifest {
+            Some(n) => match n {
+                NativeManifest::Flatpak(m) => Ok(crate::manifests::flatpak::get_all_sources(m)),
+                _ => Err("Getting the sources is not supported for this manifest format.".to_string()),
+            },
+            None => Err("No manifest to get the sources from!".to_string()),
+        }
+    }
+
+    // When offline, the build only uses the sources from the cache.
+    pub fn run_build(&self, offline: bool) -> Result<String, String> {
         let output = match &self.native_manifest {
             Some(n) => match n {
-                NativeManifest::Flatpak(m) => crate::manifests::flatpak::run_build(self),
+                NativeManifest::Flatpak(m) => crate::manifests::flatpak::run_build(self, offline),
                 _ => return Err("Running a build is not supported for this manifest format.".to_string()),
             },
             None => return Err("No manifest to run the build with!".to_string()),
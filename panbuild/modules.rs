@@ -15,7 +15,7 @@ pub enum OS {
 // when the generated manifest will be used? We could
 // consider optionally downloading those dependencies
 // to ensure the version of the build system...
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum BuildSystem {
     Make,
     Cmake,
@@ -71,6 +71,67 @@ impl BuildSystem {
         }
         return DEFAULT_BUILD_SYSTEM;
     }
+
+    // Gets the build system from a name, as found in the build_systems
+    // of the projects.
+    pub fn from_name(name: &str) -> BuildSystem {
+        match name.to_lowercase().as_str() {
+            "make" => BuildSystem::Make,
+            "cmake" | "cmake-ninja" => BuildSystem::Cmake,
+            "qmake" => BuildSystem::Qmake,
+            "autotools" => BuildSystem::Autotools,
+            "meson" => BuildSystem::Meson,
+            "cargo" => BuildSystem::Cargo,
+            "maven" => BuildSystem::Maven,
+            "npm" | "javascript" => BuildSystem::Npm,
+            "pip3" | "pyproject" => BuildSystem::Pip3,
+            _ => BuildSystem::Unknown,
+        }
+    }
+
+    // Gets the name of the build system, as used in the build_systems of the projects.
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            BuildSystem::Make => "make",
+            BuildSystem::Cmake => "cmake",
+            BuildSystem::Qmake => "qmake",
+            BuildSystem::Autotools => "autotools",
+            BuildSystem::Meson => "meson",
+            BuildSystem::Cargo => "cargo",
+            BuildSystem::Maven => "maven",
+            BuildSystem::Xcode => "xcode",
+            BuildSystem::Npm => "npm",
+            BuildSystem::Bash => "bash",
+            BuildSystem::Pip2 => "pip2",
+            BuildSystem::Pip3 => "pip3",
+            BuildSystem::Manual => "manual",
+            BuildSystem::Swift => "swift",
+            BuildSystem::Apt => "apt",
+            BuildSystem::Gem => "gem",
+            BuildSystem::Unknown => "unknown",
+        }
+    }
+
+    // Gets the build system most likely used by a project written in a programming language,
+    // as reported by the software hubs. Languages like C or C++ are used with too many build
+    // systems to guess one.
+    pub fn from_language(language: &str) -> Option<BuildSystem> {
+        match language.to_lowercase().as_str() {
+            "rust" => Some(BuildSystem::Cargo),
+            "javascript" | "typescript" => Some(BuildSystem::Npm),
+            "python" => Some(BuildSystem::Pip3),
+            "java" | "kotlin" => Some(BuildSystem::Maven),
+            "swift" => Some(BuildSystem::Swift),
+            "ruby" => Some(BuildSystem::Gem),
+            "objective-c" => Some(BuildSystem::Xcode),
+            "qml" => Some(BuildSystem::Qmake),
+            "meson" => Some(BuildSystem::Meson),
+            "cmake" => Some(BuildSystem::Cmake),
+            "makefile" => Some(BuildSystem::Make),
+            "m4" => Some(BuildSystem::Autotools),
+            _ => None,
+        }
+    }
 }
 
 pub const DEFAULT_BUILD_SYSTEM: BuildSystem = BuildSystem::Unknown;
@@ -81,7 +142,7 @@ impl Default for BuildSystem {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum SourceType {
     Bzr,
     Deb,
@@ -96,6 +157,8 @@ pub enum SourceType {
     Zip,
     // 7z
     Sevenzip,
+    // A single file, used as is.
+    File,
     Unknown,
 }
 
@@ -106,6 +169,14 @@ impl Default for SourceType {
         DEFAULT_SOURCE_TYPE
     }
 }
+impl SourceType {
+    pub fn is_archive(&self) -> bool {
+        match self {
+            SourceType::Tar | SourceType::Tarball | SourceType::Zip | SourceType::Sevenzip => true,
+            _ => false,
+        }
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum ModuleType {
@@ -148,6 +219,8 @@ pub struct SoftwareModule {
 
     pub download_urls: Vec<String>,
     pub url: String,
+    // The type of the source located at the url.
+    pub source_type: SourceType,
     pub build_system: BuildSystem,
     pub archive_checksum: String,
     pub source_checksum: String,
@@ -176,6 +249,15 @@ impl SoftwareModule {
     }
 }
 
+// A source that has to be fetched before building a module.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct AbstractSource {
+    pub url: String,
+    pub source_type: SourceType,
+    pub sha256: Option<String>,
+    pub commit: Option<String>,
+}
+
 #[derive(Default, Debug, Serialize, Deserialize)]
 pub struct AbstractExecutable {
     pub name: String,
@@ -0,0 +1,191 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::modules::SoftwareModule;
+use crate::projects::{ProjectVersion, SoftwareProject};
+use crate::version::{SemanticVersion, Version, VersionRange};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionBump {
+    Major,
+    Minor,
+    Patch,
+}
+impl VersionBump {
+    pub fn from_versions(current_version: &Version, latest_version: &Version) -> Option<VersionBump> {
+        if latest_version <= current_version {
+            return None;
+        }
+        let (current_version, latest_version) = match (current_version.to_semantic_version(), latest_version.to_semantic_version()) {
+            (Some(c), Some(l)) => (c, l),
+            // The versions are different, but we can't tell by how much.
+            _ => return Some(VersionBump::Patch),
+        };
+        if latest_version.major != current_version.major {
+            return Some(VersionBump::Major);
+        }
+        if latest_version.minor != current_version.minor {
+            return Some(VersionBump::Minor);
+        }
+        Some(VersionBump::Patch)
+    }
+}
+impl fmt::Display for VersionBump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VersionBump::Major => write!(f, "major"),
+            VersionBump::Minor => write!(f, "minor"),
+            VersionBump::Patch => write!(f, "patch"),
+        }
+    }
+}
+
+// The version status of a module of a manifest.
+#[derive(Debug, Serialize)]
+pub struct ModuleStatus {
+    pub module_name: String,
+    pub project_id: Option<String>,
+    // The version the module is pinned to, if it could be determined.
+    pub current_version: Option<String>,
+    // The newest stable version known for the project.
+    pub latest_version: Option<String>,
+    // Set only if the module is outdated.
+    pub bump: Option<VersionBump>,
+}
+
+// Gets the version status of a module. The versions are looked up in the database first,
+// then in the project and finally in the tags of the git repository of the module.
+pub fn get_module_status(
+    projects: &Vec<SoftwareProject>,
+    get_versions: &dyn Fn(&str) -> Vec<ProjectVersion>,
+    get_remote_tags: &dyn Fn(&str) -> Vec<(String, String)>,
+    module: &SoftwareModule,
+) -> ModuleStatus {
+    let mut module_status = ModuleStatus {
+        module_name: module.name.to_string(),
+        project_id: None,
+        current_version: None,
+        latest_version: None,
+        bump: None,
+    };
+
+    let mut versions: Vec<ProjectVersion> = vec![];
+    if let Some(project) = crate::projects::find_module_project(projects, module) {
+        module_status.project_id = Some(project.id.to_string());
+        versions = get_versions(&project.id);
+        for version_name in &project.versions {
+            if !versions.iter().any(|v| &v.name == version_name) {
+                versions.push(ProjectVersion {
+                    project_id: project.id.to_string(),
+                    name: version_name.to_string(),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+    if versions.is_empty() && !module.url.is_empty() && !module.source_type.is_archive() {
+        for (tag_name, commit) in get_remote_tags(&module.url) {
+            let version = match SemanticVersion::from_tag_name(&tag_name) {
+                Some(v) => v,
+                None => continue,
+            };
+            versions.push(ProjectVersion {
+                name: version.to_string(),
+                tag: tag_name,
+                commit,
+                ..Default::default()
+            });
+        }
+    }
+
+    let current_version = crate::resolver::get_module_current_version(&versions, module);
+    let latest_version = crate::projects::get_best_matching_version(&versions, &VersionRange::default()).and_then(|v| Version::parse(&v.name));
+    if let (Some(current_version), Some(latest_version)) = (&current_version, &latest_version) {
+        module_status.bump = VersionBump::from_versions(current_version, latest_version);
+    }
+    module_status.current_version = current_version.map(|v| v.to_string());
+    module_status.latest_version = latest_version.map(|v| v.to_string());
+    module_status
+}
+
+pub fn get_modules_status(modules: &[SoftwareModule]) -> Vec<ModuleStatus> {
+    let projects = crate::db::Database::get_all_projects();
+    let get_remote_tags = |repo_url: &str| match crate::utils::get_remote_git_repo_tags(repo_url) {
+        Ok(t) => t,
+        Err(e) => {
+            log::warn!("Could not get the tags of {}: {}", repo_url, e);
+            vec![]
+        }
+    };
+    modules
+        .iter()
+        .map(|m| get_module_status(&projects, &crate::db::Database::get_project_versions, &get_remote_tags, m))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_module(name: &str, tag: &str) -> SoftwareModule {
+        SoftwareModule {
+            name: name.to_string(),
+            url: format!("https://{}.git", name),
+            tag: tag.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    pub fn test_version_bump() {
+        let bump = |current: &str, latest: &str| VersionBump::from_versions(&Version::parse(current).unwrap(), &Version::parse(latest).unwrap());
+        assert_eq!(bump("1.2.3", "2.0.0"), Some(VersionBump::Major));
+        assert_eq!(bump("1.2.3", "1.3.0"), Some(VersionBump::Minor));
+        assert_eq!(bump("1.2.3", "1.2.4"), Some(VersionBump::Patch));
+        assert_eq!(bump("2021.03", "2021.10"), Some(VersionBump::Minor));
+        assert_eq!(bump("1.2.3", "1.2.3"), None);
+        assert_eq!(bump("1.3.0", "1.2.3"), None);
+    }
+
+    #[test]
+    pub fn test_module_status_from_project() {
+        let projects = vec![SoftwareProject {
+            id: "org.gnome.glib".to_string(),
+            vcs_urls: vec!["https://glib.git".to_string()],
+            versions: vec!["2.62.0".to_string(), "2.66.1".to_string(), "2.67.0-rc1".to_string()],
+            ..Default::default()
+        }];
+        let get_versions = |_: &str| vec![];
+        let get_remote_tags = |_: &str| panic!("The remote tags should not be fetched.");
+
+        let status = get_module_status(&projects, &get_versions, &get_remote_tags, &get_module("glib", "2.62.0"));
+        assert_eq!(status.project_id.unwrap(), "org.gnome.glib");
+        assert_eq!(status.current_version.unwrap(), "2.62.0");
+        assert_eq!(status.latest_version.unwrap(), "2.66.1");
+        assert_eq!(status.bump, Some(VersionBump::Minor));
+    }
+
+    #[test]
+    pub fn test_module_status_from_remote_tags() {
+        let get_versions = |_: &str| vec![];
+        let get_remote_tags = |_: &str| {
+            vec![
+                ("v1.0.0".to_string(), "abc".to_string()),
+                ("v1.0.1".to_string(), "def".to_string()),
+                ("nightly".to_string(), "ghi".to_string()),
+            ]
+        };
+
+        let status = get_module_status(&vec![], &get_versions, &get_remote_tags, &get_module("libfoo", "v1.0.0"));
+        assert_eq!(status.current_version.unwrap(), "1.0.0");
+        assert_eq!(status.latest_version.unwrap(), "1.0.1");
+        assert_eq!(status.bump, Some(VersionBump::Patch));
+
+        // Modules following a branch have no current version.
+        let status = get_module_status(&vec![], &get_versions, &get_remote_tags, &get_module("libfoo", ""));
+        assert!(status.current_version.is_none());
+        assert!(status.bump.is_none());
+    }
+}
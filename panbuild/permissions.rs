@@ -0,0 +1,337 @@
+use serde::{Deserialize, Serialize};
+
+// The access mode of a filesystem permission, set with a suffix on the path.
+// See https://docs.flatpak.org/en/latest/sandbox-permissions.html
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum FilesystemMode {
+    ReadWrite,
+    ReadOnly,
+    // Read-write access, creating the directory if it does not exist.
+    Create,
+}
+impl FilesystemMode {
+    pub fn parse(suffix: &str) -> Option<FilesystemMode> {
+        match suffix {
+            "rw" => Some(FilesystemMode::ReadWrite),
+            "ro" => Some(FilesystemMode::ReadOnly),
+            "create" => Some(FilesystemMode::Create),
+            _ => None,
+        }
+    }
+
+    pub fn get_description(&self) -> &str {
+        match self {
+            FilesystemMode::ReadWrite | FilesystemMode::Create => "read-write",
+            FilesystemMode::ReadOnly => "read-only",
+        }
+    }
+}
+
+// A permission granted to the sandbox of an application by one of its finish-args.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Permission {
+    Share(String),
+    Filesystem(String, FilesystemMode),
+    Socket(String),
+    Device(String),
+    Allow(String),
+    TalkName(String),
+    OwnName(String),
+    SystemTalkName(String),
+    SystemOwnName(String),
+    Env(String, String),
+    // A directory of the home of the user, persisted in the data directory of the application.
+    Persist(String),
+    // The permissions revoked, usually to remove a permission granted by a previous
+    // finish-arg or by the runtime.
+    Unshare(String),
+    NoSocket(String),
+    NoDevice(String),
+    NoFilesystem(String),
+    // The other finish-args, like --unset-env, kept as is.
+    Other(String),
+}
+impl Permission {
+    pub fn parse(finish_arg: &str) -> Result<Permission, String> {
+        let (arg_name, arg_value) = match finish_arg.trim().trim_start_matches("--").split_once('=') {
+            Some((n, v)) => (n, v.to_string()),
+            None => return Ok(Permission::Other(finish_arg.trim().to_string())),
+        };
+        if arg_value.is_empty() {
+            return Err(format!("Missing value for finish-arg {}.", finish_arg));
+        }
+        let permission = match arg_name {
+            "share" => Permission::Share(arg_value),
+            "socket" => Permission::Socket(arg_value),
+            "device" => Permission::Device(arg_value),
+            "allow" => Permission::Allow(arg_value),
+            "filesystem" => {
+                let (path, mode) = match arg_value.rsplit_once(':').map(|(p, m)| (p, FilesystemMode::parse(m))) {
+                    Some((path, Some(mode))) => (path.to_string(), mode),
+                    _ => (arg_value, FilesystemMode::ReadWrite),
+                };
+                Permission::Filesystem(path, mode)
+            }
+            "talk-name" => Permission::TalkName(arg_value),
+            "own-name" => Permission::OwnName(arg_value),
+            "system-talk-name" => Permission::SystemTalkName(arg_value),
+            "system-own-name" => Permission::SystemOwnName(arg_value),
+            "env" => match arg_value.split_once('=') {
+                Some((name, value)) => Permission::Env(name.to_string(), value.to_string()),
+                None => return Err(format!("Invalid environment variable in finish-arg {}.", finish_arg)),
+            },
+            "persist" => Permission::Persist(arg_value),
+            "unshare" => Permission::Unshare(arg_value),
+            "nosocket" => Permission::NoSocket(arg_value),
+            "nodevice" => Permission::NoDevice(arg_value),
+            "nofilesystem" => Permission::NoFilesystem(arg_value),
+            _ => Permission::Other(finish_arg.trim().to_string()),
+        };
+        Ok(permission)
+    }
+
+    pub fn to_finish_arg(&self) -> String {
+        match self {
+            Permission::Share(v) => format!("--share={}", v),
+            Permission::Socket(v) => format!("--socket={}", v),
+            Permission::Device(v) => format!("--device={}", v),
+            Permission::Allow(v) => format!("--allow={}", v),
+            Permission::Filesystem(path, mode) => match mode {
+                FilesystemMode::ReadWrite => format!("--filesystem={}", path),
+                FilesystemMode::ReadOnly => format!("--filesystem={}:ro", path),
+                FilesystemMode::Create => format!("--filesystem={}:create", path),
+            },
+            Permission::TalkName(v) => format!("--talk-name={}", v),
+            Permission::OwnName(v) => format!("--own-name={}", v),
+            Permission::SystemTalkName(v) => format!("--system-talk-name={}", v),
+            Permission::SystemOwnName(v) => format!("--system-own-name={}", v),
+            Permission::Env(name, value) => format!("--env={}={}", name, value),
+            Permission::Persist(v) => format!("--persist={}", v),
+            Permission::Unshare(v) => format!("--unshare={}", v),
+            Permission::NoSocket(v) => format!("--nosocket={}", v),
+            Permission::NoDevice(v) => format!("--nodevice={}", v),
+            Permission::NoFilesystem(v) => format!("--nofilesystem={}", v),
+            Permission::Other(v) => v.to_string(),
+        }
+    }
+
+    // Gets the key of the permissions that override each other. Only the last
+    // filesystem permission for a path, and the last value of a variable, are used by flatpak.
+    // A permission and its revocation also override each other.
+    fn get_override_key(&self) -> Option<String> {
+        match self {
+            Permission::Share(v) | Permission::Unshare(v) => Some(format!("share={}", v)),
+            Permission::Socket(v) | Permission::NoSocket(v) => Some(format!("socket={}", v)),
+            Permission::Device(v) | Permission::NoDevice(v) => Some(format!("device={}", v)),
+            Permission::Filesystem(path, _) | Permission::NoFilesystem(path) => Some(format!("filesystem={}", path)),
+            Permission::Env(name, _) => Some(format!("env={}", name)),
+            _ => None,
+        }
+    }
+
+    // Describes the permission for a human reviewer. The finish-args that only
+    // configure the sandbox, like the environment variables, are not described.
+    pub fn get_description(&self) -> Option<String> {
+        let description = match self {
+            Permission::Share(v) => v.to_string(),
+            Permission::Socket(v) => match v.as_str() {
+                "x11" | "fallback-x11" => "X11".to_string(),
+                "wayland" => "Wayland".to_string(),
+                "pulseaudio" => "audio".to_string(),
+                "session-bus" => "full session bus".to_string(),
+                "system-bus" => "full system bus".to_string(),
+                "ssh-auth" => "SSH agent".to_string(),
+                "pcsc" => "smart cards".to_string(),
+                "cups" => "printing".to_string(),
+                _ => format!("{} socket", v),
+            },
+            Permission::Device(v) => match v.as_str() {
+                "dri" => "GPU acceleration".to_string(),
+                "all" => "all devices".to_string(),
+                _ => format!("{} devices", v),
+            },
+            Permission::Allow(v) => v.to_string(),
+            Permission::Filesystem(path, mode) => format!("{} {}", path, mode.get_description()),
+            Permission::TalkName(v) => format!("talks to {}", v),
+            Permission::OwnName(v) => format!("owns {}", v),
+            Permission::SystemTalkName(v) => format!("talks to system {}", v),
+            Permission::SystemOwnName(v) => format!("owns system {}", v),
+            // The revoked permissions are not granted to the application.
+            Permission::Unshare(_) | Permission::NoSocket(_) | Permission::NoDevice(_) | Permission::NoFilesystem(_) => return None,
+            Permission::Env(_, _) | Permission::Persist(_) | Permission::Other(_) => return None,
+        };
+        Some(description)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PermissionSet {
+    pub permissions: Vec<Permission>,
+}
+impl PermissionSet {
+    pub fn parse(finish_args: &[String]) -> Result<PermissionSet, String> {
+        let mut permission_set = PermissionSet::default();
+        for finish_arg in finish_args {
+            permission_set.permissions.push(Permission::parse(finish_arg)?);
+        }
+        Ok(permission_set)
+    }
+
+    pub fn to_finish_args(&self) -> Vec<String> {
+        self.permissions.iter().map(|p| p.to_finish_arg()).collect()
+    }
+
+    // Removes the duplicated and overridden permissions, and sorts the remaining ones.
+    // The permissions revoked by a later finish-arg are removed, but the revocations are
+    // kept, since they can also revoke the permissions granted by the runtime.
+    pub fn normalize(&mut self) {
+        let mut permissions: Vec<Permission> = vec![];
+        for permission in self.permissions.drain(..).rev() {
+            if permissions.contains(&permission) {
+                continue;
+            }
+            if let Some(key) = permission.get_override_key() {
+                if permissions.iter().any(|p| p.get_override_key().as_ref() == Some(&key)) {
+                    continue;
+                }
+            }
+            permissions.push(permission);
+        }
+        permissions.sort();
+        self.permissions = permissions;
+    }
+
+    pub fn get_normalized(&self) -> PermissionSet {
+        let mut permission_set = self.clone();
+        permission_set.normalize();
+        permission_set
+    }
+
+    // Summarizes the permissions for a human reviewer, for example "has network, home read-write, X11".
+    pub fn get_summary(&self) -> String {
+        let descriptions: Vec<String> = self.get_normalized().permissions.iter().filter_map(|p| p.get_description()).collect();
+        match descriptions.is_empty() {
+            true => "has no permissions".to_string(),
+            false => format!("has {}", descriptions.join(", ")),
+        }
+    }
+
+    // Gets the permissions added and removed by another set of permissions.
+    pub fn diff(&self, other: &PermissionSet) -> PermissionDiff {
+        let before = self.get_normalized();
+        let after = other.get_normalized();
+        PermissionDiff {
+            added: after.permissions.iter().filter(|p| !before.permissions.contains(p)).cloned().collect(),
+            removed: before.permissions.iter().filter(|p| !after.permissions.contains(p)).cloned().collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PermissionDiff {
+    pub added: Vec<Permission>,
+    pub removed: Vec<Permission>,
+}
+impl PermissionDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_finish_args(finish_args: &[&str]) -> Vec<String> {
+        finish_args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    pub fn test_parse() {
+        assert_eq!(Permission::parse("--share=network").unwrap(), Permission::Share("network".to_string()));
+        assert_eq!(
+            Permission::parse("--filesystem=xdg-run/pipewire-0:ro").unwrap(),
+            Permission::Filesystem("xdg-run/pipewire-0".to_string(), FilesystemMode::ReadOnly)
+        );
+        assert_eq!(
+            Permission::parse("--filesystem=~/.config/app:create").unwrap(),
+            Permission::Filesystem("~/.config/app".to_string(), FilesystemMode::Create)
+        );
+        assert_eq!(
+            Permission::parse("--env=GTK_THEME=Adwaita:dark").unwrap(),
+            Permission::Env("GTK_THEME".to_string(), "Adwaita:dark".to_string())
+        );
+        assert_eq!(Permission::parse("--nosocket=x11").unwrap(), Permission::NoSocket("x11".to_string()));
+        assert_eq!(Permission::parse("--unset-env=FOO").unwrap(), Permission::Other("--unset-env=FOO".to_string()));
+        assert!(Permission::parse("--socket=").is_err());
+        assert!(Permission::parse("--env=GTK_THEME").is_err());
+    }
+
+    #[test]
+    pub fn test_to_finish_args() {
+        let finish_args = to_finish_args(&[
+            "--share=ipc",
+            "--filesystem=home:ro",
+            "--filesystem=xdg-download",
+            "--env=FOO=bar",
+            "--persist=.mozilla",
+            "--nofilesystem=host",
+        ]);
+        assert_eq!(PermissionSet::parse(&finish_args).unwrap().to_finish_args(), finish_args);
+    }
+
+    #[test]
+    pub fn test_normalize() {
+        let mut permission_set = PermissionSet::parse(&to_finish_args(&[
+            "--socket=x11",
+            "--share=network",
+            "--filesystem=home:ro",
+            "--socket=x11",
+            "--filesystem=home",
+            "--env=FOO=1",
+            "--env=FOO=2",
+            "--device=dri",
+            "--nodevice=dri",
+            "--nosocket=wayland",
+            "--socket=wayland",
+        ]))
+        .unwrap();
+        permission_set.normalize();
+        assert_eq!(
+            permission_set.to_finish_args(),
+            vec![
+                "--share=network",
+                "--filesystem=home",
+                "--socket=wayland",
+                "--socket=x11",
+                "--env=FOO=2",
+                "--nodevice=dri"
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_get_summary() {
+        let permission_set = PermissionSet::parse(&to_finish_args(&["--socket=x11", "--share=network", "--filesystem=home", "--env=FOO=1"])).unwrap();
+        assert_eq!(permission_set.get_summary(), "has network, home read-write, X11");
+        assert_eq!(PermissionSet::default().get_summary(), "has no permissions");
+        let permission_set = PermissionSet::parse(&to_finish_args(&["--share=network", "--unshare=network"])).unwrap();
+        assert_eq!(permission_set.get_summary(), "has no permissions");
+    }
+
+    #[test]
+    pub fn test_diff() {
+        let before = PermissionSet::parse(&to_finish_args(&["--share=ipc", "--socket=x11", "--filesystem=home:ro"])).unwrap();
+        let after = PermissionSet::parse(&to_finish_args(&["--socket=x11", "--share=ipc", "--filesystem=home", "--device=all"])).unwrap();
+        let diff = before.diff(&after);
+        assert_eq!(
+            diff.added,
+            vec![
+                Permission::Filesystem("home".to_string(), FilesystemMode::ReadWrite),
+                Permission::Device("all".to_string())
+            ]
+        );
+        assert_eq!(diff.removed, vec![Permission::Filesystem("home".to_string(), FilesystemMode::ReadOnly)]);
+        assert!(before.diff(&before).is_empty());
+    }
+}
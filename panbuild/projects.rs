@@ -3,6 +3,8 @@ use std::path::Path;
 
 use std::process::Command;
 
+use crate::version::{Version, VersionRange};
+
 pub const CORE_PROJECTS: [&'static str; 20] = [
     "https://git.savannah.gnu.org/cgit/bash.git",
     "https://git.savannah.gnu.org/cgit/make.git",
@@ -26,7 +28,8 @@ pub const CORE_PROJECTS: [&'static str; 20] = [
     "https://gitlab.com/gnutls/gnutls.git",
 ];
 
-#[derive(Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
 pub struct SoftwareProject {
     // Project ids are based on the reverse DNS notation, and
     // are either derived from build manifests found in the project
@@ -58,11 +61,33 @@ pub struct SoftwareProject {
     // for project de-duplication, in the case a project has multiple remote
     // git repositories.
     pub root_hashes: Vec<String>,
+
+    // Other ids that this project was known under before being de-duplicated.
+    // Those are normally ids derived from the urls of mirrors or of other hubs.
+    pub aliases: Vec<String>,
+
+    // The license declared on the hub hosting the project, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<crate::manifests::manifest::License>,
+    pub is_archived: bool,
+    pub is_fork: bool,
+    // Whether the repository is a mirror of a repository hosted elsewhere.
+    pub is_mirror: bool,
+    // The programming languages of the project, from the most used to the least used.
+    // TODO should be a HashSet instead
+    pub languages: Vec<String>,
+
+    // The Flatpak application of the project, for the applications published on Flathub.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flatpak_app: Option<FlatpakApp>,
 }
 impl SoftwareProject {
-    pub fn harvest(repo_url: &str) -> SoftwareProject {
+    // Harvests a project from its git repository. The versions of the project
+    // found in the repository are also returned, so that the repository does
+    // not have to be inspected again to store them.
+    pub fn harvest(repo_url: &str) -> Result<(SoftwareProject, Vec<ProjectVersion>), String> {
         let mut project = SoftwareProject::default();
-        let repo_path = crate::utils::clone_git_repo(repo_url).unwrap();
+        let repo_path = crate::utils::clone_git_repo(repo_url)?;
         project.id = crate::utils::repo_url_to_reverse_dns(repo_url);
         for file_path in crate::utils::get_all_paths(Path::new(&repo_path)).unwrap() {
             let mut abstract_manifest = match crate::manifests::manifest::AbstractManifest::load_from_file(file_path.to_str().unwrap().to_string()) {
@@ -73,23 +98,130 @@ impl SoftwareProject {
 
             // TODO harvest executable names
         }
+        for (_, component) in crate::appstream::find_components(Path::new(&repo_path)) {
+            project.merge(&component.to_software_project());
+        }
         match crate::utils::get_git_repo_root_hashes(&repo_path) {
             Ok(root_hashes) => project.root_hashes = root_hashes,
             Err(e) => {
                 log::warn!("Could not get root commit hashes for repo located at {}: {}.", &repo_path, e);
             }
         }
-        project
+        let project_versions = get_project_versions(&project.id, repo_url, &repo_path);
+        for project_version in &project_versions {
+            if !project.versions.contains(&project_version.name) {
+                project.versions.push(project_version.name.to_string());
+            }
+        }
+        crate::version::sort_versions(&mut project.versions);
+        project.vcs_urls.push(repo_url.to_string());
+        Ok((project, project_versions))
     }
 
     pub fn merge(&mut self, other_project: &SoftwareProject) {
-        for build_system in &other_project.build_systems {
-            self.build_systems.push(build_system.clone());
+        if self.name.is_empty() {
+            self.name = other_project.name.to_string();
+        }
+        if self.summary.is_empty() {
+            self.summary = other_project.summary.to_string();
+        }
+        if self.description.is_empty() {
+            self.description = other_project.description.to_string();
+        }
+        if self.default_branch.is_none() {
+            self.default_branch = other_project.default_branch.clone();
+        }
+        if self.license.is_none() || self.license == Some(crate::manifests::manifest::License::Unknown) {
+            if other_project.license.is_some() {
+                self.license = other_project.license.clone();
+            }
+        }
+        if self.flatpak_app.is_none() {
+            self.flatpak_app = other_project.flatpak_app.clone();
+        }
+        // A project archived on any of its hubs is not maintained anymore. The fork status
+        // is not merged, since forks are never de-duplicated with their parent project.
+        self.is_archived = self.is_archived || other_project.is_archived;
+        // The project is only a mirror if all its repositories are mirrors.
+        self.is_mirror = self.is_mirror && other_project.is_mirror;
+        merge_values(&mut self.web_urls, &other_project.web_urls);
+        merge_values(&mut self.vcs_urls, &other_project.vcs_urls);
+        merge_values(&mut self.artifact_names, &other_project.artifact_names);
+        merge_values(&mut self.build_systems, &other_project.build_systems);
+        merge_values(&mut self.maintainers, &other_project.maintainers);
+        merge_values(&mut self.versions, &other_project.versions);
+        merge_values(&mut self.keywords, &other_project.keywords);
+        merge_values(&mut self.languages, &other_project.languages);
+        merge_values(&mut self.root_hashes, &other_project.root_hashes);
+        merge_values(&mut self.aliases, &other_project.aliases);
+        if other_project.id != self.id && !other_project.id.is_empty() {
+            merge_values(&mut self.aliases, &vec![other_project.id.to_string()]);
         }
     }
+
+    // Gets the newest version of the project, whatever versioning scheme it uses.
+    pub fn get_latest_version(&self) -> Option<String> {
+        let mut versions = self.versions.to_vec();
+        crate::version::sort_versions(&mut versions);
+        match versions.pop() {
+            Some(v) => match Version::parse(&v) {
+                Some(_) => Some(v),
+                None => None,
+            },
+            None => None,
+        }
+    }
+
+    // Sets the programming languages of the project, and adds the build systems
+    // that are likely used with those languages.
+    pub fn set_languages(&mut self, languages: Vec<String>) {
+        for language in &languages {
+            if let Some(build_system) = crate::modules::BuildSystem::from_language(language) {
+                merge_values(&mut self.build_systems, &vec![build_system.get_name().to_string()]);
+            }
+        }
+        self.languages = languages;
+    }
+
+    // Gets a signature of the git history of the project, based on its root
+    // commit hashes. Two projects with the same signature are considered to be
+    // the same project hosted in different places.
+    pub fn get_root_signature(&self) -> Option<String> {
+        if self.root_hashes.is_empty() {
+            return None;
+        }
+        Some(get_root_signature(&self.root_hashes))
+    }
 }
 
-#[derive(Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct FlatpakApp {
+    // The runtime and the SDK, with their version, for example org.gnome.Platform//40.
+    pub runtime: String,
+    pub sdk: String,
+    // Summaries of the permissions requested by the application, for example network or home read-write.
+    pub permissions: Vec<String>,
+}
+
+fn get_root_signature(root_hashes: &Vec<String>) -> String {
+    let mut root_hashes = root_hashes.to_vec();
+    root_hashes.sort();
+    root_hashes.dedup();
+    root_hashes.join(",")
+}
+
+// TODO remove when those are migrated to HashSets.
+fn merge_values(values: &mut Vec<String>, other_values: &Vec<String>) {
+    for value in other_values {
+        if !values.contains(value) {
+            values.push(value.to_string());
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
 pub struct ProjectVersion {
     pub project_id: String,
     // Name of the version. Normally follows sem ver.
@@ -97,32 +229,125 @@ pub struct ProjectVersion {
     pub url: String,
     pub url_type: crate::modules::SourceType,
     pub tag: String,
+    // The hash of the commit that the tag points to, if any.
+    pub commit: String,
     pub branch: String,
     pub sha256sum: String,
+    // The release date of the version, in the YYYY-MM-DD format, if known.
+    pub date: String,
     pub dependencies: Vec<Dependency>,
 }
 
-#[derive(Serialize, Deserialize, Default)]
+impl ProjectVersion {
+    // Gets a module pinned to this version of the project.
+    pub fn to_module(&self, name: &str) -> crate::modules::SoftwareModule {
+        let mut module = crate::modules::SoftwareModule::default();
+        module.name = name.to_string();
+        module.project_id = Some(self.project_id.to_string());
+        module.version = self.name.to_string();
+        module.url = self.url.to_string();
+        module.source_type = self.url_type.clone();
+        module.tag = self.tag.to_string();
+        module.commit = self.commit.to_string();
+        module.archive_checksum = self.sha256sum.to_string();
+        module
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
 pub struct Dependency {
-    pub min_version: crate::version::SemanticVersion,
-    pub max_version: crate::version::SemanticVersion,
+    pub version_range: crate::version::VersionRange,
     pub project_id: String,
 }
 
+// Gets the highest version matching the version range.
+pub fn get_best_matching_version<'a>(versions: &'a Vec<ProjectVersion>, version_range: &VersionRange) -> Option<&'a ProjectVersion> {
+    let mut best_version: Option<(&ProjectVersion, Version)> = None;
+    for project_version in versions {
+        let version = match Version::parse(&project_version.name) {
+            Some(v) => v,
+            None => continue,
+        };
+        let semantic_version = match version.to_semantic_version() {
+            Some(v) => v,
+            None => continue,
+        };
+        if !version_range.matches(&semantic_version) {
+            continue;
+        }
+        if let Some((_, best)) = &best_version {
+            if &version <= best {
+                continue;
+            }
+        }
+        best_version = Some((project_version, version));
+    }
+    best_version.map(|(v, _)| v)
+}
+
+// Gets a module for the version of the project best matching the version range.
+pub fn get_project_module(
+    project: &SoftwareProject,
+    module_name: &str,
+    versions: &Vec<ProjectVersion>,
+    version_range: &VersionRange,
+) -> Result<crate::modules::SoftwareModule, String> {
+    let mut module = match get_best_matching_version(versions, version_range) {
+        Some(v) => v.to_module(module_name),
+        None => {
+            // Without any known version, we can only install the default branch.
+            if version_range != &VersionRange::default() {
+                return Err(format!("No version of {} matching {}.", project.id, version_range));
+            }
+            if project.vcs_urls.is_empty() {
+                return Err(format!("No version and no vcs url known for {}.", project.id));
+            }
+            let mut module = crate::modules::SoftwareModule::default();
+            module.name = module_name.to_string();
+            module.project_id = Some(project.id.to_string());
+            module.url = project.vcs_urls[0].to_string();
+            module.source_type = crate::modules::SourceType::Git;
+            module
+        }
+    };
+    if module.url.is_empty() && !project.vcs_urls.is_empty() {
+        module.url = project.vcs_urls[0].to_string();
+    }
+    for build_system in &project.build_systems {
+        let build_system = crate::modules::BuildSystem::from_name(build_system);
+        if build_system != crate::modules::BuildSystem::Unknown {
+            module.build_system = build_system;
+            break;
+        }
+    }
+    Ok(module)
+}
+
 // Get the potential modules that are inferable from the
 // projects.
 pub fn get_modules() -> Vec<crate::modules::SoftwareModule> {
     let mut modules = vec![];
     for project in crate::db::Database::get_all_projects() {
+        let version_records = crate::db::Database::get_project_versions(&project.id);
         for project_version in &project.versions {
             for artifact_name in &project.artifact_names {
                 let mut module = crate::modules::SoftwareModule::default();
                 module.name = artifact_name.to_string();
+                module.project_id = Some(project.id.to_string());
                 module.version = project_version.to_string();
                 module.tag = project_version.to_string();
                 if project.vcs_urls.len() != 0 {
                     module.url = project.vcs_urls[0].to_string();
                 }
+                // The version records have the real tag name and the commit hash.
+                if let Some(version) = version_records.iter().find(|v| &v.name == project_version) {
+                    module.tag = version.tag.to_string();
+                    module.commit = version.commit.to_string();
+                    if !version.url.is_empty() {
+                        module.url = version.url.to_string();
+                    }
+                }
                 modules.push(module);
                 // println!("Project {} could install {} version {}.", project.name, artifact_name, project_version);
             }
@@ -131,15 +356,231 @@ pub fn get_modules() -> Vec<crate::modules::SoftwareModule> {
     modules
 }
 
-pub fn get_project_tag_names() -> Vec<String> {
-    // call tag_names(&self, pattern: Option<&str>) -> Result<StringArray, Error>
-    // on the repository.
-    // https://docs.rs/git2/0.13.8/git2/struct.Repository.html#method.tag_names
-    return vec![];
+pub fn get_project_tag_names(repo_path: &str) -> Result<Vec<String>, String> {
+    let tags = crate::utils::get_git_repo_tags(repo_path)?;
+    Ok(tags.into_iter().map(|(tag_name, _)| tag_name).collect())
 }
 
-pub fn get_project_commit_signature() -> String {
-    // Here we want to asses a project signature by traversing the Revision graph.
-    // https://docs.rs/git2/0.13.8/git2/struct.Revwalk.html
-    return String::from("");
+// Gets the versions of a project from the tags of its git repository.
+// Tags that cannot be parsed as a version are ignored.
+pub fn get_project_versions(project_id: &str, repo_url: &str, repo_path: &str) -> Vec<ProjectVersion> {
+    let tags = match crate::utils::get_git_repo_tags(repo_path) {
+        Ok(t) => t,
+        Err(e) => {
+            log::warn!("Could not get the tags for repo located at {}: {}.", repo_path, e);
+            return vec![];
+        }
+    };
+    let mut versions = get_versions_from_tags(project_id, repo_url, tags);
+    // The dates of the versions are only known if the project publishes its releases in its AppStream metadata.
+    for (_, component) in crate::appstream::find_components(Path::new(repo_path)) {
+        for version in versions.iter_mut().filter(|v| v.date.is_empty()) {
+            if let Some(date) = component.get_release_date(&version.name) {
+                version.date = date.to_string();
+            }
+        }
+    }
+    versions
+}
+
+// Gets the versions of a project from the tags of its git repository, with the
+// hash of the commit they point to. Tags that are not versions are ignored.
+pub fn get_versions_from_tags(project_id: &str, repo_url: &str, tags: Vec<(String, String)>) -> Vec<ProjectVersion> {
+    let mut versions: Vec<ProjectVersion> = vec![];
+    for (tag_name, commit_hash) in tags {
+        let semantic_version = match crate::version::SemanticVersion::from_tag_name(&tag_name) {
+            Some(v) => v,
+            None => {
+                log::debug!("Could not parse a version from tag {}.", tag_name);
+                continue;
+            }
+        };
+        let version_name = semantic_version.to_string();
+        // Some projects have multiple tags for the same version.
+        if versions.iter().any(|v| v.name == version_name) {
+            continue;
+        }
+        versions.push(ProjectVersion {
+            project_id: project_id.to_string(),
+            name: version_name,
+            url: repo_url.to_string(),
+            url_type: crate::modules::SourceType::Git,
+            tag: tag_name,
+            commit: commit_hash,
+            ..Default::default()
+        });
+    }
+    versions
+}
+
+// Normalizes a url for comparison, since the same repository can be
+// referenced with or without the .git suffix or a trailing slash.
+fn normalize_url(url: &str) -> String {
+    let url = url.trim().to_lowercase();
+    let url = url.split("://").last().unwrap_or(&url).to_string();
+    url.trim_end_matches('/').trim_end_matches(".git").to_string()
+}
+
+// Finds the project that a module is built from, using the project of the module when it
+// is known, and otherwise the url of the source of the module. The source can be the repository
+// of the project, or an archive published on the web pages of the project, like the release
+// archives of GitHub or GitLab.
+pub fn find_module_project<'a>(projects: &'a Vec<SoftwareProject>, module: &crate::modules::SoftwareModule) -> Option<&'a SoftwareProject> {
+    if let Some(project_id) = &module.project_id {
+        // The project could have been merged into another project since the module was installed.
+        if let Some(project) = projects.iter().find(|p| &p.id == project_id || p.aliases.contains(project_id)) {
+            return Some(project);
+        }
+    }
+    if module.url.is_empty() {
+        return None;
+    }
+    let module_url = normalize_url(&module.url);
+    if let Some(project) = projects.iter().find(|p| p.vcs_urls.iter().any(|u| normalize_url(u) == module_url)) {
+        return Some(project);
+    }
+    // The longest matching url is the most specific one.
+    let mut best_match: Option<(&SoftwareProject, usize)> = None;
+    for project in projects {
+        for url in project.vcs_urls.iter().chain(project.web_urls.iter()) {
+            let url = normalize_url(url);
+            // A project url has to contain a path to identify a project.
+            if !url.contains('/') || !module_url.starts_with(&format!("{}/", url)) {
+                continue;
+            }
+            if best_match.map(|(_, l)| url.len() > l).unwrap_or(true) {
+                best_match = Some((project, url.len()));
+            }
+        }
+    }
+    best_match.map(|(p, _)| p)
+}
+
+// Finds the project with the same git history, using the root commit hashes of a repository.
+pub fn find_project_by_root_hashes<'a>(projects: &'a Vec<SoftwareProject>, root_hashes: &Vec<String>) -> Option<&'a SoftwareProject> {
+    if root_hashes.is_empty() {
+        return None;
+    }
+    let root_signature = get_root_signature(root_hashes);
+    projects.iter().find(|p| p.get_root_signature().as_ref() == Some(&root_signature))
+}
+
+// Gets the version of a project published as a release on a software hub, with the url of
+// the release archive. Releases that are not versions are ignored.
+pub fn get_release_version(project_id: &str, tag_name: &str, archive_url: &str) -> Option<ProjectVersion> {
+    let semantic_version = crate::version::SemanticVersion::from_tag_name(tag_name)?;
+    Some(ProjectVersion {
+        project_id: project_id.to_string(),
+        name: semantic_version.to_string(),
+        url: archive_url.to_string(),
+        url_type: crate::modules::SourceType::Tarball,
+        tag: tag_name.to_string(),
+        ..Default::default()
+    })
+}
+
+// Gets the signature of a project by looking at the root commits of its git repository.
+// See SoftwareProject::get_root_signature.
+pub fn get_project_commit_signature(repo_path: &str) -> Result<String, String> {
+    let root_hashes = crate::utils::get_git_repo_root_hashes(repo_path)?;
+    Ok(get_root_signature(&root_hashes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_versions(names: Vec<&str>) -> Vec<ProjectVersion> {
+        names
+            .iter()
+            .map(|n| ProjectVersion {
+                name: n.to_string(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn test_get_best_matching_version() {
+        let versions = get_versions(vec!["1.2.0", "1.10.1", "2.0.0", "2.1.0-rc1", "not-a-version"]);
+        let best_version = get_best_matching_version(&versions, &VersionRange::parse("^1.2").unwrap());
+        assert_eq!(best_version.unwrap().name, "1.10.1");
+        let best_version = get_best_matching_version(&versions, &VersionRange::default());
+        assert_eq!(best_version.unwrap().name, "2.0.0");
+
+        let versions = get_versions(vec!["2021.03", "2021.10", "2020.12"]);
+        let best_version = get_best_matching_version(&versions, &VersionRange::default());
+        assert_eq!(best_version.unwrap().name, "2021.10");
+
+        let versions = get_versions(vec!["1:2.30-1ubuntu2", "2.29-1"]);
+        let best_version = get_best_matching_version(&versions, &VersionRange::parse(">=2.30").unwrap());
+        assert_eq!(best_version.unwrap().name, "1:2.30-1ubuntu2");
+        assert!(get_best_matching_version(&versions, &VersionRange::parse(">=3").unwrap()).is_none());
+    }
+
+    #[test]
+    pub fn test_get_project_module() {
+        let mut project = SoftwareProject::default();
+        project.id = "org.gnome.gitlab.gnome.glib".to_string();
+        project.vcs_urls = vec!["https://gitlab.gnome.org/GNOME/glib.git".to_string()];
+        project.build_systems = vec!["meson".to_string()];
+        let mut versions = get_versions(vec!["2.64.0", "2.66.0"]);
+        versions[0].tag = "2.64.0".to_string();
+        versions[0].commit = "0123456789abcdef".to_string();
+
+        let module = get_project_module(&project, "glib", &versions, &VersionRange::parse("~2.64").unwrap()).unwrap();
+        assert_eq!(module.version, "2.64.0");
+        assert_eq!(module.tag, "2.64.0");
+        assert_eq!(module.commit, "0123456789abcdef");
+        assert_eq!(module.url, "https://gitlab.gnome.org/GNOME/glib.git");
+        assert!(module.build_system == crate::modules::BuildSystem::Meson);
+
+        assert!(get_project_module(&project, "glib", &versions, &VersionRange::parse(">=3").unwrap()).is_err());
+        let module = get_project_module(&project, "glib", &vec![], &VersionRange::default()).unwrap();
+        assert!(module.tag.is_empty());
+    }
+
+    #[test]
+    pub fn test_get_latest_version() {
+        let mut project = SoftwareProject::default();
+        assert!(project.get_latest_version().is_none());
+        project.versions = vec!["4.4.20".to_string(), "4.10".to_string(), "4.4".to_string()];
+        assert_eq!(project.get_latest_version().unwrap(), "4.10");
+    }
+
+    #[test]
+    pub fn test_find_module_project() {
+        let mut glib = SoftwareProject::default();
+        glib.id = "org.gnome.gitlab.GNOME.glib".to_string();
+        glib.vcs_urls = vec!["https://gitlab.gnome.org/GNOME/glib.git".to_string()];
+        glib.aliases = vec!["com.github.gnome.glib".to_string()];
+        let mut libfoo = SoftwareProject::default();
+        libfoo.id = "com.github.example.libfoo".to_string();
+        libfoo.vcs_urls = vec!["https://github.com/example/libfoo.git".to_string()];
+        libfoo.web_urls = vec!["https://github.com".to_string()];
+        let projects = vec![glib, libfoo];
+
+        let mut module = crate::modules::SoftwareModule::default();
+        module.url = "https://gitlab.gnome.org/GNOME/glib".to_string();
+        assert_eq!(find_module_project(&projects, &module).unwrap().id, "org.gnome.gitlab.GNOME.glib");
+        module.url = "https://github.com/example/libfoo/archive/v1.0.tar.gz".to_string();
+        assert_eq!(find_module_project(&projects, &module).unwrap().id, "com.github.example.libfoo");
+        // A domain alone does not identify a project.
+        module.url = "https://github.com/example/libbar/archive/v1.0.tar.gz".to_string();
+        assert!(find_module_project(&projects, &module).is_none());
+        // The project of an installed module is found even if its url changed.
+        module.project_id = Some("com.github.gnome.glib".to_string());
+        assert_eq!(find_module_project(&projects, &module).unwrap().id, "org.gnome.gitlab.GNOME.glib");
+    }
+
+    #[test]
+    pub fn test_find_project_by_root_hashes() {
+        let mut project = SoftwareProject::default();
+        project.id = "com.github.example.libfoo".to_string();
+        project.root_hashes = vec!["abcdef".to_string(), "012345".to_string()];
+        let projects = vec![project];
+        let root_hashes = vec!["012345".to_string(), "abcdef".to_string()];
+        assert_eq!(find_project_by_root_hashes(&projects, &root_hashes).unwrap().id, "com.github.example.libfoo");
+        assert!(find_project_by_root_hashes(&projects, &vec![]).is_none());
+    }
 }
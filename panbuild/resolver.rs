@@ -0,0 +1,460 @@
+use std::fmt;
+
+use crate::modules::{SoftwareModule, SourceType};
+use crate::projects::{ProjectVersion, SoftwareProject};
+use crate::version::{Version, VersionRange};
+
+// The modules to add to a manifest in order to install a package,
+// including all its transitive dependencies.
+#[derive(Debug, Default)]
+pub struct InstallPlan {
+    // The modules to install, ordered so that every module comes
+    // after its own dependencies.
+    pub modules: Vec<SoftwareModule>,
+    // The dependencies that are already provided, either by the runtime
+    // or by the modules already in the manifest.
+    pub provided: Vec<String>,
+    // The dependencies that could not be found in the projects database.
+    pub unresolved: Vec<String>,
+}
+impl fmt::Display for InstallPlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Modules to install, in build order:")?;
+        for module in &self.modules {
+            if module.version.is_empty() {
+                writeln!(f, "  {} ({})", module.name, module.url)?;
+            } else {
+                writeln!(f, "  {} {} ({})", module.name, module.version, module.url)?;
+            }
+        }
+        if !self.provided.is_empty() {
+            writeln!(f, "Already provided: {}", self.provided.join(", "))?;
+        }
+        if !self.unresolved.is_empty() {
+            writeln!(f, "Could not resolve: {}", self.unresolved.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+struct Resolver<'a> {
+    projects: &'a Vec<SoftwareProject>,
+    get_versions: &'a dyn Fn(&str) -> Vec<ProjectVersion>,
+    provided_names: &'a Vec<String>,
+    // The ids of the projects being resolved, used to detect dependency cycles.
+    resolving: Vec<String>,
+    plan: InstallPlan,
+}
+impl<'a> Resolver<'a> {
+    fn is_provided(&self, project: &SoftwareProject) -> bool {
+        self.provided_names
+            .iter()
+            .any(|n| n == &project.id || n == &project.name || project.artifact_names.contains(n))
+    }
+
+    fn resolve(&mut self, project: &SoftwareProject, module_name: &str, version_range: &VersionRange) -> Result<(), String> {
+        if self.is_provided(project) {
+            log::info!("{} is already provided, not installing it.", project.id);
+            if !self.plan.provided.contains(&project.id) {
+                self.plan.provided.push(project.id.to_string());
+            }
+            return Ok(());
+        }
+
+        let project_id = Some(project.id.to_string());
+        if let Some(module) = self.plan.modules.iter().find(|m| m.project_id == project_id) {
+            // The project was already required by another module, we make sure that
+            // the version that was selected also satisfies this requirement.
+            let semantic_version = Version::parse(&module.version).and_then(|v| v.to_semantic_version());
+            let is_compatible = match semantic_version {
+                Some(v) => version_range.matches(&v),
+                None => version_range == &VersionRange::default(),
+            };
+            if !is_compatible {
+                return Err(format!(
+                    "Conflicting requirements for {}: version {} was selected, but {} is required.",
+                    project.id, module.version, version_range
+                ));
+            }
+            return Ok(());
+        }
+
+        if self.resolving.contains(&project.id) {
+            return Err(format!("Dependency cycle detected: {} -> {}.", self.resolving.join(" -> "), project.id));
+        }
+
+        let versions = (self.get_versions)(&project.id);
+        let module = crate::projects::get_project_module(project, module_name, &versions, version_range)?;
+        let dependencies = match crate::projects::get_best_matching_version(&versions, version_range) {
+            Some(v) => v.dependencies.to_vec(),
+            None => vec![],
+        };
+
+        self.resolving.push(project.id.to_string());
+        for dependency in &dependencies {
+            let dependency_project = match find_project_by_id(self.projects, &dependency.project_id) {
+                Some(p) => p,
+                None => {
+                    log::warn!("Could not find dependency {} of {} in the database.", dependency.project_id, project.id);
+                    if !self.plan.unresolved.contains(&dependency.project_id) {
+                        self.plan.unresolved.push(dependency.project_id.to_string());
+                    }
+                    continue;
+                }
+            };
+            let dependency_module_name = get_project_module_name(dependency_project);
+            self.resolve(dependency_project, &dependency_module_name, &dependency.version_range)?;
+        }
+        self.resolving.pop();
+
+        self.plan.modules.push(module);
+        Ok(())
+    }
+}
+
+fn find_project_by_id<'a>(projects: &'a Vec<SoftwareProject>, project_id: &str) -> Option<&'a SoftwareProject> {
+    projects.iter().find(|p| p.id == project_id || p.aliases.iter().any(|a| a == project_id))
+}
+
+// Gets the name to use for the module installing a project.
+fn get_project_module_name(project: &SoftwareProject) -> String {
+    if let Some(artifact_name) = project.artifact_names.first() {
+        return artifact_name.to_string();
+    }
+    if !project.name.is_empty() {
+        return project.name.to_string();
+    }
+    project.id.split('.').last().unwrap_or(&project.id).to_string()
+}
+
+// Finds the project providing the artifact or project named `name`.
+pub fn find_project<'a>(projects: &'a Vec<SoftwareProject>, name: &str) -> Result<&'a SoftwareProject, String> {
+    let candidates: Vec<&SoftwareProject> = projects
+        .iter()
+        .filter(|p| p.id == name || p.name == name || p.artifact_names.iter().any(|a| a == name))
+        .collect();
+    if candidates.is_empty() {
+        return Err(format!("Could not find any project providing {}.", name));
+    }
+    if candidates.len() > 1 {
+        let candidate_ids: Vec<&str> = candidates.iter().map(|p| p.id.as_str()).collect();
+        return Err(format!(
+            "{} is provided by more than one project, use one of the project ids instead: {}.",
+            name,
+            candidate_ids.join(", ")
+        ));
+    }
+    Ok(candidates[0])
+}
+
+// Computes the modules required to install the package named `name`, with all its
+// transitive dependencies. The dependencies listed in `provided_names` are skipped.
+pub fn get_install_plan(
+    projects: &Vec<SoftwareProject>,
+    get_versions: &dyn Fn(&str) -> Vec<ProjectVersion>,
+    name: &str,
+    version_range: &VersionRange,
+    provided_names: &Vec<String>,
+) -> Result<InstallPlan, String> {
+    let project = find_project(projects, name)?;
+    let mut resolver = Resolver {
+        projects,
+        get_versions,
+        provided_names,
+        resolving: vec![],
+        plan: InstallPlan::default(),
+    };
+    resolver.resolve(project, name, version_range)?;
+    if resolver.plan.modules.is_empty() {
+        return Err(format!("{} is already provided by the runtime or by another module of the manifest.", name));
+    }
+    Ok(resolver.plan)
+}
+
+// Same as get_install_plan, using the projects and the versions from the database.
+pub fn get_db_install_plan(name: &str, version_range: &VersionRange, provided_names: &Vec<String>) -> Result<InstallPlan, String> {
+    let projects = crate::db::Database::get_all_projects();
+    get_install_plan(&projects, &crate::db::Database::get_project_versions, name, version_range, provided_names)
+}
+
+// A newer version of a module of a manifest.
+#[derive(Debug)]
+pub struct ModuleUpgrade {
+    // The version that the module is currently pinned to.
+    pub current_version: Version,
+    pub new_version: Version,
+    // The module, pinned to the new version.
+    pub module: SoftwareModule,
+}
+
+// Gets the version record that the module is pinned to, if any.
+pub fn get_module_version<'a>(versions: &'a Vec<ProjectVersion>, module: &SoftwareModule) -> Option<&'a ProjectVersion> {
+    versions.iter().find(|v| {
+        (!module.tag.is_empty() && v.tag == module.tag)
+            || (!module.commit.is_empty() && v.commit == module.commit)
+            || (module.source_type.is_archive() && !v.url.is_empty() && v.url == module.url)
+    })
+}
+
+// Gets the version that the module is pinned to. When the pinned version is not
+// in the database, we try to infer it from the tag of the module.
+pub fn get_module_current_version(versions: &Vec<ProjectVersion>, module: &SoftwareModule) -> Option<Version> {
+    if let Some(project_version) = get_module_version(versions, module) {
+        return Version::parse(&project_version.name);
+    }
+    crate::version::SemanticVersion::from_tag_name(&module.tag).map(Version::Semantic)
+}
+
+// Gets the newest version of the module, if it is newer than the version currently used.
+// Modules that are not pinned to a specific version cannot be upgraded.
+pub fn get_module_upgrade(
+    projects: &Vec<SoftwareProject>,
+    get_versions: &dyn Fn(&str) -> Vec<ProjectVersion>,
+    module: &SoftwareModule,
+) -> Option<ModuleUpgrade> {
+    let project = crate::projects::find_module_project(projects, module)?;
+    let versions = get_versions(&project.id);
+    let current_version = match get_module_current_version(&versions, module) {
+        Some(v) => v,
+        None => {
+            log::info!("Module {} is not pinned to a known version.", module.name);
+            return None;
+        }
+    };
+    // Archives need a checksum to be pinned, and git repos need a tag or a commit.
+    let versions: Vec<ProjectVersion> = versions
+        .into_iter()
+        .filter(|v| match &module.source_type {
+            SourceType::Git => v.url_type == SourceType::Git && (!v.tag.is_empty() || !v.commit.is_empty()),
+            t if t.is_archive() => v.url_type.is_archive() && !v.sha256sum.is_empty(),
+            _ => true,
+        })
+        .collect();
+    let newest_project_version = crate::projects::get_best_matching_version(&versions, &VersionRange::default())?;
+    let new_version = Version::parse(&newest_project_version.name)?;
+    if new_version <= current_version {
+        return None;
+    }
+    let mut new_module = newest_project_version.to_module(&module.name);
+    if new_module.url.is_empty() {
+        new_module.url = module.url.to_string();
+    }
+    Some(ModuleUpgrade {
+        current_version,
+        new_version,
+        module: new_module,
+    })
+}
+
+// Gets the names of the modules that depend on the module named `module_name`,
+// according to the dependencies of the versions they are pinned to.
+pub fn get_dependent_modules(
+    projects: &Vec<SoftwareProject>,
+    get_versions: &dyn Fn(&str) -> Vec<ProjectVersion>,
+    modules: &Vec<SoftwareModule>,
+    module_name: &str,
+) -> Vec<String> {
+    let mut dependent_modules = vec![];
+    let project = match modules.iter().find(|m| m.name == module_name) {
+        Some(m) => match crate::projects::find_module_project(projects, m) {
+            Some(p) => p,
+            None => return dependent_modules,
+        },
+        None => return dependent_modules,
+    };
+    for module in modules {
+        if module.name == module_name {
+            continue;
+        }
+        let module_project = match crate::projects::find_module_project(projects, module) {
+            Some(p) => p,
+            None => continue,
+        };
+        let versions = get_versions(&module_project.id);
+        let module_version = match get_module_version(&versions, module) {
+            Some(v) => v,
+            None => continue,
+        };
+        let depends_on_project = module_version
+            .dependencies
+            .iter()
+            .any(|d| d.project_id == project.id || project.aliases.contains(&d.project_id));
+        if depends_on_project {
+            dependent_modules.push(module.name.to_string());
+        }
+    }
+    dependent_modules
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::projects::Dependency;
+    use std::collections::BTreeMap;
+
+    fn get_project(id: &str, artifact_name: &str) -> SoftwareProject {
+        SoftwareProject {
+            id: id.to_string(),
+            artifact_names: vec![artifact_name.to_string()],
+            vcs_urls: vec![format!("https://{}.git", artifact_name)],
+            ..Default::default()
+        }
+    }
+
+    fn get_version(project_id: &str, name: &str, dependencies: Vec<(&str, &str)>) -> ProjectVersion {
+        ProjectVersion {
+            project_id: project_id.to_string(),
+            name: name.to_string(),
+            tag: name.to_string(),
+            url_type: SourceType::Git,
+            dependencies: dependencies
+                .iter()
+                .map(|(id, range)| Dependency {
+                    project_id: id.to_string(),
+                    version_range: VersionRange::parse(range).unwrap(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn get_test_db() -> (Vec<SoftwareProject>, BTreeMap<String, Vec<ProjectVersion>>) {
+        let projects = vec![
+            get_project("org.gtk.gtk", "gtk"),
+            get_project("org.gnome.pango", "pango"),
+            get_project("org.gnome.glib", "glib"),
+            get_project("org.freedesktop.libffi", "libffi"),
+        ];
+        let mut versions = BTreeMap::new();
+        versions.insert(
+            "org.gtk.gtk".to_string(),
+            vec![get_version(
+                "org.gtk.gtk",
+                "3.24.0",
+                vec![("org.gnome.pango", ">=1.44"), ("org.gnome.glib", "^2.60")],
+            )],
+        );
+        versions.insert(
+            "org.gnome.pango".to_string(),
+            vec![
+                get_version("org.gnome.pango", "1.44.0", vec![("org.gnome.glib", ">=2.62")]),
+                get_version("org.gnome.pango", "1.48.0", vec![("org.gnome.glib", ">=2.62"), ("org.unknown.harfbuzz", "*")]),
+            ],
+        );
+        versions.insert(
+            "org.gnome.glib".to_string(),
+            vec![
+                get_version("org.gnome.glib", "2.62.0", vec![("org.freedesktop.libffi", "*")]),
+                get_version("org.gnome.glib", "2.66.0", vec![("org.freedesktop.libffi", "*")]),
+            ],
+        );
+        (projects, versions)
+    }
+
+    #[test]
+    pub fn test_install_plan_order() {
+        let (projects, versions) = get_test_db();
+        let get_versions = |id: &str| versions.get(id).cloned().unwrap_or(vec![]);
+        let plan = get_install_plan(&projects, &get_versions, "gtk", &VersionRange::default(), &vec![]).unwrap();
+        let names: Vec<&str> = plan.modules.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["libffi", "glib", "pango", "gtk"]);
+        assert_eq!(plan.modules[1].version, "2.66.0");
+        assert_eq!(plan.modules[2].version, "1.48.0");
+        assert_eq!(plan.unresolved, vec!["org.unknown.harfbuzz"]);
+    }
+
+    #[test]
+    pub fn test_install_plan_skips_provided() {
+        let (projects, versions) = get_test_db();
+        let get_versions = |id: &str| versions.get(id).cloned().unwrap_or(vec![]);
+        let provided_names = vec!["glib".to_string()];
+        let plan = get_install_plan(&projects, &get_versions, "gtk", &VersionRange::default(), &provided_names).unwrap();
+        let names: Vec<&str> = plan.modules.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["pango", "gtk"]);
+        assert_eq!(plan.provided, vec!["org.gnome.glib"]);
+
+        assert!(get_install_plan(&projects, &get_versions, "glib", &VersionRange::default(), &provided_names).is_err());
+    }
+
+    #[test]
+    pub fn test_install_plan_conflict() {
+        let (projects, mut versions) = get_test_db();
+        versions.insert(
+            "org.gnome.pango".to_string(),
+            vec![get_version("org.gnome.pango", "1.44.0", vec![("org.gnome.glib", "~2.62")])],
+        );
+        let get_versions = |id: &str| versions.get(id).cloned().unwrap_or(vec![]);
+        // gtk requires pango first, which pins glib to 2.62.
+        let plan = get_install_plan(&projects, &get_versions, "gtk", &VersionRange::default(), &vec![]).unwrap();
+        assert_eq!(plan.modules[1].version, "2.62.0");
+
+        versions.insert(
+            "org.gtk.gtk".to_string(),
+            vec![get_version(
+                "org.gtk.gtk",
+                "3.24.0",
+                vec![("org.gnome.pango", "*"), ("org.gnome.glib", ">=2.66")],
+            )],
+        );
+        let get_versions = |id: &str| versions.get(id).cloned().unwrap_or(vec![]);
+        let error = get_install_plan(&projects, &get_versions, "gtk", &VersionRange::default(), &vec![]).unwrap_err();
+        assert!(error.contains("Conflicting requirements"));
+    }
+
+    #[test]
+    pub fn test_get_module_upgrade() {
+        let (projects, mut versions) = get_test_db();
+        // Archive versions can't be used to upgrade git modules.
+        versions.get_mut("org.gnome.glib").unwrap().push(ProjectVersion {
+            project_id: "org.gnome.glib".to_string(),
+            name: "2.68.0".to_string(),
+            url: "https://download.gnome.org/sources/glib/2.68/glib-2.68.0.tar.xz".to_string(),
+            url_type: SourceType::Tarball,
+            tag: "2.68.0".to_string(),
+            sha256sum: "67734f584f3a05a2872f57e9a8db38f3b06c7087fb531c5be4d4dc6b1d7a3e8c".to_string(),
+            ..Default::default()
+        });
+        let get_versions = |id: &str| versions.get(id).cloned().unwrap_or(vec![]);
+
+        let mut module = SoftwareModule {
+            name: "glib".to_string(),
+            url: "https://glib.git".to_string(),
+            source_type: SourceType::Git,
+            tag: "2.62.0".to_string(),
+            ..Default::default()
+        };
+        let upgrade = get_module_upgrade(&projects, &get_versions, &module).unwrap();
+        assert_eq!(upgrade.current_version.to_string(), "2.62.0");
+        assert_eq!(upgrade.module.tag, "2.66.0");
+        assert_eq!(upgrade.module.url, "https://glib.git");
+        assert_eq!(upgrade.module.name, "glib");
+
+        module.tag = "2.66.0".to_string();
+        assert!(get_module_upgrade(&projects, &get_versions, &module).is_none());
+        // Modules following a branch are not upgraded.
+        module.tag = "".to_string();
+        assert!(get_module_upgrade(&projects, &get_versions, &module).is_none());
+    }
+
+    #[test]
+    pub fn test_get_dependent_modules() {
+        let (projects, versions) = get_test_db();
+        let get_versions = |id: &str| versions.get(id).cloned().unwrap_or(vec![]);
+        let plan = get_install_plan(&projects, &get_versions, "gtk", &VersionRange::default(), &vec![]).unwrap();
+        let dependent_modules = get_dependent_modules(&projects, &get_versions, &plan.modules, "glib");
+        assert_eq!(dependent_modules, vec!["pango", "gtk"]);
+        assert!(get_dependent_modules(&projects, &get_versions, &plan.modules, "gtk").is_empty());
+    }
+
+    #[test]
+    pub fn test_install_plan_cycle() {
+        let (projects, mut versions) = get_test_db();
+        versions.insert(
+            "org.freedesktop.libffi".to_string(),
+            vec![get_version("org.freedesktop.libffi", "3.3.0", vec![("org.gnome.glib", "*")])],
+        );
+        let get_versions = |id: &str| versions.get(id).cloned().unwrap_or(vec![]);
+        let error = get_install_plan(&projects, &get_versions, "glib", &VersionRange::default(), &vec![]).unwrap_err();
+        assert!(error.contains("cycle"));
+    }
+}
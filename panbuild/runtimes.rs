@@ -0,0 +1,187 @@
+use std::fs;
+use std::path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::version::Version;
+
+// The catalogs distributed with panbuild. Additional catalogs can be
+// added in the runtimes directory of the database.
+const BUILTIN_CATALOGS: &[&str] = &[
+    include_str!("runtimes/org.freedesktop.Sdk-20.08.yaml"),
+    include_str!("runtimes/org.freedesktop.Sdk-21.08.yaml"),
+    include_str!("runtimes/org.gnome.Sdk-40.yaml"),
+    include_str!("runtimes/org.gnome.Sdk-41.yaml"),
+    include_str!("runtimes/org.kde.Sdk-5.15-21.08.yaml"),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct RuntimeLibrary {
+    pub name: String,
+    pub version: String,
+    // The names under which the library is found by pkg-config.
+    pub pkg_config_names: Vec<String>,
+}
+impl RuntimeLibrary {
+    pub fn matches(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        self.name.to_lowercase() == name || self.pkg_config_names.iter().any(|n| n.to_lowercase() == name)
+    }
+}
+
+// The list of the libraries shipped in a specific version of a runtime.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct RuntimeCatalog {
+    // The id of the sdk, for example org.gnome.Sdk.
+    pub id: String,
+    // The branch of the sdk, for example 40.
+    pub version: String,
+    // The sdk this sdk is based on, if any. The libraries
+    // of the base sdk are also provided by this sdk.
+    pub base_id: Option<String>,
+    pub base_version: Option<String>,
+    pub libraries: Vec<RuntimeLibrary>,
+}
+impl RuntimeCatalog {
+    pub fn parse(content: &str) -> Result<RuntimeCatalog, String> {
+        match serde_yaml::from_str(content) {
+            Ok(c) => Ok(c),
+            Err(e) => Err(format!("Could not parse runtime catalog: {}.", e)),
+        }
+    }
+}
+
+// Gets the sdk id associated with a runtime id.
+fn get_sdk_id(runtime_id: &str) -> String {
+    match runtime_id.strip_suffix(".Platform") {
+        Some(prefix) => format!("{}.Sdk", prefix),
+        None => runtime_id.to_string(),
+    }
+}
+
+// Gets the built-in catalogs and the catalogs found in the database.
+// A catalog from the database replaces the built-in catalog with the same id and version.
+pub fn get_all_catalogs() -> Vec<RuntimeCatalog> {
+    let mut catalogs: Vec<RuntimeCatalog> = vec![];
+    for catalog_content in BUILTIN_CATALOGS {
+        match RuntimeCatalog::parse(catalog_content) {
+            Ok(c) => catalogs.push(c),
+            Err(e) => log::warn!("Invalid built-in runtime catalog: {}", e),
+        }
+    }
+
+    let runtimes_path = crate::db::Database::get_runtimes_db_path();
+    let catalog_paths = match crate::utils::get_all_paths(path::Path::new(&runtimes_path)) {
+        Ok(paths) => paths,
+        Err(_) => return catalogs,
+    };
+    for catalog_path in catalog_paths.iter() {
+        let catalog_path_str = catalog_path.to_str().unwrap();
+        if !catalog_path.is_file() || !(catalog_path_str.ends_with("yml") || catalog_path_str.ends_with("yaml")) {
+            continue;
+        }
+        let catalog_content = match fs::read_to_string(catalog_path) {
+            Ok(content) => content,
+            Err(e) => {
+                log::debug!("Could not read runtime catalog {}: {}.", &catalog_path_str, e);
+                continue;
+            }
+        };
+        let catalog = match RuntimeCatalog::parse(&catalog_content) {
+            Ok(c) => c,
+            Err(e) => {
+                log::warn!("Invalid runtime catalog at {}: {}", &catalog_path_str, e);
+                continue;
+            }
+        };
+        catalogs.retain(|c| c.id != catalog.id || c.version != catalog.version);
+        catalogs.push(catalog);
+    }
+    catalogs
+}
+
+// Gets the catalog for a version of a runtime. If that version is not known,
+// for example with the master branch, the newest known version is used instead.
+pub fn get_catalog<'a>(catalogs: &'a [RuntimeCatalog], runtime_id: &str, runtime_version: &str) -> Option<&'a RuntimeCatalog> {
+    let sdk_id = get_sdk_id(runtime_id);
+    let mut candidates: Vec<&RuntimeCatalog> = catalogs.iter().filter(|c| c.id == sdk_id).collect();
+    if let Some(catalog) = candidates.iter().find(|c| c.version == runtime_version) {
+        return Some(catalog);
+    }
+    candidates.sort_by(|a, b| Version::parse(&a.version).cmp(&Version::parse(&b.version)));
+    let catalog = candidates.pop()?;
+    log::warn!(
+        "No catalog for version {} of {}, using version {} instead.",
+        runtime_version,
+        sdk_id,
+        catalog.version
+    );
+    Some(catalog)
+}
+
+// Gets all the libraries provided by a runtime, including the ones
+// provided by the sdks it is based on.
+pub fn get_runtime_libraries(catalogs: &[RuntimeCatalog], runtime_id: &str, runtime_version: &str) -> Vec<RuntimeLibrary> {
+    let mut libraries: Vec<RuntimeLibrary> = vec![];
+    let mut catalog = get_catalog(catalogs, runtime_id, runtime_version);
+    // Protects against catalogs declaring themselves as their base.
+    let mut visited_ids: Vec<String> = vec![];
+    while let Some(c) = catalog {
+        if visited_ids.contains(&c.id) {
+            break;
+        }
+        visited_ids.push(c.id.to_string());
+        libraries.extend(c.libraries.iter().cloned());
+        catalog = match (&c.base_id, &c.base_version) {
+            (Some(base_id), Some(base_version)) => get_catalog(catalogs, base_id, base_version),
+            (Some(base_id), None) => get_catalog(catalogs, base_id, ""),
+            _ => None,
+        };
+    }
+    libraries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_builtin_catalogs() -> Vec<RuntimeCatalog> {
+        BUILTIN_CATALOGS.iter().map(|c| RuntimeCatalog::parse(c).unwrap()).collect()
+    }
+
+    #[test]
+    pub fn test_builtin_catalogs() {
+        let catalogs = get_builtin_catalogs();
+        assert_eq!(catalogs.len(), BUILTIN_CATALOGS.len());
+        for catalog in &catalogs {
+            assert!(!catalog.id.is_empty());
+            assert!(!catalog.version.is_empty());
+            assert!(!catalog.libraries.is_empty());
+        }
+    }
+
+    #[test]
+    pub fn test_get_catalog() {
+        let catalogs = get_builtin_catalogs();
+        assert_eq!(get_catalog(&catalogs, "org.gnome.Sdk", "40").unwrap().version, "40");
+        assert_eq!(get_catalog(&catalogs, "org.gnome.Platform", "40").unwrap().id, "org.gnome.Sdk");
+        assert_eq!(get_catalog(&catalogs, "org.freedesktop.Sdk", "master").unwrap().version, "21.08");
+        assert!(get_catalog(&catalogs, "org.unknown.Sdk", "1.0").is_none());
+    }
+
+    #[test]
+    pub fn test_get_runtime_libraries() {
+        let catalogs = get_builtin_catalogs();
+        let libraries = get_runtime_libraries(&catalogs, "org.gnome.Sdk", "40");
+        // gtk comes from the gnome sdk, and glib from the freedesktop sdk.
+        assert!(libraries.iter().any(|l| l.matches("gtk")));
+        let glib = libraries.iter().find(|l| l.matches("gio-2.0")).unwrap();
+        assert_eq!(glib.version, "2.64.6");
+
+        let libraries = get_runtime_libraries(&catalogs, "org.freedesktop.Sdk", "21.08");
+        assert!(!libraries.iter().any(|l| l.matches("gtk")));
+        assert!(libraries.iter().any(|l| l.matches("GLib")));
+    }
+}
@@ -2,7 +2,6 @@ use std::env;
 use std::fs::{self, DirEntry};
 use std::io::{stdin, stdout, Write};
 use std::path::Path;
-use std::process::{Command, Output, Stdio};
 use std::time::SystemTime;
 
 // Gets the path the repos should be located at.
@@ -19,85 +18,45 @@ pub fn clone_git_repo(repo_url: &str) -> Result<String, String> {
     let repos_dir = get_repos_dir_path();
     let repo_dir = format!("{}/{}", repos_dir, project_id);
     if Path::new(&repo_dir).is_dir() {
+        println!("Updating repo {}", repo_url);
+        // The existing clone is still usable when the remote cannot be reached.
+        if let Err(e) = crate::git::update_repo(Path::new(&repo_dir)) {
+            log::warn!("Could not update repo {}, using the existing clone: {}", repo_url, e);
+        }
         return Ok(repo_dir);
     }
-    if let Err(e) = fs::create_dir(&repo_dir) {
-        return Err(e.to_string());
-    }
 
     println!("Cloning repo {}", repo_url);
-    let mut output = Command::new("git")
-        .arg("clone")
-        .arg(repo_url)
-        .arg(&repo_dir)
-        .stdout(Stdio::piped())
-        .spawn()
-        .unwrap();
-
-    let mut output = match output.wait_with_output() {
-        Ok(o) => o,
-        Err(e) => return Err(e.to_string()),
-    };
-    if !output.status.success() {
-        return Err("Could not clone repo.".to_string());
+    if let Err(e) = crate::git::clone_repo(repo_url, Path::new(&repo_dir), &crate::git::CloneOptions::default()) {
+        fs::remove_dir_all(&repo_dir).unwrap_or(());
+        return Err(e);
     }
 
     Ok(repo_dir)
 }
 
+// Gets the file at the url, using the source cache.
 pub fn fetch_file(file_url: String) -> Result<String, String> {
-    let file_name_parts = file_url.split("/");
-    let file_name = file_name_parts.last().unwrap();
-
-    println!("Getting file at {}", file_url);
-    let mut output = Command::new("wget")
-        .arg(file_url.to_string())
-        .arg("-P /tmp/")
-        .stdout(Stdio::piped())
-        .spawn()
-        .unwrap();
-
-    let local_file_path = "/tmp/".to_owned() + &file_name.to_owned();
-
-    let mut output = match output.wait_with_output() {
-        Ok(o) => o,
-        Err(e) => return Err(e.to_string()),
-    };
-    if !output.status.success() {
-        return Err("Could not fetch file.".to_string());
-    }
-
-    Ok(local_file_path)
+    let local_file_path = crate::cache::fetch_file(&crate::cache::get_cache_dir_path(), &file_url, None, false)?;
+    Ok(local_file_path.to_str().unwrap().to_string())
 }
 
 pub fn get_git_repo_root_hashes(repo_path: &str) -> Result<Vec<String>, String> {
-    // FIXME there can actually be more than 1 parentless commit
-    // in a git repo, in the case of a merger. A parentless commit
-    // can also be found in multiple projects in the case of a fork.
+    // A parentless commit can be found in multiple projects in the case of a fork.
     println!("Getting initial commit for repo at {}", repo_path);
+    crate::git::get_root_hashes(Path::new(repo_path))
+}
 
-    let mut output = Command::new("git")
-        .arg(format!("--git-dir={}/.git", repo_path).to_owned())
-        .arg("rev-list")
-        .arg("--max-parents=0".to_owned())
-        .arg("HEAD")
-        .stdout(Stdio::piped())
-        .spawn()
-        .unwrap();
-
-    let mut output = match output.wait_with_output() {
-        Ok(o) => o,
-        Err(e) => return Err(e.to_string()),
-    };
-    if !output.status.success() {
-        return Err("Could not get root hashes.".to_string());
-    }
-    let all_hashes = match std::str::from_utf8(&output.stdout) {
-        Ok(v) => v,
-        Err(e) => panic!("Invalid UTF-8 sequence: {}", e),
-    };
+// Gets the tags of a git repo, with the hash of the commit
+// they point to.
+pub fn get_git_repo_tags(repo_path: &str) -> Result<Vec<(String, String)>, String> {
+    crate::git::get_tags(Path::new(repo_path))
+}
 
-    Ok(all_hashes.split('\n').map(|s| s.trim().to_string()).filter(|s| s.len() != 0).collect())
+// Gets the tags of a remote git repo, with the hash of the commit
+// they point to, without cloning the repo.
+pub fn get_remote_git_repo_tags(repo_url: &str) -> Result<Vec<(String, String)>, String> {
+    crate::git::get_remote_tags(repo_url)
 }
 
 pub fn get_all_paths(dir: &Path) -> Result<Vec<std::path::PathBuf>, String> {
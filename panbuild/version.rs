@@ -1,8 +1,12 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 
 // See https://github.com/rust-lang/rfcs/blob/master/text/1105-api-evolution.md
 // and https://doc.rust-lang.org/cargo/reference/manifest.html#the-version-field
-#[derive(Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct SemanticVersion {
     pub major: i32,
     pub minor: i32,
@@ -20,12 +24,13 @@ impl SemanticVersion {
         let mut pre_release = "".to_string();
         let mut build = "".to_string();
 
-        let parts: Vec<&str> = version.split('+').collect();
+        // The pre-release and build identifiers can contain hyphens.
+        let parts: Vec<&str> = version.splitn(2, '+').collect();
         if parts.len() == 2 {
             build = parts[1].to_string();
         }
 
-        let parts: Vec<&str> = parts[0].split('-').collect();
+        let parts: Vec<&str> = parts[0].splitn(2, '-').collect();
         if parts.len() == 2 {
             pre_release = parts[1].to_string();
         }
@@ -33,28 +38,28 @@ impl SemanticVersion {
         let version_core = parts[0].trim().to_string();
         let version_parts: Vec<&str> = version_core.split('.').collect();
         if version_parts.len() != 3 {
-            eprintln!("Invalid semantic version {}.", version_core);
+            log::debug!("Invalid semantic version {}.", version_core);
             return None;
         }
 
         let major: i32 = match version_parts[0].parse() {
             Ok(v) => v,
             Err(e) => {
-                eprintln!("Invalid major version {}", version_parts[0]);
+                log::debug!("Invalid major version {}", version_parts[0]);
                 return None;
             }
         };
         let minor: i32 = match version_parts[1].parse() {
             Ok(v) => v,
             Err(e) => {
-                eprintln!("Invalid minor version {}", version_parts[1]);
+                log::debug!("Invalid minor version {}", version_parts[1]);
                 return None;
             }
         };
         let patch: i32 = match version_parts[2].parse() {
             Ok(v) => v,
             Err(e) => {
-                eprintln!("Invalid patch version {}", version_parts[2]);
+                log::debug!("Invalid patch version {}", version_parts[2]);
                 return None;
             }
         };
@@ -67,6 +72,669 @@ impl SemanticVersion {
             build: build,
         })
     }
+
+    // Parses a version from a git tag name. Projects use a lot of different
+    // conventions for their tags, for example `v1.2.3`, `gtk-3.24.1` or `release-1_2_3`.
+    // Versions with only a major and a minor part are accepted, in which case
+    // the patch version is 0.
+    pub fn from_tag_name(tag_name: &str) -> Option<SemanticVersion> {
+        let version_start = match tag_name.find(|c: char| c.is_ascii_digit()) {
+            Some(i) => i,
+            None => return None,
+        };
+        // The prefix has to be separated from the version, unless it's the
+        // common `v` prefix.
+        let prefix = &tag_name[..version_start];
+        if !prefix.is_empty() && prefix != "v" && prefix != "V" && !prefix.ends_with(|c: char| c == '-' || c == '_' || c == '/') {
+            return None;
+        }
+        let mut version = tag_name[version_start..].replace("_", ".");
+
+        let version_core_end = version.find(['-', '+']).unwrap_or(version.len());
+        if version[..version_core_end].split('.').count() == 2 {
+            version.insert_str(version_core_end, ".0");
+        }
+
+        SemanticVersion::parse(&version)
+    }
+}
+
+impl SemanticVersion {
+    pub fn new(major: i32, minor: i32, patch: i32) -> SemanticVersion {
+        SemanticVersion {
+            major: major,
+            minor: minor,
+            patch: patch,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_pre_release(&self) -> bool {
+        !self.pre_release.is_empty()
+    }
+
+    // Compares the pre-release identifiers of 2 versions, using the rules
+    // defined in https://semver.org/#spec-item-11
+    fn cmp_pre_release(&self, other: &SemanticVersion) -> Ordering {
+        // A version without a pre-release has a higher precedence.
+        if self.pre_release.is_empty() && other.pre_release.is_empty() {
+            return Ordering::Equal;
+        }
+        if self.pre_release.is_empty() {
+            return Ordering::Greater;
+        }
+        if other.pre_release.is_empty() {
+            return Ordering::Less;
+        }
+
+        let mut identifiers = self.pre_release.split('.');
+        let mut other_identifiers = other.pre_release.split('.');
+        loop {
+            let (identifier, other_identifier) = match (identifiers.next(), other_identifiers.next()) {
+                (None, None) => return Ordering::Equal,
+                // A larger set of pre-release fields has a higher precedence.
+                (None, Some(_)) => return Ordering::Less,
+                (Some(_), None) => return Ordering::Greater,
+                (Some(i), Some(o)) => (i, o),
+            };
+            let ordering = match (identifier.parse::<u64>(), other_identifier.parse::<u64>()) {
+                (Ok(i), Ok(o)) => i.cmp(&o),
+                // Numeric identifiers always have lower precedence than alphanumeric identifiers.
+                (Ok(_), Err(_)) => Ordering::Less,
+                (Err(_), Ok(_)) => Ordering::Greater,
+                (Err(_), Err(_)) => identifier.cmp(other_identifier),
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+    }
+}
+
+// The build metadata is ignored when determining the precedence.
+impl Ord for SemanticVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.major
+            .cmp(&other.major)
+            .then(self.minor.cmp(&other.minor))
+            .then(self.patch.cmp(&other.patch))
+            .then(self.cmp_pre_release(other))
+    }
+}
+
+impl PartialOrd for SemanticVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for SemanticVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for SemanticVersion {}
+
+impl fmt::Display for SemanticVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if !self.pre_release.is_empty() {
+            write!(f, "-{}", self.pre_release)?;
+        }
+        if !self.build.is_empty() {
+            write!(f, "+{}", self.build)?;
+        }
+        Ok(())
+    }
+}
+
+// A range of versions, as found in the dependency declarations of the
+// various build systems. Supported syntaxes are
+//   * the cargo and npm ones: `^1.2`, `~1.2`, `1.2.*`, `>=1.0, <2.0`;
+//   * the debian ones: `(>= 1.2)`, `(<< 2.0)`, `(= 1.2.3)`.
+// A range without any bound matches all the versions.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default)]
+pub struct VersionRange {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_version: Option<SemanticVersion>,
+    pub min_inclusive: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_version: Option<SemanticVersion>,
+    pub max_inclusive: bool,
+}
+impl VersionRange {
+    pub fn exact(version: &SemanticVersion) -> VersionRange {
+        VersionRange {
+            min_version: Some(version.clone()),
+            min_inclusive: true,
+            max_version: Some(version.clone()),
+            max_inclusive: true,
+        }
+    }
+
+    pub fn parse(range: &str) -> Option<VersionRange> {
+        let mut version_range = VersionRange::default();
+        let range = range.trim().trim_start_matches('(').trim_end_matches(')');
+        for comparator in range.split(',') {
+            let comparator = comparator.trim();
+            if comparator.is_empty() || comparator == "*" {
+                continue;
+            }
+            let other_range = match VersionRange::parse_comparator(comparator) {
+                Some(r) => r,
+                None => {
+                    log::debug!("Invalid version comparator {}.", comparator);
+                    return None;
+                }
+            };
+            version_range.intersect(&other_range);
+        }
+        Some(version_range)
+    }
+
+    fn parse_comparator(comparator: &str) -> Option<VersionRange> {
+        let operator_end = comparator.find(|c: char| c.is_ascii_digit()).unwrap_or(comparator.len());
+        let operator = comparator[..operator_end].trim();
+        let (version, parts_count) = match parse_partial_version(&comparator[operator_end..]) {
+            Some(v) => v,
+            // Debian dependencies can use debian versions, like `(>= 1:2.30-1)`.
+            None => (Version::parse(&comparator[operator_end..])?.to_semantic_version()?, 3),
+        };
+
+        let mut range = VersionRange::default();
+        match operator {
+            ">=" => range.set_min(version, true),
+            ">" | ">>" => range.set_min(version, false),
+            "<=" => range.set_max(version, true),
+            "<" | "<<" => range.set_max(version, false),
+            "^" => {
+                let max_version = if version.major != 0 || parts_count == 1 {
+                    SemanticVersion::new(version.major + 1, 0, 0)
+                } else if version.minor != 0 || parts_count == 2 {
+                    SemanticVersion::new(0, version.minor + 1, 0)
+                } else {
+                    SemanticVersion::new(0, 0, version.patch + 1)
+                };
+                range.set_min(version, true);
+                range.set_max(max_version, false);
+            }
+            "~" | "" | "=" | "==" => {
+                if parts_count == 3 && operator != "~" {
+                    return Some(VersionRange::exact(&version));
+                }
+                let max_version = if parts_count == 1 {
+                    SemanticVersion::new(version.major + 1, 0, 0)
+                } else {
+                    SemanticVersion::new(version.major, version.minor + 1, 0)
+                };
+                range.set_min(version, true);
+                range.set_max(max_version, false);
+            }
+            _ => return None,
+        }
+        Some(range)
+    }
+
+    fn set_min(&mut self, version: SemanticVersion, inclusive: bool) {
+        self.min_version = Some(version);
+        self.min_inclusive = inclusive;
+    }
+
+    fn set_max(&mut self, version: SemanticVersion, inclusive: bool) {
+        self.max_version = Some(version);
+        self.max_inclusive = inclusive;
+    }
+
+    // Restricts the range to the versions also matched by the other range.
+    pub fn intersect(&mut self, other: &VersionRange) {
+        if let Some(other_min) = &other.min_version {
+            let replace = match &self.min_version {
+                None => true,
+                Some(min) => other_min > min || (other_min == min && !other.min_inclusive),
+            };
+            if replace {
+                self.set_min(other_min.clone(), other.min_inclusive);
+            }
+        }
+        if let Some(other_max) = &other.max_version {
+            let replace = match &self.max_version {
+                None => true,
+                Some(max) => other_max < max || (other_max == max && !other.max_inclusive),
+            };
+            if replace {
+                self.set_max(other_max.clone(), other.max_inclusive);
+            }
+        }
+    }
+
+    pub fn matches(&self, version: &SemanticVersion) -> bool {
+        if let Some(min_version) = &self.min_version {
+            if version < min_version || (version == min_version && !self.min_inclusive) {
+                return false;
+            }
+        }
+        if let Some(max_version) = &self.max_version {
+            if version > max_version || (version == max_version && !self.max_inclusive) {
+                return false;
+            }
+        }
+        // Like cargo and npm, we only match pre-releases when they
+        // are explicitely requested.
+        if version.is_pre_release() {
+            let same_core = |v: &SemanticVersion| v.major == version.major && v.minor == version.minor && v.patch == version.patch;
+            let min_requested = self.min_version.as_ref().map_or(false, |v| v.is_pre_release() && same_core(v));
+            let max_requested = self.max_version.as_ref().map_or(false, |v| v.is_pre_release() && same_core(v));
+            if !min_requested && !max_requested {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+impl fmt::Display for VersionRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.min_version.is_some() && self.min_version == self.max_version && self.min_inclusive && self.max_inclusive {
+            return write!(f, "={}", self.min_version.as_ref().unwrap());
+        }
+        let mut comparators: Vec<String> = vec![];
+        if let Some(min_version) = &self.min_version {
+            comparators.push(format!("{}{}", if self.min_inclusive { ">=" } else { ">" }, min_version));
+        }
+        if let Some(max_version) = &self.max_version {
+            comparators.push(format!("{}{}", if self.max_inclusive { "<=" } else { "<" }, max_version));
+        }
+        if comparators.is_empty() {
+            return write!(f, "*");
+        }
+        write!(f, "{}", comparators.join(", "))
+    }
+}
+
+// Parses a version for which the minor and patch parts are optional,
+// like `1.2` or `1.2.*`. Returns the version with the number of parts that were
+// specified.
+fn parse_partial_version(version: &str) -> Option<(SemanticVersion, usize)> {
+    let version = version.trim().trim_end_matches(".*").trim_end_matches(".x");
+    let version_core_end = version.find(|c: char| c == '-' || c == '+').unwrap_or(version.len());
+    let parts_count = version[..version_core_end].split('.').count();
+
+    let mut full_version = version.to_string();
+    for _ in parts_count..3 {
+        full_version.insert_str(version_core_end, ".0");
+    }
+    let semantic_version = SemanticVersion::parse(&full_version)?;
+    Some((semantic_version, parts_count))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum VersionScheme {
+    Semantic,
+    Debian,
+    Loose,
+}
+
+// A version following any of the version schemes we support.
+// Use Version::parse to detect the version scheme automatically.
+#[derive(Debug, Clone)]
+pub enum Version {
+    Semantic(SemanticVersion),
+    Debian(DebianVersion),
+    Loose(LooseVersion),
+}
+impl Version {
+    pub fn parse(version: &str) -> Option<Version> {
+        let version = version.trim();
+        if !version.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        // Those are only valid in debian versions.
+        if version.contains(':') || version.contains('~') {
+            return Version::parse_with_scheme(version, VersionScheme::Debian);
+        }
+        // Something like `1.2.3-1` is both a valid semantic version and a valid debian
+        // version. Semantic pre-releases rarely start with a digit though, while
+        // debian revisions always do.
+        if let Some(i) = version.find('-') {
+            if version[i + 1..].starts_with(|c: char| c.is_ascii_digit()) {
+                return Version::parse_with_scheme(version, VersionScheme::Debian);
+            }
+        }
+        if let Some(v) = Version::parse_with_scheme(version, VersionScheme::Semantic) {
+            return Some(v);
+        }
+        if let Some(v) = Version::parse_with_scheme(version, VersionScheme::Loose) {
+            return Some(v);
+        }
+        // The debian comparison rules can order pretty much any version string.
+        Version::parse_with_scheme(version, VersionScheme::Debian)
+    }
+
+    pub fn parse_with_scheme(version: &str, scheme: VersionScheme) -> Option<Version> {
+        match scheme {
+            VersionScheme::Semantic => SemanticVersion::parse(&version.to_string()).map(Version::Semantic),
+            VersionScheme::Debian => DebianVersion::parse(version).map(Version::Debian),
+            VersionScheme::Loose => LooseVersion::parse(version).map(Version::Loose),
+        }
+    }
+
+    pub fn get_scheme(&self) -> VersionScheme {
+        match self {
+            Version::Semantic(_) => VersionScheme::Semantic,
+            Version::Debian(_) => VersionScheme::Debian,
+            Version::Loose(_) => VersionScheme::Loose,
+        }
+    }
+
+    // Gets the closest semantic version, which can be used to
+    // match a version range.
+    pub fn to_semantic_version(&self) -> Option<SemanticVersion> {
+        match self {
+            Version::Semantic(v) => Some(v.clone()),
+            Version::Loose(v) => v.to_semantic_version(),
+            Version::Debian(v) => match LooseVersion::parse(&v.upstream_version) {
+                Some(l) => l.to_semantic_version(),
+                None => SemanticVersion::parse(&v.upstream_version),
+            },
+        }
+    }
+
+    // Gets the debian version used to compare this version with the versions of any scheme.
+    // The semantic pre-releases are mapped to a tilde, which sorts them before the release
+    // like in `1.0.0~rc1`, and the trailing zero parts are ignored, so that `4.4` and `4.4.0` are equal.
+    fn get_comparison_key(&self) -> DebianVersion {
+        match self {
+            Version::Semantic(v) => {
+                let mut upstream_version = strip_trailing_zeros(&format!("{}.{}.{}", v.major, v.minor, v.patch));
+                if v.is_pre_release() {
+                    upstream_version = format!("{}~{}", upstream_version, v.pre_release);
+                }
+                DebianVersion {
+                    upstream_version,
+                    ..Default::default()
+                }
+            }
+            Version::Debian(v) => DebianVersion {
+                epoch: v.epoch,
+                upstream_version: strip_trailing_zeros(&v.upstream_version),
+                debian_revision: v.debian_revision.to_string(),
+            },
+            Version::Loose(v) => DebianVersion {
+                upstream_version: strip_trailing_zeros(&v.to_string()),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+// Strips the zero parts at the end of the numeric part of a version, keeping the pre-release
+// part starting with a tilde. `1.0.0~rc1` becomes `1~rc1`, but `1.0a` is left unchanged.
+fn strip_trailing_zeros(version: &str) -> String {
+    let (numeric_part, pre_release) = version.split_at(version.find('~').unwrap_or(version.len()));
+    if !numeric_part.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return version.to_string();
+    }
+    let mut parts: Vec<&str> = numeric_part.split('.').collect();
+    while parts.len() > 1 && parts.last().map(|p| !p.is_empty() && p.chars().all(|c| c == '0')).unwrap_or(false) {
+        parts.pop();
+    }
+    format!("{}{}", parts.join("."), pre_release)
+}
+
+// The versions of all the schemes are compared using the debian rules, on the key
+// returned by get_comparison_key, so that the ordering stays consistent across schemes.
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.get_comparison_key().cmp(&other.get_comparison_key())
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Version::Semantic(v) => v.fmt(f),
+            Version::Debian(v) => v.fmt(f),
+            Version::Loose(v) => v.fmt(f),
+        }
+    }
+}
+
+// See https://www.debian.org/doc/debian-policy/ch-controlfields.html#version
+// The format is [epoch:]upstream_version[-debian_revision]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct DebianVersion {
+    pub epoch: u32,
+    pub upstream_version: String,
+    pub debian_revision: String,
+}
+impl DebianVersion {
+    pub fn parse(version: &str) -> Option<DebianVersion> {
+        let mut debian_version = DebianVersion::default();
+        let mut version = version.trim();
+
+        if let Some(i) = version.find(':') {
+            debian_version.epoch = match version[..i].parse() {
+                Ok(e) => e,
+                Err(_) => {
+                    log::debug!("Invalid epoch in debian version {}.", version);
+                    return None;
+                }
+            };
+            version = &version[i + 1..];
+        }
+        // The upstream version can contain hyphens, but only if there is a revision.
+        if let Some(i) = version.rfind('-') {
+            debian_version.debian_revision = version[i + 1..].to_string();
+            version = &version[..i];
+        }
+        if version.is_empty() || debian_version.debian_revision.contains(':') {
+            log::debug!("Invalid debian version {}.", version);
+            return None;
+        }
+        for c in version.chars() {
+            if !c.is_ascii_alphanumeric() && !".+-~:".contains(c) {
+                log::debug!("Invalid character {} in debian version {}.", c, version);
+                return None;
+            }
+        }
+        debian_version.upstream_version = version.to_string();
+        Some(debian_version)
+    }
+}
+
+impl Ord for DebianVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.epoch
+            .cmp(&other.epoch)
+            .then(compare_debian_versions(&self.upstream_version, &other.upstream_version))
+            .then(compare_debian_versions(&self.debian_revision, &other.debian_revision))
+    }
+}
+
+impl PartialOrd for DebianVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for DebianVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for DebianVersion {}
+
+impl fmt::Display for DebianVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.epoch != 0 {
+            write!(f, "{}:", self.epoch)?;
+        }
+        write!(f, "{}", self.upstream_version)?;
+        if !self.debian_revision.is_empty() {
+            write!(f, "-{}", self.debian_revision)?;
+        }
+        Ok(())
+    }
+}
+
+// Gets the weight of a character when comparing the non-digit parts of a debian version.
+// Letters sort before non-letters, and the tilde sorts before anything, even the end of the part.
+fn get_debian_char_order(c: Option<u8>) -> i32 {
+    match c {
+        None => 0,
+        Some(b'~') => -1,
+        Some(c) if c.is_ascii_digit() => 0,
+        Some(c) if c.is_ascii_alphabetic() => c as i32,
+        Some(c) => c as i32 + 256,
+    }
+}
+
+// Compares 2 version strings using the algorithm from dpkg (verrevcmp).
+fn compare_debian_versions(version: &str, other_version: &str) -> Ordering {
+    let a = version.as_bytes();
+    let b = other_version.as_bytes();
+    let (mut i, mut j) = (0, 0);
+
+    while i < a.len() || j < b.len() {
+        // Compare the non-digit parts.
+        while (i < a.len() && !a[i].is_ascii_digit()) || (j < b.len() && !b[j].is_ascii_digit()) {
+            let a_order = get_debian_char_order(a.get(i).cloned());
+            let b_order = get_debian_char_order(b.get(j).cloned());
+            if a_order != b_order {
+                return a_order.cmp(&b_order);
+            }
+            i += 1;
+            j += 1;
+        }
+
+        // Compare the digit parts, ignoring the leading zeros.
+        while i < a.len() && a[i] == b'0' {
+            i += 1;
+        }
+        while j < b.len() && b[j] == b'0' {
+            j += 1;
+        }
+        let mut first_diff = Ordering::Equal;
+        while i < a.len() && a[i].is_ascii_digit() && j < b.len() && b[j].is_ascii_digit() {
+            if first_diff == Ordering::Equal {
+                first_diff = a[i].cmp(&b[j]);
+            }
+            i += 1;
+            j += 1;
+        }
+        if i < a.len() && a[i].is_ascii_digit() {
+            return Ordering::Greater;
+        }
+        if j < b.len() && b[j].is_ascii_digit() {
+            return Ordering::Less;
+        }
+        if first_diff != Ordering::Equal {
+            return first_diff;
+        }
+    }
+    Ordering::Equal
+}
+
+// A version made of any number of numeric parts separated by dots, like
+// `4.4` or `2021.03`. Missing parts are considered to be 0 when comparing.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct LooseVersion {
+    pub parts: Vec<u64>,
+}
+impl LooseVersion {
+    pub fn parse(version: &str) -> Option<LooseVersion> {
+        let mut parts: Vec<u64> = vec![];
+        for part in version.trim().split('.') {
+            match part.parse() {
+                Ok(p) => parts.push(p),
+                Err(_) => {
+                    log::debug!("Invalid part {} in loose version {}.", part, version);
+                    return None;
+                }
+            };
+        }
+        Some(LooseVersion { parts: parts })
+    }
+
+    fn get_part(&self, index: usize) -> u64 {
+        *self.parts.get(index).unwrap_or(&0)
+    }
+
+    // Gets the semantic version made of the first 3 parts, if they are small enough.
+    pub fn to_semantic_version(&self) -> Option<SemanticVersion> {
+        let mut parts: Vec<i32> = vec![];
+        for index in 0..3 {
+            match i32::try_from(self.get_part(index)) {
+                Ok(p) => parts.push(p),
+                Err(_) => {
+                    log::debug!("Part {} of loose version {} is too large for a semantic version.", index, self);
+                    return None;
+                }
+            }
+        }
+        Some(SemanticVersion::new(parts[0], parts[1], parts[2]))
+    }
+}
+
+impl Ord for LooseVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        for i in 0..std::cmp::max(self.parts.len(), other.parts.len()) {
+            let ordering = self.get_part(i).cmp(&other.get_part(i));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+impl PartialOrd for LooseVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for LooseVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for LooseVersion {}
+
+impl fmt::Display for LooseVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parts: Vec<String> = self.parts.iter().map(|p| p.to_string()).collect();
+        write!(f, "{}", parts.join("."))
+    }
+}
+
+// Sorts version strings from the oldest to the newest. The strings that
+// cannot be parsed as a version are moved at the beginning.
+pub fn sort_versions(versions: &mut Vec<String>) {
+    versions.sort_by(|a, b| match (Version::parse(a), Version::parse(b)) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Greater,
+        (None, Some(_)) => Ordering::Less,
+        (None, None) => a.cmp(b),
+    });
 }
 
 #[cfg(test)]
@@ -113,6 +781,19 @@ mod tests {
         assert_eq!(sem_ver.build, "build".to_string());
     }
 
+    #[test]
+    pub fn test_from_tag_name() {
+        assert_eq!(SemanticVersion::from_tag_name("1.2.3").unwrap().to_string(), "1.2.3");
+        assert_eq!(SemanticVersion::from_tag_name("v1.2.3").unwrap().to_string(), "1.2.3");
+        assert_eq!(SemanticVersion::from_tag_name("release-1_2_3").unwrap().to_string(), "1.2.3");
+        assert_eq!(SemanticVersion::from_tag_name("REL_1_2_3").unwrap().to_string(), "1.2.3");
+        assert_eq!(SemanticVersion::from_tag_name("gtk-3.24").unwrap().to_string(), "3.24.0");
+        assert_eq!(SemanticVersion::from_tag_name("v2.0.0-rc1").unwrap().to_string(), "2.0.0-rc1");
+        assert!(SemanticVersion::from_tag_name("nightly").is_none());
+        assert!(SemanticVersion::from_tag_name("foo1.2.3").is_none());
+        assert!(SemanticVersion::from_tag_name("2021").is_none());
+    }
+
     #[test]
     pub fn test_parse_version_with_build_and_release() {
         let mut sem_ver = SemanticVersion::parse(&"1.2.3-alpha+build".to_string());
@@ -124,4 +805,200 @@ mod tests {
         assert_eq!(sem_ver.build, "build".to_string());
         assert_eq!(sem_ver.pre_release, "alpha".to_string());
     }
+
+    #[test]
+    pub fn test_precedence() {
+        let versions = vec![
+            "1.0.0-alpha",
+            "1.0.0-alpha.1",
+            "1.0.0-alpha.beta",
+            "1.0.0-beta",
+            "1.0.0-beta.2",
+            "1.0.0-beta.11",
+            "1.0.0-rc.1",
+            "1.0.0",
+            "1.0.1",
+            "1.2.0",
+            "1.10.0",
+            "2.0.0",
+        ];
+        for i in 1..versions.len() {
+            let lower = SemanticVersion::parse(&versions[i - 1].to_string()).unwrap();
+            let higher = SemanticVersion::parse(&versions[i].to_string()).unwrap();
+            assert!(lower < higher, "{} should be lower than {}", versions[i - 1], versions[i]);
+        }
+        assert_eq!(
+            SemanticVersion::parse(&"1.0.0+build1".to_string()).unwrap(),
+            SemanticVersion::parse(&"1.0.0+build2".to_string()).unwrap()
+        );
+    }
+
+    fn range_matches(range: &str, version: &str) -> bool {
+        VersionRange::parse(range)
+            .unwrap()
+            .matches(&SemanticVersion::parse(&version.to_string()).unwrap())
+    }
+
+    #[test]
+    pub fn test_range_caret() {
+        assert!(range_matches("^1.2", "1.2.0"));
+        assert!(range_matches("^1.2", "1.9.3"));
+        assert!(!range_matches("^1.2", "2.0.0"));
+        assert!(!range_matches("^1.2", "1.1.9"));
+        assert!(range_matches("^0.2.3", "0.2.9"));
+        assert!(!range_matches("^0.2.3", "0.3.0"));
+        assert!(!range_matches("^0.0.3", "0.0.4"));
+    }
+
+    #[test]
+    pub fn test_range_tilde() {
+        assert!(range_matches("~1.2", "1.2.5"));
+        assert!(!range_matches("~1.2", "1.3.0"));
+        assert!(range_matches("~1.2.3", "1.2.4"));
+        assert!(!range_matches("~1.2.3", "1.2.2"));
+    }
+
+    #[test]
+    pub fn test_range_comparators() {
+        assert!(range_matches(">=1.0, <2.0", "1.5.0"));
+        assert!(!range_matches(">=1.0, <2.0", "2.0.0"));
+        assert!(!range_matches(">=1.0, <2.0", "0.9.0"));
+        assert!(range_matches("1.2.*", "1.2.7"));
+        assert!(range_matches("1.2.3", "1.2.3"));
+        assert!(!range_matches("1.2.3", "1.2.4"));
+        assert!(range_matches("*", "0.0.1"));
+        assert!(range_matches("", "3.0.1"));
+        assert!(VersionRange::parse("!1.0").is_none());
+    }
+
+    #[test]
+    pub fn test_range_debian() {
+        assert!(range_matches("(>= 1.2)", "1.2.0"));
+        assert!(range_matches("(>= 1.2)", "3.0.0"));
+        assert!(!range_matches("(>> 1.2)", "1.2.0"));
+        assert!(!range_matches("(<< 2.0)", "2.0.0"));
+        assert!(range_matches("(= 1.2.3)", "1.2.3"));
+    }
+
+    #[test]
+    pub fn test_range_pre_release() {
+        assert!(!range_matches(">=1.0", "2.0.0-alpha"));
+        assert!(range_matches(">=2.0.0-alpha", "2.0.0-beta"));
+    }
+
+    #[test]
+    pub fn test_detect_version_scheme() {
+        assert_eq!(Version::parse("1.2.3").unwrap().get_scheme(), VersionScheme::Semantic);
+        assert_eq!(Version::parse("1.2.3-alpha").unwrap().get_scheme(), VersionScheme::Semantic);
+        assert_eq!(Version::parse("1:2.30-1ubuntu2").unwrap().get_scheme(), VersionScheme::Debian);
+        assert_eq!(Version::parse("1.2.3-1").unwrap().get_scheme(), VersionScheme::Debian);
+        assert_eq!(Version::parse("2.0~rc1").unwrap().get_scheme(), VersionScheme::Debian);
+        assert_eq!(Version::parse("2021.03").unwrap().get_scheme(), VersionScheme::Loose);
+        assert_eq!(Version::parse("4.4").unwrap().get_scheme(), VersionScheme::Loose);
+        assert_eq!(Version::parse("1.2.3.4").unwrap().get_scheme(), VersionScheme::Loose);
+        assert_eq!(Version::parse("1.2a").unwrap().get_scheme(), VersionScheme::Debian);
+        assert!(Version::parse("nightly").is_none());
+        assert!(Version::parse("").is_none());
+    }
+
+    #[test]
+    pub fn test_debian_version_parse() {
+        let version = DebianVersion::parse("1:2.30-1ubuntu2").unwrap();
+        assert_eq!(version.epoch, 1);
+        assert_eq!(version.upstream_version, "2.30");
+        assert_eq!(version.debian_revision, "1ubuntu2");
+        let version = DebianVersion::parse("2.30-rc1-3").unwrap();
+        assert_eq!(version.upstream_version, "2.30-rc1");
+        assert_eq!(version.debian_revision, "3");
+        assert_eq!(version.to_string(), "2.30-rc1-3");
+        assert!(DebianVersion::parse("a:1.0").is_none());
+    }
+
+    #[test]
+    pub fn test_debian_version_ordering() {
+        let versions = vec!["1.0~rc1", "1.0", "1.0-1", "1.0-1ubuntu1", "1.0-2", "1.0a", "1.0.1", "1.10", "0:2.0", "1:0.1"];
+        for i in 1..versions.len() {
+            let lower = DebianVersion::parse(versions[i - 1]).unwrap();
+            let higher = DebianVersion::parse(versions[i]).unwrap();
+            assert!(lower < higher, "{} should be lower than {}", versions[i - 1], versions[i]);
+        }
+        assert_eq!(DebianVersion::parse("1.01").unwrap(), DebianVersion::parse("1.1").unwrap());
+    }
+
+    #[test]
+    pub fn test_loose_version_ordering() {
+        assert!(LooseVersion::parse("2021.03").unwrap() < LooseVersion::parse("2021.10").unwrap());
+        assert!(LooseVersion::parse("4.4").unwrap() < LooseVersion::parse("4.4.20").unwrap());
+        assert_eq!(LooseVersion::parse("4.4").unwrap(), LooseVersion::parse("4.4.0").unwrap());
+        assert!(LooseVersion::parse("4.a").is_none());
+    }
+
+    #[test]
+    pub fn test_mixed_schemes_ordering() {
+        assert!(Version::parse("4.4").unwrap() < Version::parse("4.4.20").unwrap());
+        assert!(Version::parse("4.4.20").unwrap() < Version::parse("4.5").unwrap());
+        assert!(Version::parse("1.2.3").unwrap() < Version::parse("1:1.0").unwrap());
+    }
+
+    #[test]
+    pub fn test_mixed_schemes_transitivity() {
+        let versions: Vec<Version> = vec![
+            "1.0.0-rc1",
+            "1.0.0",
+            "1.0.0-1",
+            "1.0~rc1",
+            "1.0",
+            "1.0a",
+            "1:0.1",
+            "4.4",
+            "4.4.0",
+            "4.4.0.0",
+            "4.4.20",
+            "2021.03",
+            "2.30-rc1-3",
+        ]
+        .iter()
+        .map(|v| Version::parse(v).unwrap())
+        .collect();
+        for a in &versions {
+            for b in &versions {
+                assert_eq!(a.cmp(b), b.cmp(a).reverse(), "{} and {} should be ordered consistently", a, b);
+                for c in &versions {
+                    if a <= b && b <= c {
+                        assert!(a <= c, "{} <= {} <= {} should imply {} <= {}", a, b, c, a, c);
+                    }
+                }
+            }
+        }
+
+        let parse = |v: &str| Version::parse(v).unwrap();
+        assert!(parse("1.0.0-rc1") < parse("1.0.0"));
+        assert!(parse("1.0.0") < parse("1.0.0-1"));
+        assert!(parse("1.0.0-rc1") < parse("1.0.0-1"));
+        assert_eq!(parse("1.0.0-rc1"), parse("1.0.0~rc1"));
+        assert_eq!(parse("4.4"), parse("4.4.0"));
+        assert_eq!(parse("4.4.0"), parse("4.4.0.0"));
+    }
+
+    #[test]
+    pub fn test_loose_to_semantic_version() {
+        assert_eq!(
+            LooseVersion::parse("2021.03").unwrap().to_semantic_version().unwrap(),
+            SemanticVersion::new(2021, 3, 0)
+        );
+        assert!(LooseVersion::parse("20210301000000").unwrap().to_semantic_version().is_none());
+    }
+
+    #[test]
+    pub fn test_sort_versions() {
+        let mut versions: Vec<String> = ["2.30", "nightly", "1:1.0", "2.4.1", "2.4"].iter().map(|v| v.to_string()).collect();
+        sort_versions(&mut versions);
+        assert_eq!(versions, vec!["nightly", "2.4", "2.4.1", "2.30", "1:1.0"]);
+    }
+
+    #[test]
+    pub fn test_range_debian_versions() {
+        assert!(range_matches("(>= 1:2.30-1)", "2.31.0"));
+        assert!(!range_matches("(>= 1:2.30-1)", "2.29.0"));
+    }
 }
@@ -128,7 +128,10 @@ fn main() {
 
     if command_name == &"import-flathub-manifests".to_string() {
         let mut db = panbuild::db::Database::get_database();
-        let all_flathub_repos = pb_tools::hubs::github::get_org_repos("flathub");
+        // When rewinding, the revisions of the modules are also imported from
+        // all the previous versions of the manifests.
+        let rewind = args.contains(&"--rewind".to_string());
+        let all_flathub_repos = pb_tools::hubs::github::get_org_repos(pb_tools::flathub::FLATHUB_ORG);
         for flathub_repo in &all_flathub_repos {
             let repo_url = &flathub_repo.vcs_urls[0];
             let repo_dir = match panbuild::utils::clone_git_repo(&repo_url) {
@@ -138,7 +141,12 @@ fn main() {
                     continue;
                 },
             };
-            // TODO we should also rewind on all the commits of that repo?
+            if rewind {
+                match pb_tools::history::get_module_revisions(&repo_dir) {
+                    Ok(module_revisions) => db.add_module_revisions(module_revisions),
+                    Err(e) => log::error!("Could not rewind the history of {}: {}", repo_dir, e),
+                };
+            }
             let repo_file_paths = match panbuild::utils::get_all_paths(path::Path::new(&repo_dir)) {
                 Ok(paths) => paths,
                 Err(message) => {
@@ -148,13 +156,7 @@ fn main() {
             };
             for file_path in &repo_file_paths {
                 let file_path = file_path.to_str().unwrap();
-                if file_path.contains(".git/") {
-                    continue;
-                }
-
-                // We're a bit aggressive here, we could try parsing only the files
-                // that match exactly the flatpak path convention.
-                if !file_path.ends_with(".json") && !file_path.ends_with(".yaml") && !file_path.ends_with(".yml") {
+                if !pb_tools::history::is_manifest_path(file_path) {
                     continue;
                 }
                 let manifest_content = match fs::read_to_string(file_path) {
@@ -169,53 +171,129 @@ fn main() {
                     None => continue,
                 };
 
-                for module in flatpak_manifest.modules {
-                    db.add_module(module);
-                }
-
-                // TODO infer projects from the modules when possible.
+                pb_tools::flathub::import_manifest(&mut db, flatpak_manifest, &repo_url);
             }
 
         }
         println!("There are {} flathub repos.", all_flathub_repos.len());
     }
 
-    if command_name == &"import-projects-from-gitlabs".to_string() {
-        // There is a list of all the public GitLab instances hosted here
-        // https://wiki.p2pfoundation.net/List_of_Community-Hosted_GitLab_Instances
+    // Prints all the versions of the modules found in the history of the
+    // manifests of a repo, and adds them to the db.
+    if command_name == &"harvest-module-history".to_string() {
+        if args.len() < 3 {
+            eprintln!("Require a repo url.");
+            exit(1);
+        }
         let mut db = panbuild::db::Database::get_database();
-        pb_tools::hubs::gitlab::get_and_add_repos("gitlab.gnome.org", "PB_GNOME_GITLAB_TOKEN", &mut db);
-        pb_tools::hubs::gitlab::get_and_add_repos("source.puri.sm", "PB_PURISM_GITLAB_TOKEN", &mut db);
-        pb_tools::hubs::gitlab::get_and_add_repos("salsa.debian.org", "PB_DEBIAN_GITLAB_TOKEN", &mut db);
-        // KDE was recently migrated to GitLab.
-        // See https://gitlab.com/gitlab-org/gitlab-foss/-/issues/53206 for details.
-        pb_tools::hubs::gitlab::get_and_add_repos("invent.kde.org", "PB_KDE_GITLAB_TOKEN", &mut db);
-        pb_tools::hubs::gitlab::get_and_add_repos("code.videolan.org", "PB_VLC_GITLAB_TOKEN", &mut db);
-        pb_tools::hubs::gitlab::get_and_add_repos("gitlab.haskell.org", "PB_HASKELL_GITLAB_TOKEN", &mut db);
-        pb_tools::hubs::gitlab::get_and_add_repos("devel.trisquel.info", "PB_TRISQUEL_GITLAB_TOKEN", &mut db);
-        pb_tools::hubs::gitlab::get_and_add_repos("gitlab.freedesktop.org", "PB_XDG_GITLAB_TOKEN", &mut db);
+        let repo_url = &args[2];
+        let repo_dir = match panbuild::utils::clone_git_repo(&repo_url) {
+            Ok(d) => d,
+            Err(e) => {
+                eprintln!("Could not clone repo {}: {}", &repo_url, e);
+                exit(1);
+            },
+        };
+        let module_revisions = match pb_tools::history::get_module_revisions(&repo_dir) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("Could not rewind the history of {}: {}", &repo_url, e);
+                exit(1);
+            },
+        };
+        println!("{}", serde_yaml::to_string(&module_revisions).unwrap());
+        println!("Found {} module versions in {}.", module_revisions.len(), &repo_url);
+        db.add_module_revisions(module_revisions);
     }
 
-    if command_name == &"import-projects-from-gitlab-com".to_string() {
+    // Imports the projects from the hubs given with --hub, for example
+    // --hub gitlab:gitlab.gnome.org. Without any --hub, the hubs listed
+    // in the hubs configuration file are used. With --with-versions, the
+    // versions of the projects, like their releases, are also imported.
+    if command_name == &"import".to_string() {
+        let mut hub_specs: Vec<String> = vec![];
+        let mut with_versions = false;
+        let mut args_iter = args.iter().skip(2);
+        while let Some(arg) = args_iter.next() {
+            if arg == "--with-versions" {
+                with_versions = true;
+                continue;
+            }
+            if arg != "--hub" {
+                eprintln!("Unknown argument {}.", arg);
+                exit(1);
+            }
+            match args_iter.next() {
+                Some(hub_spec) => hub_specs.push(hub_spec.to_string()),
+                None => {
+                    eprintln!("--hub requires a value.");
+                    exit(1);
+                }
+            };
+        }
+
+        let hubs: Result<Vec<pb_tools::hubs::hub::Hub>, String> = match hub_specs.len() {
+            0 => pb_tools::hubs::hub::get_configured_hubs(),
+            _ => hub_specs.iter().map(|s| pb_tools::hubs::hub::Hub::from_spec(s)).collect(),
+        };
+        let hubs = match hubs {
+            Ok(h) => h,
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        };
+
         let mut db = panbuild::db::Database::get_database();
-        pb_tools::hubs::gitlab::get_and_add_repos("gitlab.com", "PB_GITLAB_TOKEN", &mut db);
+        for (hub_name, error) in pb_tools::hubs::hub::import_projects(hubs, &mut db, with_versions) {
+            eprintln!("Could not import all the projects from {}: {}", hub_name, error);
+            exit_code = 1;
+        }
     }
 
-    if command_name == &"import-projects-from-github-com".to_string() {
+    // Imports the source packages of a Debian suite, or of a local Sources index,
+    // with their versions and build dependencies.
+    if command_name == &"import-debian-sources".to_string() {
+        let hub = match args.get(2) {
+            Some(a) if a.starts_with('/') || a.starts_with('.') => pb_tools::hubs::deb::DebianPackagesHub::from_sources_path(a),
+            Some(suite) => pb_tools::hubs::deb::DebianPackagesHub::new(suite),
+            None => pb_tools::hubs::deb::DebianPackagesHub::new(pb_tools::hubs::deb::DEFAULT_SUITE),
+        };
         let mut db = panbuild::db::Database::get_database();
-        pb_tools::hubs::github::get_and_add_repos(&mut db);
+        if let Err(e) = hub.import(&mut db) {
+            eprintln!("Could not import the Debian source packages: {}", e);
+            exit_code = 1;
+        }
+    }
+
+    // Imports the brew formulae, with their stable version, archive and dependencies.
+    if command_name == &"import-brew-formulae".to_string() {
+        let hub = pb_tools::hubs::brew::Homebrew {};
+        let mut db = panbuild::db::Database::get_database();
+        if let Err(e) = hub.import(&mut db) {
+            eprintln!("Could not import the brew formulae: {}", e);
+            exit_code = 1;
+        }
     }
 
-    if command_name == &"import-brew-recipes".to_string() {
+    if command_name == &"dedup-projects".to_string() {
         let mut db = panbuild::db::Database::get_database();
-        pb_tools::hubs::brew::get_and_add_recipes(&mut db);
+        pb_tools::dedup::dedup_db_projects(&mut db);
     }
 
     // Used for manually harvesting a single project.
     if command_name == &"harvest-project".to_string() {
         let mut db = panbuild::db::Database::get_database();
         let repo_url = &args[2];
-        let project = panbuild::projects::SoftwareProject::harvest(&repo_url);
+        let (project, project_versions) = match panbuild::projects::SoftwareProject::harvest(&repo_url) {
+            Ok(p) => p,
+            Err(e) => {
+                eprintln!("Could not harvest project at {}: {}", &repo_url, e);
+                exit(1);
+            },
+        };
+        println!("Found {} versions for project {}.", project_versions.len(), &project.id);
+        db.add_project_versions(&project.id, project_versions);
         if db.has_project(&project.id) {
             db.update_project(&project);
         } else {
@@ -0,0 +1,451 @@
+// Crawler shared by the software hubs.
+//
+// The paged requests are fetched concurrently, each one following the Link headers
+// of its responses. Rate-limited responses are retried after the delay requested
+// by the server, and the position of each crawl can be saved to a checkpoint file
+// so that an interrupted crawl can be resumed later.
+use std::collections::BTreeMap;
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use reqwest::header;
+use serde::{Deserialize, Serialize};
+
+use panbuild::utils::PagedRequest;
+
+pub const DEFAULT_MAX_RETRIES: u32 = 5;
+pub const DEFAULT_BACKOFF_SECONDS: u64 = 5;
+// Rate limits that reset later than that are better handled by resuming the crawl later.
+pub const MAX_RETRY_DELAY_SECONDS: u64 = 3600;
+pub const CRAWLS_SUBDIR: &str = "/crawls";
+// The maximum number of pages waiting to be handled.
+const PAGES_CHANNEL_SIZE: usize = 16;
+
+// The position of the crawls, indexed by the url of their first page.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Checkpoint {
+    // The next page to fetch for each crawl. Finished crawls have no next page.
+    pub next_page_urls: BTreeMap<String, Option<String>>,
+}
+impl Checkpoint {
+    pub fn load(checkpoint_path: &Path) -> Checkpoint {
+        let checkpoint_content = match fs::read_to_string(checkpoint_path) {
+            Ok(c) => c,
+            Err(_) => return Checkpoint::default(),
+        };
+        match serde_yaml::from_str(&checkpoint_content) {
+            Ok(c) => c,
+            Err(e) => {
+                log::warn!("Invalid crawl checkpoint at {}: {}", checkpoint_path.display(), e);
+                Checkpoint::default()
+            }
+        }
+    }
+
+    pub fn save(&self, checkpoint_path: &Path) -> Result<(), String> {
+        if let Some(parent_dir) = checkpoint_path.parent() {
+            if let Err(e) = fs::create_dir_all(parent_dir) {
+                return Err(format!("Could not create directory {}: {}", parent_dir.display(), e));
+            }
+        }
+        match fs::write(checkpoint_path, serde_yaml::to_string(&self).unwrap()) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Could not save crawl checkpoint at {}: {}", checkpoint_path.display(), e)),
+        }
+    }
+}
+
+// Gets the path of the checkpoint file for a crawl, in the data directory.
+pub fn get_checkpoint_path(crawl_name: &str) -> PathBuf {
+    let crawls_dir = format!("{}{}", panbuild::cache::get_data_dir_path(), CRAWLS_SUBDIR);
+    Path::new(&crawls_dir).join(format!("{}.yaml", crawl_name))
+}
+
+pub struct Page {
+    pub content: String,
+    pub next_page_url: Option<String>,
+}
+
+fn get_header_number(headers: &header::HeaderMap, header_names: &[&str]) -> Option<u64> {
+    for header_name in header_names {
+        if let Some(value) = headers.get(*header_name).and_then(|v| v.to_str().ok()) {
+            if let Ok(number) = value.trim().parse::<u64>() {
+                return Some(number);
+            }
+        }
+    }
+    None
+}
+
+// Gets the delay until the rate limit of the server is reset, if the rate limit was reached.
+// GitHub uses the X-RateLimit-* headers and GitLab uses the RateLimit-* headers.
+fn get_rate_limit_delay(headers: &header::HeaderMap) -> Option<Duration> {
+    if get_header_number(headers, &["x-ratelimit-remaining", "ratelimit-remaining"])? != 0 {
+        return None;
+    }
+    let reset_time = get_header_number(headers, &["x-ratelimit-reset", "ratelimit-reset"])?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    // Adding a second, since the reset time is rounded.
+    Some(Duration::from_secs(reset_time.saturating_sub(now) + 1))
+}
+
+// Gets the delay before retrying a failed request, or None if the request should not be retried.
+pub fn get_retry_delay(status: u16, headers: &header::HeaderMap, attempt: u32, backoff: Duration) -> Option<Duration> {
+    let exponential_backoff = backoff * 2u32.pow(attempt);
+    if let Some(retry_after) = get_header_number(headers, &["retry-after"]) {
+        if status == 403 || status == 429 || status == 503 {
+            return Some(Duration::from_secs(retry_after));
+        }
+    }
+    if let Some(delay) = get_rate_limit_delay(headers) {
+        if status == 403 || status == 429 {
+            return Some(delay);
+        }
+    }
+    // A 403 without rate limit headers is a permission error.
+    if status == 429 || status >= 500 {
+        return Some(exponential_backoff);
+    }
+    None
+}
+
+#[derive(Clone)]
+pub struct Crawler {
+    client: reqwest::Client,
+    pub max_retries: u32,
+    pub backoff: Duration,
+    // Where to save the position of the crawls. The crawls are not resumable without it.
+    pub checkpoint_path: Option<PathBuf>,
+}
+impl Crawler {
+    pub fn new(headers: header::HeaderMap) -> Crawler {
+        Crawler {
+            client: reqwest::Client::builder().default_headers(headers).build().unwrap(),
+            max_retries: DEFAULT_MAX_RETRIES,
+            backoff: Duration::from_secs(DEFAULT_BACKOFF_SECONDS),
+            checkpoint_path: None,
+        }
+    }
+
+    // Fetches a page, retrying when the server is rate limiting us or temporarily unavailable.
+    pub async fn get_page(&self, url: &str, token: Option<&str>) -> Result<Page, String> {
+        match self.get_page_if_exists(url, token).await? {
+            Some(page) => Ok(page),
+            None => Err(format!("Page {} was not found.", url)),
+        }
+    }
+
+    // Same as get_page, but a missing page is not an error.
+    pub async fn get_page_if_exists(&self, url: &str, token: Option<&str>) -> Result<Option<Page>, String> {
+        self.fetch_page(url, token, None).await
+    }
+
+    // Posts a JSON body, for the APIs like GraphQL that are not queried with GET requests.
+    pub async fn post_page(&self, url: &str, token: Option<&str>, body: &str) -> Result<Page, String> {
+        match self.fetch_page(url, token, Some(body)).await? {
+            Some(page) => Ok(page),
+            None => Err(format!("Page {} was not found.", url)),
+        }
+    }
+
+    async fn fetch_page(&self, url: &str, token: Option<&str>, body: Option<&str>) -> Result<Option<Page>, String> {
+        let mut attempt = 0;
+        loop {
+            let mut request = match body {
+                Some(body) => self.client.post(url).header(header::CONTENT_TYPE, "application/json").body(body.to_string()),
+                None => self.client.get(url),
+            };
+            if let Some(token) = token {
+                request = request.bearer_auth(token);
+            }
+            log::info!("Getting page at {}.", url);
+            let response = match request.send().await {
+                Ok(r) => r,
+                Err(e) => return Err(format!("Could not fetch url {}: {}.", url, e)),
+            };
+
+            let status = response.status().as_u16();
+            if status < 400 {
+                let next_page_url = match response.headers().get("link").and_then(|h| h.to_str().ok()) {
+                    Some(link_header) => panbuild::utils::get_next_page_url(link_header),
+                    None => None,
+                };
+                // We're not waiting if there are no more pages.
+                let rate_limit_delay = match next_page_url {
+                    Some(_) => get_rate_limit_delay(response.headers()),
+                    None => None,
+                };
+                // 204 is used by some APIs for an empty page.
+                let content = match status {
+                    204 => "[]".to_string(),
+                    _ => match response.text().await {
+                        Ok(c) => c,
+                        Err(e) => return Err(format!("Could not read the response from {}: {}.", url, e)),
+                    },
+                };
+                if let Some(delay) = rate_limit_delay {
+                    log::warn!("Rate limit reached, waiting {} seconds.", delay.as_secs());
+                    tokio::time::sleep(delay).await;
+                }
+                return Ok(Some(Page {
+                    content: content,
+                    next_page_url: next_page_url,
+                }));
+            }
+            if status == 404 {
+                return Ok(None);
+            }
+
+            let retry_delay = match get_retry_delay(status, response.headers(), attempt, self.backoff) {
+                Some(d) => d,
+                None => return Err(format!("Got status {} from {}: {}", status, url, response.text().await.unwrap_or_default())),
+            };
+            if attempt >= self.max_retries || retry_delay.as_secs() > MAX_RETRY_DELAY_SECONDS {
+                return Err(format!("Giving up on {} after {} attempts, got status {}.", url, attempt + 1, status));
+            }
+            log::warn!("Got status {} from {}, retrying in {} seconds.", status, url, retry_delay.as_secs());
+            tokio::time::sleep(retry_delay).await;
+            attempt += 1;
+        }
+    }
+
+    // Crawls the paged requests concurrently, starting from the next_page_url of each request.
+    // The results of each page are passed to handle_results as soon as the page is parsed.
+    // When a checkpoint path is set, the crawls resume from the saved checkpoint, and the
+    // checkpoint is removed once all the crawls it contains are finished.
+    pub async fn crawl<T, F>(&self, requests: Vec<PagedRequest>, parse_page: fn(&str) -> Result<Vec<T>, String>, mut handle_results: F) -> Result<(), String>
+    where
+        T: Send + 'static,
+        F: FnMut(Vec<T>),
+    {
+        let mut checkpoint = match &self.checkpoint_path {
+            Some(p) => Checkpoint::load(p),
+            None => Checkpoint::default(),
+        };
+
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<(String, Result<(Vec<T>, Option<String>), String>)>(PAGES_CHANNEL_SIZE);
+        for request in requests {
+            let first_page_url = match request.next_page_url {
+                Some(u) => u,
+                None => return Err(format!("No url to crawl for {}.", request.domain)),
+            };
+            let mut next_page_url = match checkpoint.next_page_urls.get(&first_page_url) {
+                Some(Some(u)) => u.to_string(),
+                Some(None) => {
+                    log::info!("Crawl of {} already finished.", first_page_url);
+                    continue;
+                }
+                None => first_page_url.to_string(),
+            };
+
+            let crawler = self.clone();
+            let sender = sender.clone();
+            let token = request.token;
+            tokio::spawn(async move {
+                loop {
+                    let page_result = match crawler.get_page(&next_page_url, token.as_deref()).await {
+                        Ok(page) => match parse_page(&page.content) {
+                            Ok(results) => Ok((results, page.next_page_url)),
+                            Err(e) => Err(format!("Could not parse page {}: {}", next_page_url, e)),
+                        },
+                        Err(e) => Err(e),
+                    };
+                    let next_url = match &page_result {
+                        Ok((_, next_url)) => next_url.clone(),
+                        Err(_) => None,
+                    };
+                    if sender.send((first_page_url.to_string(), page_result)).await.is_err() {
+                        return;
+                    }
+                    next_page_url = match next_url {
+                        Some(u) => u,
+                        None => return,
+                    };
+                }
+            });
+        }
+        // The channel is closed once all the crawls are done.
+        drop(sender);
+
+        let mut errors: Vec<String> = vec![];
+        while let Some((first_page_url, page_result)) = receiver.recv().await {
+            let (results, next_page_url) = match page_result {
+                Ok(r) => r,
+                Err(e) => {
+                    log::error!("Crawl of {} stopped: {}", first_page_url, e);
+                    errors.push(e);
+                    continue;
+                }
+            };
+            handle_results(results);
+            checkpoint.next_page_urls.insert(first_page_url, next_page_url);
+            if let Some(checkpoint_path) = &self.checkpoint_path {
+                checkpoint.save(checkpoint_path)?;
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(format!("{} crawls did not finish. First error: {}", errors.len(), errors[0]));
+        }
+        if let Some(checkpoint_path) = &self.checkpoint_path {
+            if checkpoint.next_page_urls.values().all(|u| u.is_none()) {
+                fs::remove_file(checkpoint_path).unwrap_or(());
+            }
+        }
+        Ok(())
+    }
+}
+
+// Runs a future to completion, for the synchronous callers of the crawler.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(future)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    type MockResponse = (u16, Vec<(&'static str, String)>, String);
+
+    // Starts a minimal HTTP server on a random port, and returns its base url.
+    // The handler gets the path of the request and the number of previous requests for that path.
+    fn start_mock_server(handler: fn(&str, &str, usize) -> MockResponse) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let server_base_url = base_url.to_string();
+        let hits: Arc<Mutex<HashMap<String, usize>>> = Arc::new(Mutex::new(HashMap::new()));
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(s) => s,
+                    Err(_) => continue,
+                };
+                let mut request: Vec<u8> = vec![];
+                let mut buffer = [0; 1024];
+                while !String::from_utf8_lossy(&request).contains("\r\n\r\n") {
+                    match stream.read(&mut buffer) {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buffer[..n]),
+                    }
+                }
+                let request = String::from_utf8_lossy(&request).to_string();
+                let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+                let hit_count = {
+                    let mut hits = hits.lock().unwrap();
+                    let hit_count = hits.entry(path.to_string()).or_insert(0);
+                    *hit_count += 1;
+                    *hit_count - 1
+                };
+
+                let (status, headers, body) = handler(&server_base_url, &path, hit_count);
+                let mut response = format!("HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n", status, body.len());
+                for (header_name, header_value) in headers {
+                    response.push_str(&format!("{}: {}\r\n", header_name, header_value));
+                }
+                response.push_str("\r\n");
+                response.push_str(&body);
+                stream.write_all(response.as_bytes()).unwrap_or(());
+            }
+        });
+        base_url
+    }
+
+    fn mock_handler(base_url: &str, path: &str, hit_count: usize) -> MockResponse {
+        let next_link = |page: &str| vec![("Link", format!("<{}{}>; rel=\"next\"", base_url, page))];
+        match path {
+            "/projects?page=1" => (200, next_link("/projects?page=2"), "[\"glib\", \"gtk\"]".to_string()),
+            // The second page is rate limited on the first attempt.
+            "/projects?page=2" if hit_count == 0 => (429, vec![("Retry-After", "0".to_string())], "".to_string()),
+            "/projects?page=2" => (200, vec![], "[\"libsoup\"]".to_string()),
+            "/users?page=1" => (200, next_link("/users?page=2"), "[\"louib\"]".to_string()),
+            "/users?page=2" => (403, vec![], "{\"message\": \"Forbidden\"}".to_string()),
+            _ => (404, vec![], "".to_string()),
+        }
+    }
+
+    fn parse_names(content: &str) -> Result<Vec<String>, String> {
+        match serde_json::from_str(content) {
+            Ok(n) => Ok(n),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn get_request(url: &str) -> PagedRequest {
+        PagedRequest {
+            domain: "".to_string(),
+            token: None,
+            next_page_url: Some(url.to_string()),
+        }
+    }
+
+    fn get_test_crawler() -> Crawler {
+        let mut crawler = Crawler::new(header::HeaderMap::new());
+        crawler.backoff = Duration::from_millis(10);
+        crawler
+    }
+
+    #[test]
+    pub fn test_get_retry_delay() {
+        let backoff = Duration::from_secs(2);
+        let mut headers = header::HeaderMap::new();
+        assert_eq!(get_retry_delay(404, &headers, 0, backoff), None);
+        assert_eq!(get_retry_delay(403, &headers, 0, backoff), None);
+        assert_eq!(get_retry_delay(429, &headers, 2, backoff), Some(Duration::from_secs(8)));
+        assert_eq!(get_retry_delay(502, &headers, 0, backoff), Some(Duration::from_secs(2)));
+
+        headers.insert("Retry-After", header::HeaderValue::from_static("30"));
+        assert_eq!(get_retry_delay(403, &headers, 0, backoff), Some(Duration::from_secs(30)));
+
+        let mut headers = header::HeaderMap::new();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        headers.insert("X-RateLimit-Remaining", header::HeaderValue::from_static("0"));
+        headers.insert("X-RateLimit-Reset", header::HeaderValue::from_str(&(now + 60).to_string()).unwrap());
+        let delay = get_retry_delay(403, &headers, 0, backoff).unwrap();
+        assert!(delay.as_secs() >= 59 && delay.as_secs() <= 61);
+    }
+
+    #[test]
+    pub fn test_crawl() {
+        let base_url = start_mock_server(mock_handler);
+        let requests = vec![get_request(&format!("{}/projects?page=1", base_url))];
+        let mut all_names: Vec<String> = vec![];
+        block_on(get_test_crawler().crawl(requests, parse_names, |names| all_names.extend(names))).unwrap();
+        assert_eq!(all_names, vec!["glib", "gtk", "libsoup"]);
+    }
+
+    #[test]
+    pub fn test_crawl_checkpoint() {
+        let base_url = start_mock_server(mock_handler);
+        let checkpoint_dir = tempfile::tempdir().unwrap();
+        let checkpoint_path = checkpoint_dir.path().join("crawl.yaml");
+        let mut crawler = get_test_crawler();
+        crawler.checkpoint_path = Some(checkpoint_path.clone());
+
+        let projects_url = format!("{}/projects?page=1", base_url);
+        let users_url = format!("{}/users?page=1", base_url);
+        let requests = vec![get_request(&projects_url), get_request(&users_url)];
+        let mut all_names: Vec<String> = vec![];
+        assert!(block_on(crawler.crawl(requests, parse_names, |names| all_names.extend(names))).is_err());
+        all_names.sort();
+        assert_eq!(all_names, vec!["glib", "gtk", "libsoup", "louib"]);
+
+        // The failed crawl is resumed from its last page, and the finished crawl is skipped.
+        let checkpoint = Checkpoint::load(&checkpoint_path);
+        assert_eq!(checkpoint.next_page_urls.get(&projects_url).unwrap(), &None);
+        assert_eq!(checkpoint.next_page_urls.get(&users_url).unwrap(), &Some(format!("{}/users?page=2", base_url)));
+
+        let requests = vec![get_request(&projects_url)];
+        let mut all_names: Vec<String> = vec![];
+        block_on(crawler.crawl(requests, parse_names, |names| all_names.extend(names))).unwrap();
+        assert!(all_names.is_empty());
+        assert!(checkpoint_path.exists());
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::BTreeMap;
+
+use panbuild::projects::SoftwareProject;
+
+// The id prefixes of the public hubs that host projects from anyone, and
+// therefore a lot of mirrors.
+const SHARED_HUBS: [&str; 3] = ["org.codeberg.", "com.gitlab.", "com.github."];
+
+// A group of projects that were found to be the same project, hosted
+// in different places.
+#[derive(Debug)]
+pub struct DuplicateGroup {
+    pub canonical_id: String,
+    pub duplicate_ids: Vec<String>,
+}
+
+// Groups the projects by their root commit hashes.
+//
+// A repository can have more than one root commit (in the case of a merger
+// of unrelated histories), and a root commit can be found in more than one
+// repository (in the case of a fork, or of a project that imported another
+// one). Because of that, we only consider 2 projects to be duplicates when
+// they have exactly the same set of root hashes. Projects that only share
+// some of their root hashes are most likely related projects, and are left alone.
+// Forks also have the same root hashes as their parent project, but are
+// different projects, so they are never de-duplicated.
+pub fn get_duplicate_groups(projects: &Vec<SoftwareProject>) -> Vec<DuplicateGroup> {
+    let mut projects_by_signature: BTreeMap<String, Vec<&SoftwareProject>> = BTreeMap::new();
+    let mut signatures_by_hash: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for project in projects {
+        if project.is_fork {
+            continue;
+        }
+        let signature = match project.get_root_signature() {
+            Some(s) => s,
+            None => continue,
+        };
+        for root_hash in signature.split(",") {
+            let signatures = signatures_by_hash.entry(root_hash.to_string()).or_insert(vec![]);
+            if !signatures.contains(&signature) {
+                signatures.push(signature.to_string());
+            }
+        }
+        projects_by_signature.entry(signature).or_insert(vec![]).push(project);
+    }
+
+    for (root_hash, signatures) in &signatures_by_hash {
+        if signatures.len() > 1 {
+            log::info!(
+                "Root hash {} is shared by {} unrelated histories, not merging them.",
+                root_hash,
+                signatures.len()
+            );
+        }
+    }
+
+    let mut groups: Vec<DuplicateGroup> = vec![];
+    for (_, mut duplicate_projects) in projects_by_signature {
+        duplicate_projects.sort_by_key(|p| get_canonical_sort_key(p));
+        duplicate_projects.dedup_by(|a, b| a.id == b.id);
+        if duplicate_projects.len() < 2 {
+            continue;
+        }
+        let canonical_id = duplicate_projects.remove(0).id.to_string();
+        groups.push(DuplicateGroup {
+            canonical_id,
+            duplicate_ids: duplicate_projects.iter().map(|p| p.id.to_string()).collect(),
+        });
+    }
+    groups
+}
+
+// Gets the key used to choose the canonical project of a group of duplicates.
+// The projects that are not mirrors come first, then the projects hosted outside
+// of the shared hubs, since those are usually hosted by the upstream project itself,
+// and then the projects hosted on the shared hubs, in the order of SHARED_HUBS.
+// The ids are used to break the ties.
+fn get_canonical_sort_key(project: &SoftwareProject) -> (bool, usize, String) {
+    let hub_rank = match SHARED_HUBS.iter().position(|h| project.id.starts_with(h)) {
+        Some(position) => position + 1,
+        None => 0,
+    };
+    (project.is_mirror, hub_rank, project.id.to_string())
+}
+
+// Merges the duplicated projects into their canonical project. The ids of the
+// duplicated projects are kept as aliases of the canonical project.
+pub fn dedup_projects(projects: Vec<SoftwareProject>) -> Vec<SoftwareProject> {
+    let groups = get_duplicate_groups(&projects);
+
+    let mut canonical_ids: BTreeMap<String, String> = BTreeMap::new();
+    for group in &groups {
+        for duplicate_id in &group.duplicate_ids {
+            canonical_ids.insert(duplicate_id.to_string(), group.canonical_id.to_string());
+        }
+    }
+
+    let mut duplicates: Vec<SoftwareProject> = vec![];
+    let mut deduped_projects: Vec<SoftwareProject> = vec![];
+    for project in projects {
+        if canonical_ids.contains_key(&project.id) {
+            duplicates.push(project);
+        } else {
+            deduped_projects.push(project);
+        }
+    }
+
+    for duplicate in duplicates {
+        let canonical_id = canonical_ids.get(&duplicate.id).unwrap();
+        for project in &mut deduped_projects {
+            if &project.id == canonical_id {
+                log::debug!("Merging project {} into {}.", &duplicate.id, canonical_id);
+                project.merge(&duplicate);
+                break;
+            }
+        }
+    }
+
+    deduped_projects
+}
+
+pub fn dedup_db_projects(db: &mut panbuild::db::Database) {
+    let groups = get_duplicate_groups(&db.projects);
+    for group in &groups {
+        for duplicate_id in &group.duplicate_ids {
+            let mut duplicate = match db.get_project(duplicate_id) {
+                Some(p) => p,
+                None => continue,
+            };
+            log::info!("Merging project {} into {}.", duplicate_id, &group.canonical_id);
+            // The duplicate is merged into the canonical project stored under the canonical id.
+            duplicate.id = group.canonical_id.to_string();
+            duplicate.aliases.push(duplicate_id.to_string());
+            db.update_project(&duplicate);
+            db.remove_project(duplicate_id);
+        }
+    }
+    println!("Merged {} groups of duplicated projects.", groups.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_project(id: &str, root_hashes: Vec<&str>) -> SoftwareProject {
+        SoftwareProject {
+            id: id.to_string(),
+            vcs_urls: vec![format!("https://{}.git", id)],
+            root_hashes: root_hashes.iter().map(|h| h.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    pub fn test_dedup_mirrors() {
+        let projects = vec![
+            get_project("com.github.gnome.gtk", vec!["abc"]),
+            get_project("org.gnome.gitlab.gnome.gtk", vec!["abc"]),
+            get_project("com.github.gnome.glib", vec!["def"]),
+        ];
+        let projects = dedup_projects(projects);
+        assert_eq!(projects.len(), 2);
+        // The project hosted by the upstream project is preferred over the shared hubs.
+        assert_eq!(projects[0].id, "org.gnome.gitlab.gnome.gtk");
+        assert_eq!(projects[0].aliases, vec!["com.github.gnome.gtk"]);
+        assert_eq!(projects[0].vcs_urls.len(), 2);
+    }
+
+    #[test]
+    pub fn test_dedup_multiple_root_hashes() {
+        let projects = vec![
+            get_project("com.github.user.project", vec!["def", "abc"]),
+            get_project("com.gitlab.user.project", vec!["abc", "def"]),
+        ];
+        let groups = get_duplicate_groups(&projects);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].canonical_id, "com.gitlab.user.project");
+    }
+
+    #[test]
+    pub fn test_dedup_forks_and_mirrors() {
+        let mut fork = get_project("org.freedesktop.gitlab.user.project", vec!["abc"]);
+        fork.is_fork = true;
+        let mut mirror = get_project("org.example.git.project", vec!["abc"]);
+        mirror.is_mirror = true;
+        let projects = vec![fork, mirror, get_project("com.github.user.project", vec!["abc"])];
+        let groups = get_duplicate_groups(&projects);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].canonical_id, "com.github.user.project");
+        assert_eq!(groups[0].duplicate_ids, vec!["org.example.git.project"]);
+
+        let projects = dedup_projects(projects);
+        assert_eq!(projects.len(), 2);
+        assert!(projects[0].is_fork);
+        assert!(!projects[1].is_mirror);
+    }
+
+    #[test]
+    pub fn test_dedup_shared_root_hash() {
+        // The second project merged the history of the first one,
+        // but they are not the same project.
+        let projects = vec![
+            get_project("com.github.user.project", vec!["abc"]),
+            get_project("com.github.user.superproject", vec!["abc", "def"]),
+            get_project("com.github.user.nohistory", vec![]),
+        ];
+        assert_eq!(get_duplicate_groups(&projects).len(), 0);
+        assert_eq!(dedup_projects(projects).len(), 3);
+    }
+}
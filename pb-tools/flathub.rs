@@ -0,0 +1,160 @@
+use panbuild::manifests::flatpak::{FlatpakManifest, FlatpakModule};
+use panbuild::permissions::PermissionSet;
+use panbuild::projects::{FlatpakApp, SoftwareProject};
+
+pub const FLATHUB_ORG: &str = "flathub";
+pub const FLATHUB_APPS_URL: &str = "https://flathub.org/apps/details";
+
+// Describes the permissions granted by the finish-args of a manifest,
+// for example --share=network is described as network.
+pub fn get_permission_descriptions(finish_args: &[String]) -> Vec<String> {
+    let permission_set = match PermissionSet::parse(finish_args) {
+        Ok(p) => p,
+        Err(e) => {
+            log::warn!("Could not parse the finish-args: {}", e);
+            return vec![];
+        }
+    };
+    permission_set.get_normalized().permissions.iter().filter_map(|p| p.get_description()).collect()
+}
+
+// Gets the project of the application built by a Flathub manifest. The manifests
+// of the extensions and of the runtimes don't define an application id.
+pub fn get_app_project(manifest: &FlatpakManifest, repo_url: &str) -> Option<SoftwareProject> {
+    let app_id = manifest.get_id();
+    if app_id.is_empty() {
+        return None;
+    }
+    if manifest.runtime.is_empty() || manifest.build_extension.unwrap_or(false) {
+        return None;
+    }
+
+    let mut project = SoftwareProject::default();
+    project.id = app_id.to_string();
+    project.name = match manifest.app_name.is_empty() {
+        true => app_id.split('.').last().unwrap_or(app_id).to_string(),
+        false => manifest.app_name.to_string(),
+    };
+    project.web_urls.push(format!("{}/{}", FLATHUB_APPS_URL, app_id));
+    // The repo only contains the packaging of the application, not its sources.
+    project.web_urls.push(repo_url.trim_end_matches(".git").to_string());
+    if !manifest.command.is_empty() {
+        project.artifact_names.push(manifest.command.to_string());
+    }
+    project.build_systems.push(manifest.get_type().to_string());
+    project.flatpak_app = Some(FlatpakApp {
+        runtime: format!("{}//{}", manifest.runtime, manifest.runtime_version),
+        sdk: format!("{}//{}", manifest.sdk, manifest.runtime_version),
+        permissions: get_permission_descriptions(&manifest.finish_args),
+    });
+    Some(project)
+}
+
+// Finds the project that a module is built from, first using the url of its source, then
+// using the root commits of its git repository. Returns the id of the project if found.
+pub fn find_module_project_id(db: &panbuild::db::Database, module: &FlatpakModule) -> Option<String> {
+    let software_module = module.to_module();
+    if let Some(project) = panbuild::projects::find_module_project(&db.projects, &software_module) {
+        return Some(project.id.to_string());
+    }
+    if software_module.source_type != panbuild::modules::SourceType::Git || !software_module.url.starts_with("https://") {
+        return None;
+    }
+    let repo_path = match panbuild::utils::clone_git_repo(&software_module.url) {
+        Ok(p) => p,
+        Err(e) => {
+            log::warn!("Could not clone repo {} of module {}: {}", software_module.url, module.name, e);
+            return None;
+        }
+    };
+    let root_hashes = match panbuild::utils::get_git_repo_root_hashes(&repo_path) {
+        Ok(h) => h,
+        Err(e) => {
+            log::warn!("Could not get the root commits of {}: {}", software_module.url, e);
+            return None;
+        }
+    };
+    panbuild::projects::find_project_by_root_hashes(&db.projects, &root_hashes).map(|p| p.id.to_string())
+}
+
+// Imports a Flathub manifest in the db: the project of the application, and the modules
+// of the manifest, linked to the projects they are built from when possible.
+pub fn import_manifest(db: &mut panbuild::db::Database, manifest: FlatpakManifest, repo_url: &str) {
+    if let Some(app_project) = get_app_project(&manifest, repo_url) {
+        if db.has_project(&app_project.id) {
+            db.update_project(&app_project);
+        } else {
+            db.add_project(app_project);
+        }
+    }
+
+    for module in manifest.modules {
+        if let Some(project_id) = find_module_project_id(db, &module) {
+            db.link_module(&module, &project_id);
+        }
+        db.add_module(module);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_get_permission_descriptions() {
+        let finish_args = vec![
+            "--share=network".to_string(),
+            "--socket=x11".to_string(),
+            "--socket=wayland".to_string(),
+            "--filesystem=home".to_string(),
+            "--talk-name=org.freedesktop.Notifications".to_string(),
+            "--env=GTK_USE_PORTAL=1".to_string(),
+            "--share=network".to_string(),
+            "--nosocket=x11".to_string(),
+        ];
+        let permissions = get_permission_descriptions(&finish_args);
+        assert_eq!(
+            permissions,
+            vec!["network", "home read-write", "Wayland", "talks to org.freedesktop.Notifications"]
+        );
+    }
+
+    #[test]
+    pub fn test_get_app_project() {
+        let manifest = FlatpakManifest::parse(
+            &r###"
+            app-id: org.gnome.Clocks
+            runtime: org.gnome.Platform
+            runtime-version: "40"
+            sdk: org.gnome.Sdk
+            command: gnome-clocks
+            finish-args:
+              - --share=ipc
+              - --socket=wayland
+            modules:
+              - name: gnome-clocks
+                buildsystem: meson
+                sources:
+                  - type: git
+                    url: https://gitlab.gnome.org/GNOME/gnome-clocks.git
+            "###
+            .to_string(),
+        )
+        .unwrap();
+        let project = get_app_project(&manifest, "https://github.com/flathub/org.gnome.Clocks.git").unwrap();
+        assert_eq!(project.id, "org.gnome.Clocks");
+        assert_eq!(project.name, "Clocks");
+        assert_eq!(project.artifact_names, vec!["gnome-clocks"]);
+        assert_eq!(
+            project.web_urls,
+            vec![
+                "https://flathub.org/apps/details/org.gnome.Clocks",
+                "https://github.com/flathub/org.gnome.Clocks"
+            ]
+        );
+        let flatpak_app = project.flatpak_app.unwrap();
+        assert_eq!(flatpak_app.runtime, "org.gnome.Platform//40");
+        assert_eq!(flatpak_app.sdk, "org.gnome.Sdk//40");
+        assert_eq!(flatpak_app.permissions, vec!["ipc", "Wayland"]);
+    }
+}
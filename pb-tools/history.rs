@@ -0,0 +1,145 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use panbuild::git::FileRevision;
+use panbuild::manifests::flatpak::{FlatpakManifest, FlatpakModule, ModuleRevision};
+
+// Only the files named after an app id are considered, since
+// parsing every json or yaml file of a repo would be too slow.
+pub fn is_manifest_path(file_path: &str) -> bool {
+    if file_path.contains(".git/") {
+        return false;
+    }
+    FlatpakManifest::file_path_matches(file_path)
+}
+
+// Gets the modules of a manifest, including the modules nested in other modules.
+// The nested modules are detached from their parent module, so that every module
+// can be reused on its own.
+fn get_all_modules(modules: Vec<FlatpakModule>) -> Vec<FlatpakModule> {
+    let mut all_modules: Vec<FlatpakModule> = vec![];
+    for mut module in modules {
+        let nested_modules = std::mem::take(&mut module.modules);
+        all_modules.push(module);
+        all_modules.extend(get_all_modules(nested_modules));
+    }
+    all_modules
+}
+
+// Extracts the modules from the revisions of the manifests, from the oldest to the newest.
+// A module is only returned for the first revision in which it appears, so modules that
+// are unchanged across revisions of a manifest are only returned once.
+pub fn get_module_revisions_from_files(file_revisions: Vec<FileRevision>) -> Vec<ModuleRevision> {
+    let mut module_revisions: Vec<ModuleRevision> = vec![];
+    let mut seen_module_hashes: HashSet<u64> = HashSet::new();
+    for file_revision in file_revisions {
+        let flatpak_manifest = match FlatpakManifest::parse(&file_revision.content) {
+            Some(m) => m,
+            None => continue,
+        };
+        for module in get_all_modules(flatpak_manifest.modules) {
+            // Modules without sources can't be reused outside of their manifest.
+            if module.sources.is_empty() {
+                continue;
+            }
+            if !seen_module_hashes.insert(module.get_hash()) {
+                continue;
+            }
+            module_revisions.push(ModuleRevision {
+                manifest_path: file_revision.path.to_string(),
+                commit: file_revision.commit.to_string(),
+                timestamp: file_revision.timestamp,
+                module,
+            });
+        }
+    }
+    module_revisions
+}
+
+// Rewinds the history of a git repo to get all the versions of the
+// modules defined in the Flatpak manifests of the repo.
+pub fn get_module_revisions(repo_path: &str) -> Result<Vec<ModuleRevision>, String> {
+    let file_revisions = panbuild::git::get_file_revisions(Path::new(repo_path), &is_manifest_path)?;
+    log::info!("Found {} revisions of manifest files in {}.", file_revisions.len(), repo_path);
+    Ok(get_module_revisions_from_files(file_revisions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_file_revision(commit: &str, timestamp: i64, content: &str) -> FileRevision {
+        FileRevision {
+            path: "org.example.App.yaml".to_string(),
+            commit: commit.to_string(),
+            timestamp,
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    pub fn test_get_module_revisions_from_files() {
+        let file_revisions = vec![
+            get_file_revision(
+                "abc",
+                1600000000,
+                r###"
+                app-id: org.example.App
+                modules:
+                  - name: libfoo
+                    sources:
+                      - type: archive
+                        url: https://example.com/libfoo-1.0.tar.gz
+                  - name: app
+                    sources:
+                      - type: dir
+                        path: .
+                    modules:
+                      - name: libbar
+                        sources:
+                          - type: git
+                            url: https://example.com/libbar.git
+                            tag: v2.0
+            "###,
+            ),
+            get_file_revision("def", 1600000100, "not a manifest"),
+            get_file_revision(
+                "ghi",
+                1600000200,
+                r###"
+                app-id: org.example.App
+                modules:
+                  - name: libfoo
+                    sources:
+                      - type: archive
+                        url: https://example.com/libfoo-1.1.tar.gz
+                  - name: app
+                    sources:
+                      - type: dir
+                        path: .
+            "###,
+            ),
+        ];
+
+        let module_revisions = get_module_revisions_from_files(file_revisions);
+        assert_eq!(module_revisions.len(), 4);
+        assert_eq!(module_revisions[0].module.name, "libfoo");
+        assert_eq!(module_revisions[0].commit, "abc");
+        assert_eq!(module_revisions[1].module.name, "app");
+        assert!(module_revisions[1].module.modules.is_empty());
+        assert_eq!(module_revisions[2].module.name, "libbar");
+        assert_eq!(module_revisions[3].module.name, "libfoo");
+        assert_eq!(module_revisions[3].commit, "ghi");
+        assert_eq!(module_revisions[3].timestamp, 1600000200);
+    }
+
+    #[test]
+    pub fn test_is_manifest_path() {
+        assert!(is_manifest_path("org.example.App.json"));
+        assert!(is_manifest_path("build-aux/org.example.App.Devel.yml"));
+        assert!(!is_manifest_path("modules/libfoo.yml"));
+        assert!(!is_manifest_path("package.json"));
+        assert!(!is_manifest_path("README.md"));
+        assert!(!is_manifest_path(".git/config.json"));
+    }
+}
@@ -1,5 +1,8 @@
 pub mod brew;
+pub mod cgit;
 pub mod deb;
+pub mod gitea;
 pub mod github;
 pub mod gitlab;
 pub mod hub;
+pub mod sourcehut;
@@ -1,7 +1,15 @@
+use std::collections::BTreeMap;
+use std::env;
+
 use reqwest::header;
 
 use serde::{Deserialize, Serialize};
 
+use crate::hubs::hub::SoftwareHub;
+
+// The prefix of the ids of the formulae that are not built from a git repository.
+pub const PROJECT_ID_PREFIX: &str = "sh.brew.formulae.";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct HomebrewRecipe {
     pub name: String,
@@ -19,17 +27,75 @@ pub struct HomebrewRecipe {
 
     pub urls: HomebrewRecipeUrls,
     pub versions: HomebrewRecipeVersions,
+
+    // The names of the formulae required at runtime and at build time.
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    #[serde(default)]
+    pub build_dependencies: Vec<String>,
 }
 impl HomebrewRecipe {
-    pub fn to_software_project(self) -> panbuild::projects::SoftwareProject {
-        let mut project = panbuild::projects::SoftwareProject::default();
+    pub fn is_git_recipe(&self) -> bool {
         // We filter out http:// urls for now, but could try to convert to https in the future.
-        if self.urls.stable.url.ends_with(".git") && self.urls.stable.url.starts_with("https") {
-            project.id = panbuild::utils::repo_url_to_reverse_dns(&self.urls.stable.url);
-            project.vcs_urls.push(self.urls.stable.url);
+        self.urls.stable.url.ends_with(".git") && self.urls.stable.url.starts_with("https")
+    }
+
+    pub fn get_project_id(&self) -> String {
+        if self.is_git_recipe() {
+            return panbuild::utils::repo_url_to_reverse_dns(&self.urls.stable.url);
+        }
+        format!("{}{}", PROJECT_ID_PREFIX, self.name)
+    }
+
+    pub fn to_software_project(&self) -> panbuild::projects::SoftwareProject {
+        let mut project = panbuild::projects::SoftwareProject::default();
+        project.id = self.get_project_id();
+        project.name = self.name.to_string();
+        project.summary = self.desc.to_string();
+        if !self.homepage.is_empty() {
+            project.web_urls.push(self.homepage.to_string());
+        }
+        if self.is_git_recipe() {
+            project.vcs_urls.push(self.urls.stable.url.to_string());
         }
+        project.versions.push(self.versions.stable.to_string());
         project
     }
+
+    // Gets the stable version of the formula. The dependencies are resolved to projects
+    // using the index of the formulae names to the projects.
+    pub fn to_project_version(&self, formula_index: &BTreeMap<String, String>) -> panbuild::projects::ProjectVersion {
+        let mut project_version = panbuild::projects::ProjectVersion::default();
+        project_version.project_id = self.get_project_id();
+        project_version.name = self.versions.stable.to_string();
+        project_version.url = self.urls.stable.url.to_string();
+        if self.is_git_recipe() {
+            project_version.url_type = panbuild::modules::SourceType::Git;
+            project_version.tag = self.urls.stable.tag.clone().unwrap_or_default();
+            project_version.commit = self.urls.stable.revision.clone().unwrap_or_default();
+        } else {
+            project_version.url_type = panbuild::modules::SourceType::Tarball;
+            project_version.sha256sum = self.urls.stable.checksum.clone().unwrap_or_default();
+        }
+        for dependency_name in self.dependencies.iter().chain(self.build_dependencies.iter()) {
+            let project_id = match formula_index.get(dependency_name) {
+                Some(p) => p,
+                None => {
+                    log::debug!("No formula found for dependency {} of {}.", dependency_name, self.name);
+                    continue;
+                }
+            };
+            if project_version.dependencies.iter().any(|d| &d.project_id == project_id) {
+                continue;
+            }
+            // Homebrew only ever has one version of a formula, so there are no version constraints.
+            project_version.dependencies.push(panbuild::projects::Dependency {
+                version_range: panbuild::version::VersionRange::default(),
+                project_id: project_id.to_string(),
+            });
+        }
+        project_version
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -49,50 +115,211 @@ pub struct HomebrewRecipeUrl {
     pub url: String,
     pub tag: Option<String>,
     pub revision: Option<String>,
+    // The sha256 of the archive. Not defined for the git urls.
+    #[serde(default)]
+    pub checksum: Option<String>,
 }
 
-pub fn get_and_add_recipes(db: &mut panbuild::db::Database) {
-    // All the formulae for macOS
-    for project in get_projects("https://formulae.brew.sh/api/formula.json") {
-        db.add_project(project);
+// Indexes the ids of the projects by the names and the aliases of the formulae.
+pub fn get_formula_index(recipes: &Vec<HomebrewRecipe>) -> BTreeMap<String, String> {
+    let mut formula_index: BTreeMap<String, String> = BTreeMap::new();
+    for recipe in recipes {
+        for name in std::iter::once(&recipe.name).chain(recipe.aliases.iter()) {
+            formula_index.insert(name.to_string(), recipe.get_project_id());
+        }
     }
+    formula_index
+}
 
-    // All the formulae for Linux
-    for project in get_projects("https://formulae.brew.sh/api/formula-linux.json") {
-        db.add_project(project);
+// The base url of the Homebrew formulae API, which can be replaced for testing.
+pub fn get_api_url() -> String {
+    if let Ok(url) = env::var("PB_BREW_API_URL") {
+        return url.trim_end_matches('/').to_string();
     }
-
-    // There are also the cask formulae, but they have a different format.
-    // https://formulae.brew.sh/api/cask.json
+    "https://formulae.brew.sh/api".to_string()
 }
 
-pub fn get_projects(formulae_url: &str) -> Vec<panbuild::projects::SoftwareProject> {
-    let mut projects: Vec<panbuild::projects::SoftwareProject> = vec![];
+// All the formulae for macOS, and all the formulae for Linux.
+// There are also the cask formulae, but they have a different format.
+// https://formulae.brew.sh/api/cask.json
+const FORMULAE_FILE_NAMES: &[&str] = &["formula.json", "formula-linux.json"];
 
-    let client = reqwest::blocking::Client::builder().build().unwrap();
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Homebrew {}
+impl Homebrew {
+    fn get_requests(&self) -> Vec<panbuild::utils::PagedRequest> {
+        FORMULAE_FILE_NAMES
+            .iter()
+            .map(|file_name| panbuild::utils::PagedRequest {
+                domain: "".to_string(),
+                token: None,
+                next_page_url: Some(format!("{}/{}", get_api_url(), file_name)),
+            })
+            .collect()
+    }
 
-    // TODO make this really asynchronous with async/await.
-    let mut response = match client.get(formulae_url).send() {
-        Ok(r) => r,
-        Err(e) => return vec![],
-    };
+    // Gets all the formulae. A formula available on both macOS and Linux is only returned once.
+    pub fn get_recipes(&self) -> Result<Vec<HomebrewRecipe>, String> {
+        let mut recipes: BTreeMap<String, HomebrewRecipe> = BTreeMap::new();
+        let crawler = crate::crawler::Crawler::new(header::HeaderMap::new());
+        crate::crawler::block_on(crawler.crawl(self.get_requests(), parse_recipe_list, |page_recipes| {
+            for recipe in page_recipes {
+                recipes.entry(recipe.full_name.to_string()).or_insert(recipe);
+            }
+        }))?;
+        Ok(recipes.into_iter().map(|(_, r)| r).collect())
+    }
 
-    let brew_recipes: Vec<HomebrewRecipe> = match serde_json::from_str(&response.text().unwrap()) {
-        Ok(r) => r,
-        Err(e) => {
-            eprintln!("Could not parse brew recipes {}.", e);
-            return vec![];
+    // Imports all the formulae in the db, with their stable version and their dependencies.
+    // The formulae are merged with the projects already in the db.
+    pub fn import(&self, db: &mut panbuild::db::Database) -> Result<(), String> {
+        let recipes = self.get_recipes()?;
+        let formula_index = get_formula_index(&recipes);
+        for recipe in &recipes {
+            let project = recipe.to_software_project();
+            if db.has_project(&project.id) {
+                db.update_project(&project);
+            } else {
+                db.add_project(project);
+            }
+            db.add_project_versions(&recipe.get_project_id(), vec![recipe.to_project_version(&formula_index)]);
         }
-    };
+        log::info!("Imported {} brew formulae.", recipes.len());
+        Ok(())
+    }
+}
+impl SoftwareHub for Homebrew {
+    fn get_name(&self) -> String {
+        "brew".to_string()
+    }
+
+    fn list_projects(&self, handle_projects: &mut dyn FnMut(Vec<panbuild::projects::SoftwareProject>)) -> Result<(), String> {
+        let crawler = crate::crawler::Crawler::new(header::HeaderMap::new());
+        crate::crawler::block_on(crawler.crawl(self.get_requests(), parse_recipes, |projects| handle_projects(projects)))
+    }
+
+    // The formulae are not indexed by project id, so all the formulae have to be fetched.
+    fn get_project(&self, project_id: &str) -> Result<Option<panbuild::projects::SoftwareProject>, String> {
+        let mut found_project: Option<panbuild::projects::SoftwareProject> = None;
+        self.list_projects(&mut |projects| {
+            if found_project.is_none() {
+                found_project = projects.into_iter().find(|p| p.id == project_id);
+            }
+        })?;
+        Ok(found_project)
+    }
+
+    fn get_versions(&self, project: &panbuild::projects::SoftwareProject) -> Result<Vec<panbuild::projects::ProjectVersion>, String> {
+        let recipes = self.get_recipes()?;
+        let formula_index = get_formula_index(&recipes);
+        Ok(recipes
+            .iter()
+            .filter(|r| r.get_project_id() == project.id)
+            .map(|r| r.to_project_version(&formula_index))
+            .collect())
+    }
+}
+
+pub fn parse_recipe_list(page_content: &str) -> Result<Vec<HomebrewRecipe>, String> {
+    match serde_json::from_str(page_content) {
+        Ok(r) => Ok(r),
+        Err(e) => Err(format!("Could not parse brew recipes {}.", e)),
+    }
+}
 
+pub fn parse_recipes(page_content: &str) -> Result<Vec<panbuild::projects::SoftwareProject>, String> {
+    let brew_recipes = parse_recipe_list(page_content)?;
+
+    let mut projects: Vec<panbuild::projects::SoftwareProject> = vec![];
     for brew_recipe in brew_recipes {
         let project = brew_recipe.to_software_project();
-        if project.id.len() == 0 {
-            continue;
-        }
         log::info!("Adding project {} from brew recipe.", project.name);
         projects.push(project);
     }
+    Ok(projects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMULAE_EXAMPLE: &str = r###"[
+      {
+        "name": "glib",
+        "full_name": "glib",
+        "tap": "homebrew/core",
+        "aliases": [],
+        "license": "LGPL-2.1-or-later",
+        "desc": "Core application library for C",
+        "homepage": "https://developer.gnome.org/glib/",
+        "disabled": false,
+        "deprecated": false,
+        "outdated": false,
+        "pinned": false,
+        "urls": {
+          "stable": {
+            "url": "https://download.gnome.org/sources/glib/2.68/glib-2.68.3.tar.xz",
+            "tag": null,
+            "revision": null,
+            "checksum": "e7e1a3c20c026109c45c9ec4a31d8dcebc22e86c69486993e565817d64be3138"
+          }
+        },
+        "versions": {"stable": "2.68.3", "head": null, "bottle": true},
+        "dependencies": ["gettext", "libffi", "pcre"],
+        "build_dependencies": ["meson", "ninja", "pkg-config"]
+      },
+      {
+        "name": "libffi",
+        "full_name": "libffi",
+        "tap": "homebrew/core",
+        "aliases": ["ffi"],
+        "license": "MIT",
+        "desc": "Portable Foreign Function Interface library",
+        "homepage": "https://sourceware.org/libffi/",
+        "disabled": false,
+        "deprecated": false,
+        "outdated": false,
+        "pinned": false,
+        "urls": {
+          "stable": {
+            "url": "https://github.com/libffi/libffi.git",
+            "tag": "v3.3",
+            "revision": "5dcb741f1544c5e18c9dbf96aeb8b61cc556a616"
+          }
+        },
+        "versions": {"stable": "3.3", "head": "HEAD", "bottle": true},
+        "build_dependencies": ["libtool"]
+      }
+    ]"###;
 
-    projects
+    #[test]
+    pub fn test_parse_recipes() {
+        let projects = parse_recipes(FORMULAE_EXAMPLE).unwrap();
+        assert_eq!(projects.len(), 2);
+        assert_eq!(projects[0].id, "sh.brew.formulae.glib");
+        assert_eq!(projects[0].versions, vec!["2.68.3"]);
+        assert_eq!(projects[1].id, "com.github.libffi.libffi");
+        assert_eq!(projects[1].vcs_urls, vec!["https://github.com/libffi/libffi.git"]);
+    }
+
+    #[test]
+    pub fn test_to_project_version() {
+        let recipes = parse_recipe_list(FORMULAE_EXAMPLE).unwrap();
+        let formula_index = get_formula_index(&recipes);
+        assert_eq!(formula_index.get("ffi").unwrap(), "com.github.libffi.libffi");
+
+        let glib_version = recipes[0].to_project_version(&formula_index);
+        assert_eq!(glib_version.name, "2.68.3");
+        assert_eq!(glib_version.url_type, panbuild::modules::SourceType::Tarball);
+        assert!(glib_version.sha256sum.starts_with("e7e1a3c2"));
+        // Only libffi is a known formula.
+        assert_eq!(glib_version.dependencies.len(), 1);
+        assert_eq!(glib_version.dependencies[0].project_id, "com.github.libffi.libffi");
+
+        let libffi_version = recipes[1].to_project_version(&formula_index);
+        assert_eq!(libffi_version.url_type, panbuild::modules::SourceType::Git);
+        assert_eq!(libffi_version.tag, "v3.3");
+        assert_eq!(libffi_version.commit, "5dcb741f1544c5e18c9dbf96aeb8b61cc556a616");
+        assert!(libffi_version.sha256sum.is_empty());
+    }
 }
@@ -0,0 +1,277 @@
+use reqwest::header;
+
+use serde::{Deserialize, Serialize};
+
+use crate::hubs::hub::SoftwareHub;
+
+// The web frontends that publish an HTML index of their git repositories.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RepoIndexFormat {
+    // See https://git.zx2c4.com/cgit/about/
+    Cgit,
+    // See https://git-scm.com/docs/gitweb
+    Gitweb,
+}
+
+// Hub for the git hosts without an API, like savannah or sourceware, which
+// are only browsable through the repo index of their web frontend.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RepoIndex {
+    pub format: RepoIndexFormat,
+    // The url of the index page, for example https://git.savannah.gnu.org/cgit/
+    pub index_url: String,
+}
+impl RepoIndex {
+    pub fn new(format: RepoIndexFormat, index_url: &str) -> RepoIndex {
+        RepoIndex {
+            format: format,
+            index_url: index_url.to_string(),
+        }
+    }
+
+    fn get_page(&self, crawler: &crate::crawler::Crawler, url: &str) -> Result<String, String> {
+        Ok(crate::crawler::block_on(crawler.get_page(url, None))?.content)
+    }
+}
+impl SoftwareHub for RepoIndex {
+    fn get_name(&self) -> String {
+        match self.format {
+            RepoIndexFormat::Cgit => format!("cgit:{}", self.index_url),
+            RepoIndexFormat::Gitweb => format!("gitweb:{}", self.index_url),
+        }
+    }
+
+    fn list_projects(&self, handle_projects: &mut dyn FnMut(Vec<panbuild::projects::SoftwareProject>)) -> Result<(), String> {
+        log::info!("Getting all the repos listed at {}.", self.index_url);
+        let crawler = crate::crawler::Crawler::new(header::HeaderMap::new());
+        let index_page = self.get_page(&crawler, &self.index_url)?;
+        match self.format {
+            RepoIndexFormat::Cgit => {
+                handle_projects(parse_cgit_index(&self.index_url, &index_page));
+                // The pager of cgit links to all the other pages of the index.
+                for page_url in get_cgit_page_urls(&self.index_url, &index_page) {
+                    let page = self.get_page(&crawler, &page_url)?;
+                    handle_projects(parse_cgit_index(&self.index_url, &page));
+                }
+            }
+            RepoIndexFormat::Gitweb => handle_projects(parse_gitweb_index(&self.index_url, &index_page)),
+        };
+        Ok(())
+    }
+
+    // The index is not searchable, so the whole index has to be fetched.
+    fn get_project(&self, project_id: &str) -> Result<Option<panbuild::projects::SoftwareProject>, String> {
+        let mut found_project: Option<panbuild::projects::SoftwareProject> = None;
+        self.list_projects(&mut |projects| {
+            if found_project.is_none() {
+                found_project = projects.into_iter().find(|p| p.id == project_id);
+            }
+        })?;
+        Ok(found_project)
+    }
+}
+
+// A link found in an HTML page.
+#[derive(Debug, Default)]
+struct Link {
+    href: String,
+    title: String,
+    text: String,
+}
+
+fn unescape_html(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+// Gets the value of an attribute of an HTML tag. The value can be quoted with single or double quotes.
+fn get_attribute(tag: &str, attribute_name: &str) -> Option<String> {
+    let value_start = tag.find(&format!(" {}=", attribute_name))? + attribute_name.len() + 2;
+    let quote = tag[value_start..].chars().next()?;
+    if quote != '\'' && quote != '"' {
+        return None;
+    }
+    let value_end = tag[value_start + 1..].find(quote)? + value_start + 1;
+    Some(unescape_html(&tag[value_start + 1..value_end]))
+}
+
+// Gets all the links of an HTML fragment. The index pages are generated, so the
+// HTML is regular enough to be parsed without a complete HTML parser.
+fn get_links(html: &str) -> Vec<Link> {
+    let mut links: Vec<Link> = vec![];
+    let mut remaining_html = html;
+    while let Some(link_start) = remaining_html.find("<a ") {
+        remaining_html = &remaining_html[link_start..];
+        let tag_end = match remaining_html.find('>') {
+            Some(i) => i,
+            None => break,
+        };
+        let tag = &remaining_html[..tag_end];
+        let text_end = remaining_html.find("</a>").unwrap_or(tag_end + 1).max(tag_end + 1);
+        links.push(Link {
+            href: get_attribute(tag, "href").unwrap_or_default(),
+            title: get_attribute(tag, "title").unwrap_or_default(),
+            text: unescape_html(remaining_html[tag_end + 1..text_end].trim()),
+        });
+        remaining_html = &remaining_html[text_end..];
+    }
+    links
+}
+
+// Resolves a link of a page to an absolute url.
+fn resolve_url(page_url: &str, href: &str) -> String {
+    if href.contains("://") {
+        return href.to_string();
+    }
+    if href.starts_with('/') {
+        let scheme_end = page_url.find("://").map(|i| i + 3).unwrap_or(0);
+        let host_end = page_url[scheme_end..].find('/').map(|i| i + scheme_end).unwrap_or(page_url.len());
+        return format!("{}{}", &page_url[..host_end], href);
+    }
+    let base_url = match page_url.rfind('/') {
+        Some(i) if i > page_url.find("://").map(|i| i + 2).unwrap_or(0) => &page_url[..i + 1],
+        _ => page_url,
+    };
+    format!("{}{}", base_url, href)
+}
+
+fn get_repo_project(repo_url: &str, web_url: &str, name: &str, description: &str) -> panbuild::projects::SoftwareProject {
+    let mut project = panbuild::projects::SoftwareProject::default();
+    project.id = crate::hubs::hub::get_project_id(repo_url);
+    project.name = name.trim_end_matches(".git").to_string();
+    // The default description of the repos created with git init.
+    if !description.starts_with("Unnamed repository") {
+        project.description = description.to_string();
+    }
+    project.vcs_urls.push(repo_url.to_string());
+    project.web_urls.push(web_url.to_string());
+    project
+}
+
+// Parses the repos listed in a page of a cgit index. cgit serves the
+// repos over http at the same url as their summary page.
+pub fn parse_cgit_index(index_url: &str, page_content: &str) -> Vec<panbuild::projects::SoftwareProject> {
+    let mut projects: Vec<panbuild::projects::SoftwareProject> = vec![];
+    for row in page_content.split("<tr").skip(1) {
+        if !row.contains("toplevel-repo") && !row.contains("sublevel-repo") {
+            continue;
+        }
+        let links = get_links(row);
+        let repo_link = match links.first() {
+            Some(l) if !l.href.is_empty() => l,
+            _ => continue,
+        };
+        let repo_url = resolve_url(index_url, &repo_link.href).trim_end_matches('/').to_string();
+        let name = match repo_link.title.is_empty() {
+            true => &repo_link.text,
+            false => &repo_link.title,
+        };
+        // The name of a repo can be the path of the repo, with its section.
+        let name = name.split('/').last().unwrap_or(name);
+        let description = links.get(1).map(|l| l.text.as_str()).unwrap_or("");
+        projects.push(get_repo_project(&repo_url, &repo_url, name, description));
+    }
+    projects
+}
+
+// Gets the urls of the other pages of a cgit index, from the pager of its first page.
+pub fn get_cgit_page_urls(index_url: &str, page_content: &str) -> Vec<String> {
+    let pager = match page_content.find("class='pager'").or(page_content.find("class=\"pager\"")) {
+        Some(i) => &page_content[i..],
+        None => return vec![],
+    };
+    let pager = &pager[..pager.find("</ul>").unwrap_or(pager.len())];
+    let mut page_urls: Vec<String> = vec![];
+    for link in get_links(pager) {
+        if !link.href.contains("ofs=") {
+            continue;
+        }
+        let page_url = resolve_url(index_url, &link.href);
+        if !page_urls.contains(&page_url) {
+            page_urls.push(page_url);
+        }
+    }
+    page_urls
+}
+
+// Parses the repos listed in a gitweb index. The repos are expected to be served
+// over http from the url of the index, which is the usual gitweb setup.
+pub fn parse_gitweb_index(index_url: &str, page_content: &str) -> Vec<panbuild::projects::SoftwareProject> {
+    let mut projects: Vec<panbuild::projects::SoftwareProject> = vec![];
+    let base_url = index_url.trim_end_matches('/');
+    for row in page_content.split("<tr").skip(1) {
+        let links = get_links(row);
+        let repo_link = match links.first() {
+            Some(l) if l.href.contains("a=summary") => l,
+            _ => continue,
+        };
+        let repo_path = match repo_link.href.split(|c| c == '?' || c == ';' || c == '&').find(|p| p.starts_with("p=")) {
+            Some(p) => &p[2..],
+            None => continue,
+        };
+        let repo_url = format!("{}/{}", base_url, repo_path);
+        let web_url = resolve_url(index_url, &repo_link.href);
+        let name = repo_path.split('/').last().unwrap_or(repo_path);
+        // The full description is in the title when gitweb shortened it.
+        let description = match links.get(1) {
+            Some(l) if !l.title.is_empty() => l.title.as_str(),
+            Some(l) => l.text.as_str(),
+            None => "",
+        };
+        projects.push(get_repo_project(&repo_url, &web_url, name, description));
+    }
+    projects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_parse_cgit_index() {
+        let index_url = "https://git.savannah.gnu.org/cgit/";
+        let page_content = include_str!("../../tests/fixtures/hubs/cgit-index.html");
+        let projects = parse_cgit_index(index_url, page_content);
+        assert_eq!(projects.len(), 3);
+        assert_eq!(projects[0].name, "bash");
+        assert_eq!(projects[0].id, "org.gnu.savannah.git.cgit.bash");
+        assert_eq!(projects[0].vcs_urls, vec!["https://git.savannah.gnu.org/cgit/bash.git"]);
+        assert_eq!(projects[0].description, "GNU Bourne Again SHell");
+        assert_eq!(projects[2].name, "make");
+        assert_eq!(projects[2].description, "GNU Make & friends");
+
+        let page_urls = get_cgit_page_urls(index_url, page_content);
+        assert_eq!(
+            page_urls,
+            vec!["https://git.savannah.gnu.org/cgit/?ofs=50", "https://git.savannah.gnu.org/cgit/?ofs=100"]
+        );
+    }
+
+    #[test]
+    pub fn test_parse_gitweb_index() {
+        let index_url = "https://sourceware.org/git/";
+        let projects = parse_gitweb_index(index_url, include_str!("../../tests/fixtures/hubs/gitweb-index.html"));
+        assert_eq!(projects.len(), 2);
+        assert_eq!(projects[0].name, "binutils-gdb");
+        assert_eq!(projects[0].vcs_urls, vec!["https://sourceware.org/git/binutils-gdb.git"]);
+        assert_eq!(projects[0].web_urls, vec!["https://sourceware.org/git/?p=binutils-gdb.git;a=summary"]);
+        assert_eq!(projects[1].id, "org.sourceware.git.glibc");
+        assert_eq!(projects[1].description, "GNU C Library (glibc) main development repository");
+    }
+
+    #[test]
+    pub fn test_resolve_url() {
+        assert_eq!(
+            resolve_url("https://git.kernel.org/pub/", "/pub/scm/git/git.git/"),
+            "https://git.kernel.org/pub/scm/git/git.git/"
+        );
+        assert_eq!(
+            resolve_url("https://sourceware.org/git/", "?p=glibc.git"),
+            "https://sourceware.org/git/?p=glibc.git"
+        );
+        assert_eq!(resolve_url("https://example.com", "https://other.com/a"), "https://other.com/a");
+    }
+}
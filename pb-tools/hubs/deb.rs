@@ -1,9 +1,465 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
 use serde::{Deserialize, Serialize};
 
+use panbuild::projects::{Dependency, ProjectVersion, SoftwareProject};
+
+use crate::hubs::hub::SoftwareHub;
+
+pub const DEFAULT_SUITE: &str = "stable";
+pub const DEFAULT_MIRROR_URL: &str = "https://deb.debian.org/debian";
+pub const DEFAULT_COMPONENTS: &[&str] = &["main"];
+// The prefix of the ids of the packages without a usable Vcs-Git field.
+pub const PROJECT_ID_PREFIX: &str = "org.debian.packages.";
+
+// A source package, as described in a Sources index.
+// See https://wiki.debian.org/DebianRepository/Format#A.22Sources.22_Indices
+#[derive(Debug, Default, Clone)]
+pub struct DebianSourcePackage {
+    pub name: String,
+    pub version: String,
+    pub binaries: Vec<String>,
+    pub maintainers: Vec<String>,
+    pub homepage: String,
+    pub vcs_browser: String,
+    pub vcs_git: String,
+    // The build dependencies, with their version constraints.
+    pub build_depends: Vec<(String, String)>,
+    // The directory of the source files, relative to the root of the mirror.
+    pub directory: String,
+    // The names of the source files, with their sha256.
+    pub files: Vec<(String, String)>,
+}
+impl DebianSourcePackage {
+    pub fn from_paragraph(paragraph: &String) -> Option<DebianSourcePackage> {
+        let fields = panbuild::manifests::debian::parse_paragraph(paragraph);
+        let get_field = |field_name: &str| fields.get(field_name).map(|v| v.to_string()).unwrap_or_default();
+
+        let mut source_package = DebianSourcePackage::default();
+        source_package.name = get_field("Package");
+        source_package.version = get_field("Version");
+        if source_package.name.is_empty() || source_package.version.is_empty() {
+            return None;
+        }
+        source_package.binaries = get_field("Binary").split(',').map(|b| b.trim().to_string()).filter(|b| !b.is_empty()).collect();
+        source_package.maintainers.push(get_field("Maintainer"));
+        for uploader in get_field("Uploaders").split(">,") {
+            let uploader = uploader.trim();
+            if uploader.is_empty() {
+                continue;
+            }
+            match uploader.ends_with('>') || !uploader.contains('<') {
+                true => source_package.maintainers.push(uploader.to_string()),
+                false => source_package.maintainers.push(format!("{}>", uploader)),
+            };
+        }
+        source_package.maintainers.retain(|m| !m.is_empty());
+        source_package.homepage = get_field("Homepage");
+        source_package.vcs_browser = get_field("Vcs-Browser");
+        // The Vcs-Git field can also contain a branch (-b branch) and a subdirectory ([path]).
+        source_package.vcs_git = get_field("Vcs-Git").split_whitespace().next().unwrap_or("").to_string();
+        source_package.build_depends = panbuild::manifests::debian::parse_relationships(&get_field("Build-Depends"));
+        source_package
+            .build_depends
+            .extend(panbuild::manifests::debian::parse_relationships(&get_field("Build-Depends-Indep")));
+        source_package.directory = get_field("Directory");
+        // The lines of the field are joined, so the files are read 3 parts at a time.
+        let checksums: Vec<String> = get_field("Checksums-Sha256").split_whitespace().map(|p| p.to_string()).collect();
+        for checksum in checksums.chunks(3) {
+            if checksum.len() == 3 {
+                source_package.files.push((checksum[2].to_string(), checksum[0].to_string()));
+            }
+        }
+        Some(source_package)
+    }
+
+    pub fn get_project_id(&self) -> String {
+        // The ids are the same as the ids of the projects imported from salsa.debian.org.
+        if self.vcs_git.starts_with("https://") && self.vcs_git.ends_with(".git") {
+            return panbuild::utils::repo_url_to_reverse_dns(&self.vcs_git);
+        }
+        format!("{}{}", PROJECT_ID_PREFIX, self.name)
+    }
+
+    // Gets the version of the upstream project that is packaged.
+    pub fn get_upstream_version(&self) -> String {
+        match panbuild::version::DebianVersion::parse(&self.version) {
+            Some(v) => v.upstream_version,
+            None => self.version.to_string(),
+        }
+    }
+
+    pub fn to_software_project(&self) -> SoftwareProject {
+        let mut project = SoftwareProject::default();
+        project.id = self.get_project_id();
+        project.name = self.name.to_string();
+        project.maintainers = self.maintainers.clone();
+        project.artifact_names = self.binaries.clone();
+        for web_url in &[&self.homepage, &self.vcs_browser] {
+            if !web_url.is_empty() {
+                project.web_urls.push(web_url.to_string());
+            }
+        }
+        if !self.vcs_git.is_empty() {
+            project.vcs_urls.push(self.vcs_git.to_string());
+        }
+        project.versions.push(self.get_upstream_version());
+        project
+    }
+
+    // Gets the version of the project described by the package. The build dependencies are resolved
+    // to projects using the index of the binary packages to the projects producing them.
+    pub fn to_project_version(&self, mirror_url: &str, binary_index: &BTreeMap<String, String>) -> ProjectVersion {
+        let mut project_version = ProjectVersion::default();
+        project_version.project_id = self.get_project_id();
+        project_version.name = self.get_upstream_version();
+        // The upstream sources are in the .orig tarball, when the package is not native.
+        if let Some((file_name, sha256)) = self.files.iter().find(|(f, _)| f.contains(".orig.tar.")) {
+            project_version.url = format!("{}/{}/{}", mirror_url.trim_end_matches('/'), self.directory, file_name);
+            project_version.url_type = panbuild::modules::SourceType::Tarball;
+            project_version.sha256sum = sha256.to_string();
+        }
+        for (package_name, version_constraint) in &self.build_depends {
+            let project_id = match binary_index.get(package_name) {
+                Some(p) => p,
+                None => {
+                    log::debug!("No source package found for build dependency {} of {}.", package_name, self.name);
+                    continue;
+                }
+            };
+            if project_id == &project_version.project_id {
+                continue;
+            }
+            let version_range = match parse_version_constraint(version_constraint) {
+                Some(r) => r,
+                None => {
+                    log::warn!(
+                        "Could not parse the version constraint {} of build dependency {} of {}, ignoring it.",
+                        version_constraint,
+                        package_name,
+                        self.name
+                    );
+                    panbuild::version::VersionRange::default()
+                }
+            };
+            project_version.dependencies.push(Dependency {
+                version_range,
+                project_id: project_id.to_string(),
+            });
+        }
+        project_version
+    }
+}
+
+// Parses the version constraint of a relationship, like `>= 1:8.35-2~`. The versions of the
+// projects are upstream versions, so the epoch, the Debian revision and the pre-release or
+// repack suffixes (`~rc1`, `+dfsg`) of the version are ignored. The bounds are adjusted to
+// the stripped version, for example `<< 2.30-1~` still allows the upstream version 2.30.
+pub fn parse_version_constraint(version_constraint: &str) -> Option<panbuild::version::VersionRange> {
+    let version_start = version_constraint.find(|c: char| c.is_ascii_digit())?;
+    let (operator, version) = version_constraint.split_at(version_start);
+    let (upstream_version, debian_revision) = match panbuild::version::DebianVersion::parse(version) {
+        Some(v) => (v.upstream_version, v.debian_revision),
+        None => (version.to_string(), "".to_string()),
+    };
+    let suffix_start = upstream_version.find(['~', '+']).unwrap_or(upstream_version.len());
+    let (upstream_version, suffix) = upstream_version.split_at(suffix_start);
+    // How the version compares to the stripped version. The pre-release suffixes sort
+    // before the version, and the repack suffixes and the revisions sort after it.
+    let ordering = if suffix.starts_with('~') {
+        Ordering::Less
+    } else if !suffix.is_empty() || !debian_revision.is_empty() {
+        Ordering::Greater
+    } else {
+        Ordering::Equal
+    };
+    let operator = match (operator.trim(), ordering) {
+        ("<<", Ordering::Greater) => "<=",
+        ("<=", Ordering::Less) => "<",
+        (">>", Ordering::Greater) | (">>", Ordering::Less) => ">=",
+        (o, _) => o,
+    };
+    panbuild::version::VersionRange::parse(&format!("{} {}", operator, upstream_version))
+}
+
+// Parses all the source packages of a Sources index.
+pub fn parse_sources_index(content: &String) -> Vec<DebianSourcePackage> {
+    panbuild::manifests::debian::parse_paragraphs(content)
+        .iter()
+        .filter_map(|p| DebianSourcePackage::from_paragraph(p))
+        .collect()
+}
+
+// Indexes the ids of the projects producing each of the binary packages.
+pub fn get_binary_index(source_packages: &Vec<DebianSourcePackage>) -> BTreeMap<String, String> {
+    let mut binary_index: BTreeMap<String, String> = BTreeMap::new();
+    for source_package in source_packages {
+        for binary in &source_package.binaries {
+            binary_index.insert(binary.to_string(), source_package.get_project_id());
+        }
+    }
+    binary_index
+}
+
+fn decompress_sources_index(content: Vec<u8>, file_name: &str) -> Result<String, String> {
+    let content = match file_name.ends_with(".xz") {
+        true => {
+            let mut decompressed_content: Vec<u8> = vec![];
+            if let Err(e) = xz2::read::XzDecoder::new(&content[..]).read_to_end(&mut decompressed_content) {
+                return Err(format!("Could not decompress {}: {}", file_name, e));
+            }
+            decompressed_content
+        }
+        false => content,
+    };
+    match String::from_utf8(content) {
+        Ok(c) => Ok(c),
+        Err(e) => Err(format!("Invalid UTF-8 sequence in {}: {}", file_name, e)),
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
-pub struct DebianPackagesHub {}
+pub struct DebianPackagesHub {
+    // The suite (stable, bullseye, sid etc.) to get the packages from.
+    pub suite: String,
+    pub mirror_url: String,
+    pub components: Vec<String>,
+    // A local Sources or Sources.xz index to use instead of the indices of the mirror.
+    pub sources_path: Option<String>,
+}
 impl DebianPackagesHub {
-    pub fn get_modules_from_debian_repository(repo_name: &str, repo_sources_url: &str) -> Vec<panbuild::modules::SoftwareModule> {
-        vec![]
+    pub fn new(suite: &str) -> DebianPackagesHub {
+        DebianPackagesHub {
+            suite: suite.to_string(),
+            mirror_url: DEFAULT_MIRROR_URL.to_string(),
+            components: DEFAULT_COMPONENTS.iter().map(|c| c.to_string()).collect(),
+            sources_path: None,
+        }
+    }
+
+    pub fn from_sources_path(sources_path: &str) -> DebianPackagesHub {
+        let mut hub = DebianPackagesHub::new(DEFAULT_SUITE);
+        hub.sources_path = Some(sources_path.to_string());
+        hub
+    }
+
+    // Gets the content of the Sources indices of the hub.
+    pub fn get_sources_indices(&self) -> Result<Vec<String>, String> {
+        if let Some(sources_path) = &self.sources_path {
+            let content = match fs::read(Path::new(sources_path)) {
+                Ok(c) => c,
+                Err(e) => return Err(format!("Could not read Sources index {}: {}", sources_path, e)),
+            };
+            return Ok(vec![decompress_sources_index(content, sources_path)?]);
+        }
+
+        let mut sources_indices: Vec<String> = vec![];
+        for component in &self.components {
+            let sources_url = format!("{}/dists/{}/{}/source/Sources.xz", self.mirror_url.trim_end_matches('/'), self.suite, component);
+            log::info!("Getting Sources index at {}.", sources_url);
+            // The indices are updated daily, so they are not kept in the source cache.
+            let response = match reqwest::blocking::get(&sources_url) {
+                Ok(r) => r,
+                Err(e) => return Err(format!("Could not fetch {}: {}", sources_url, e)),
+            };
+            if !response.status().is_success() {
+                return Err(format!("Could not fetch {}: got status {}.", sources_url, response.status()));
+            }
+            let content = match response.bytes() {
+                Ok(c) => c.to_vec(),
+                Err(e) => return Err(format!("Could not read {}: {}", sources_url, e)),
+            };
+            sources_indices.push(decompress_sources_index(content, &sources_url)?);
+        }
+        Ok(sources_indices)
+    }
+
+    pub fn get_source_packages(&self) -> Result<Vec<DebianSourcePackage>, String> {
+        let mut source_packages: Vec<DebianSourcePackage> = vec![];
+        for sources_index in self.get_sources_indices()? {
+            source_packages.extend(parse_sources_index(&sources_index));
+        }
+        Ok(source_packages)
+    }
+
+    // Imports all the source packages of the hub in the db, with their versions and
+    // build dependencies. The packages are merged with the projects already in the db.
+    pub fn import(&self, db: &mut panbuild::db::Database) -> Result<(), String> {
+        let source_packages = self.get_source_packages()?;
+        let binary_index = get_binary_index(&source_packages);
+        let mut versions: BTreeMap<String, Vec<ProjectVersion>> = BTreeMap::new();
+        for source_package in &source_packages {
+            let project = source_package.to_software_project();
+            versions
+                .entry(project.id.to_string())
+                .or_insert(vec![])
+                .push(source_package.to_project_version(&self.mirror_url, &binary_index));
+            if db.has_project(&project.id) {
+                db.update_project(&project);
+            } else {
+                db.add_project(project);
+            }
+        }
+        for (project_id, project_versions) in versions {
+            db.add_project_versions(&project_id, project_versions);
+        }
+        log::info!("Imported {} source packages from {}.", source_packages.len(), self.get_name());
+        Ok(())
+    }
+
+    // Gets the modules of all the source packages of a local Sources or Sources.xz index.
+    pub fn get_modules_from_debian_repository(repo_name: &str, sources_path: &str) -> Vec<panbuild::modules::SoftwareModule> {
+        let hub = DebianPackagesHub::from_sources_path(sources_path);
+        let source_packages = match hub.get_source_packages() {
+            Ok(p) => p,
+            Err(e) => {
+                log::error!("Could not get the packages of {}: {}", repo_name, e);
+                return vec![];
+            }
+        };
+        let binary_index = get_binary_index(&source_packages);
+        source_packages
+            .iter()
+            .map(|p| p.to_project_version(&hub.mirror_url, &binary_index).to_module(&p.name))
+            .filter(|m| !m.url.is_empty())
+            .collect()
+    }
+}
+impl SoftwareHub for DebianPackagesHub {
+    fn get_name(&self) -> String {
+        match &self.sources_path {
+            Some(sources_path) => format!("debian:{}", sources_path),
+            None => format!("debian:{}", self.suite),
+        }
+    }
+
+    fn list_projects(&self, handle_projects: &mut dyn FnMut(Vec<SoftwareProject>)) -> Result<(), String> {
+        let source_packages = self.get_source_packages()?;
+        handle_projects(source_packages.iter().map(|p| p.to_software_project()).collect());
+        Ok(())
+    }
+
+    fn get_project(&self, project_id: &str) -> Result<Option<SoftwareProject>, String> {
+        let source_packages = self.get_source_packages()?;
+        Ok(source_packages
+            .iter()
+            .find(|p| p.get_project_id() == project_id)
+            .map(|p| p.to_software_project()))
+    }
+
+    fn get_versions(&self, project: &SoftwareProject) -> Result<Vec<ProjectVersion>, String> {
+        let source_packages = self.get_source_packages()?;
+        let binary_index = get_binary_index(&source_packages);
+        Ok(source_packages
+            .iter()
+            .filter(|p| p.get_project_id() == project.id)
+            .map(|p| p.to_project_version(&self.mirror_url, &binary_index))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCES_INDEX_EXAMPLE: &str = r###"Package: glib2.0
+Binary: libglib2.0-0, libglib2.0-dev, libglib2.0-bin
+Version: 2.66.8-1
+Maintainer: Debian GNOME Maintainers <pkg-gnome-maintainers@lists.alioth.debian.org>
+Uploaders: Iain Lane <laney@debian.org>, Simon McVittie <smcv@debian.org>
+Build-Depends: debhelper-compat (= 13), libffi-dev (>= 3.3), libpcre3-dev (>= 1:8.35), meson (>= 0.52.0)
+Homepage: https://wiki.gnome.org/Projects/GLib
+Vcs-Browser: https://salsa.debian.org/gnome-team/glib
+Vcs-Git: https://salsa.debian.org/gnome-team/glib.git -b debian/bullseye
+Directory: pool/main/g/glib2.0
+Checksums-Sha256:
+ 3f5a0e14f4e6a05bbb86cf0ce49c4d8bfe65154b1d3d5a6e4cc4ce48cd12b4ad 3419 glib2.0_2.66.8-1.dsc
+ 97bc87dd91365589af5cbbfea2574833aea7a1b71840fd365ecd2852c76b9c8b 4844932 glib2.0_2.66.8.orig.tar.xz
+ a46e2d0b3e5c1a2b4b7df88b87f4e6c4d4c8c6a9c0fe3e8dd8b3a8e9c1f3a1b2 102348 glib2.0_2.66.8-1.debian.tar.xz
+
+Package: libffi
+Binary: libffi7, libffi-dev
+Version: 3.3-6
+Maintainer: Debian GCC Maintainers <debian-gcc@lists.debian.org>
+Build-Depends: debhelper (>= 10), dpkg-dev (>= 1.16.1)
+Homepage: https://sourceware.org/libffi/
+Directory: pool/main/libf/libffi
+"###;
+
+    #[test]
+    pub fn test_parse_sources_index() {
+        let source_packages = parse_sources_index(&SOURCES_INDEX_EXAMPLE.to_string());
+        assert_eq!(source_packages.len(), 2);
+
+        let glib = &source_packages[0];
+        assert_eq!(glib.name, "glib2.0");
+        assert_eq!(glib.vcs_git, "https://salsa.debian.org/gnome-team/glib.git");
+        assert_eq!(glib.maintainers.len(), 3);
+        assert_eq!(glib.maintainers[2], "Simon McVittie <smcv@debian.org>");
+        assert_eq!(glib.files.len(), 3);
+        assert_eq!(glib.get_upstream_version(), "2.66.8");
+
+        let project = glib.to_software_project();
+        assert_eq!(project.id, "org.debian.salsa.gnome-team.glib");
+        assert_eq!(project.web_urls.len(), 2);
+        assert_eq!(project.artifact_names.len(), 3);
+
+        assert_eq!(source_packages[1].get_project_id(), "org.debian.packages.libffi");
+    }
+
+    #[test]
+    pub fn test_to_project_version() {
+        let source_packages = parse_sources_index(&SOURCES_INDEX_EXAMPLE.to_string());
+        let binary_index = get_binary_index(&source_packages);
+        let project_version = source_packages[0].to_project_version(DEFAULT_MIRROR_URL, &binary_index);
+        assert_eq!(project_version.name, "2.66.8");
+        assert_eq!(
+            project_version.url,
+            "https://deb.debian.org/debian/pool/main/g/glib2.0/glib2.0_2.66.8.orig.tar.xz"
+        );
+        assert!(project_version.sha256sum.starts_with("97bc87dd"));
+        // Only libffi-dev is produced by a known source package.
+        assert_eq!(project_version.dependencies.len(), 1);
+        assert_eq!(project_version.dependencies[0].project_id, "org.debian.packages.libffi");
+    }
+
+    #[test]
+    pub fn test_parse_version_constraint() {
+        let version_range = parse_version_constraint(">= 1:8.35").unwrap();
+        assert_eq!(version_range.min_version, Some(panbuild::version::SemanticVersion::new(8, 35, 0)));
+        assert!(version_range.min_inclusive);
+        let version_range = parse_version_constraint("<< 2.30-1~").unwrap();
+        assert_eq!(version_range.max_version, Some(panbuild::version::SemanticVersion::new(2, 30, 0)));
+        assert!(version_range.max_inclusive);
+        let version_range = parse_version_constraint("<< 2.30~rc1").unwrap();
+        assert!(!version_range.max_inclusive);
+        let version_range = parse_version_constraint("<< 2.30").unwrap();
+        assert!(!version_range.max_inclusive);
+        let version_range = parse_version_constraint(">> 1.2-3").unwrap();
+        assert!(version_range.min_inclusive);
+        let version_range = parse_version_constraint(">= 5.2.1+dfsg-1").unwrap();
+        assert_eq!(version_range.min_version, Some(panbuild::version::SemanticVersion::new(5, 2, 1)));
+        assert!(parse_version_constraint("").is_none());
+    }
+
+    #[test]
+    pub fn test_read_local_sources_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let sources_path = dir.path().join("Sources.xz");
+        let mut compressed_content: Vec<u8> = vec![];
+        xz2::read::XzEncoder::new(SOURCES_INDEX_EXAMPLE.as_bytes(), 6)
+            .read_to_end(&mut compressed_content)
+            .unwrap();
+        fs::write(&sources_path, compressed_content).unwrap();
+
+        let hub = DebianPackagesHub::from_sources_path(sources_path.to_str().unwrap());
+        let source_packages = hub.get_source_packages().unwrap();
+        assert_eq!(source_packages.len(), 2);
+        let project = hub.get_project("org.debian.packages.libffi").unwrap().unwrap();
+        assert_eq!(project.versions, vec!["3.3"]);
     }
 }
@@ -0,0 +1,205 @@
+use std::env;
+
+use reqwest::header;
+
+use serde::{Deserialize, Serialize};
+
+use crate::hubs::hub::SoftwareHub;
+
+// The known public Gitea and Forgejo instances, with the name of the environment variable
+// containing their API token. The token is optional, since the repos can be listed anonymously.
+pub const KNOWN_INSTANCES: &[(&str, &str)] = &[("codeberg.org", "PB_CODEBERG_TOKEN"), ("gitea.com", "PB_GITEA_TOKEN")];
+
+// The maximum page size of the Gitea API.
+const PAGE_SIZE: usize = 50;
+
+// Hub for the instances of Gitea, and of Forgejo which is a fork of Gitea with the same API.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Gitea {
+    pub domain: String,
+    // The name of the environment variable containing the API token for the instance.
+    pub token_env_var_name: String,
+}
+impl Gitea {
+    pub fn new(domain: &str) -> Gitea {
+        Gitea {
+            domain: domain.to_string(),
+            token_env_var_name: get_token_env_var_name(domain),
+        }
+    }
+
+    fn get_token(&self) -> Option<String> {
+        env::var(&self.token_env_var_name).ok()
+    }
+
+    fn get_api_url(&self) -> String {
+        format!("{}/api/v1", crate::hubs::gitlab::get_base_url(&self.domain))
+    }
+}
+impl SoftwareHub for Gitea {
+    fn get_name(&self) -> String {
+        format!("gitea:{}", self.domain)
+    }
+
+    fn list_projects(&self, handle_projects: &mut dyn FnMut(Vec<panbuild::projects::SoftwareProject>)) -> Result<(), String> {
+        log::info!("Getting all projects from Gitea instance at {}.", self.domain);
+        let request = panbuild::utils::PagedRequest {
+            domain: self.domain.to_string(),
+            token: self.get_token(),
+            next_page_url: Some(format!("{}/repos/search?limit={}&page=1", self.get_api_url(), PAGE_SIZE)),
+        };
+        let mut crawler = crate::crawler::Crawler::new(header::HeaderMap::new());
+        let checkpoint_name = format!("gitea-{}", self.domain.replace(|c: char| !c.is_ascii_alphanumeric() && c != '.', "-"));
+        crawler.checkpoint_path = Some(crate::crawler::get_checkpoint_path(&checkpoint_name));
+        crate::crawler::block_on(crawler.crawl(vec![request], parse_search_results, |projects| handle_projects(projects)))
+    }
+
+    fn get_project(&self, project_id: &str) -> Result<Option<panbuild::projects::SoftwareProject>, String> {
+        let full_name = match get_full_name(&self.domain, project_id) {
+            Some(n) => n,
+            None => return Ok(None),
+        };
+        let crawler = crate::crawler::Crawler::new(header::HeaderMap::new());
+        let token = self.get_token();
+        let repo_url = format!("{}/repos/{}", self.get_api_url(), full_name);
+        let page = match crate::crawler::block_on(crawler.get_page_if_exists(&repo_url, token.as_deref()))? {
+            Some(p) => p,
+            None => return Ok(None),
+        };
+        let gitea_repo: GiteaRepo = match serde_json::from_str(&page.content) {
+            Ok(r) => r,
+            Err(e) => return Err(format!("Could not parse Gitea repo {}: {}.", full_name, e)),
+        };
+        Ok(Some(gitea_repo.to_software_project()))
+    }
+}
+
+// Gets the name of the environment variable containing the API token of an instance.
+// Instances that are not known use a variable derived from their domain, for example
+// PB_GITEA_TOKEN_GIT_EXAMPLE_COM for git.example.com.
+pub fn get_token_env_var_name(domain: &str) -> String {
+    if let Some((_, token_env_var_name)) = KNOWN_INSTANCES.iter().find(|(d, _)| *d == domain) {
+        return token_env_var_name.to_string();
+    }
+    let domain = domain.split("://").last().unwrap_or(domain);
+    let domain: String = domain
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    format!("PB_GITEA_TOKEN_{}", domain)
+}
+
+// Gets the full name (owner/repo) of a repo from its project id. Gitea
+// repos are always owned directly by a user or an organization.
+fn get_full_name(domain: &str, project_id: &str) -> Option<String> {
+    let domain = domain.split("://").last().unwrap_or(domain);
+    let reversed_domain: Vec<&str> = domain.split('.').rev().collect();
+    let full_name = project_id.strip_prefix(&format!("{}.", reversed_domain.join(".")))?;
+    Some(full_name.replacen('.', "/", 1))
+}
+
+// See https://try.gitea.io/api/swagger#/repository/repoSearch
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GiteaSearchResults {
+    pub ok: bool,
+    pub data: Vec<GiteaRepo>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GiteaRepo {
+    pub id: i64,
+    pub name: String,
+    pub full_name: String,
+    pub description: String,
+    pub fork: bool,
+    pub mirror: bool,
+    pub archived: bool,
+    pub html_url: String,
+    pub clone_url: String,
+    // The url of the repo that is mirrored, if the repo is a mirror.
+    pub original_url: Option<String>,
+    pub website: Option<String>,
+    pub default_branch: Option<String>,
+    pub language: Option<String>,
+    pub topics: Option<Vec<String>>,
+}
+impl GiteaRepo {
+    pub fn to_software_project(self) -> panbuild::projects::SoftwareProject {
+        let mut project = panbuild::projects::SoftwareProject::default();
+        project.id = crate::hubs::hub::get_project_id(&self.clone_url);
+        project.name = self.name;
+        project.description = self.description;
+        project.default_branch = self.default_branch;
+        project.vcs_urls.push(self.clone_url);
+        if let Some(original_url) = self.original_url {
+            if original_url.starts_with("https://") {
+                project.vcs_urls.push(original_url);
+            }
+        }
+        project.web_urls.push(self.html_url);
+        if let Some(website) = self.website {
+            if !website.is_empty() {
+                project.web_urls.push(website);
+            }
+        }
+        if let Some(topics) = self.topics {
+            project.keywords = topics;
+        }
+        project.is_archived = self.archived;
+        project.is_fork = self.fork;
+        project.is_mirror = self.mirror;
+        if let Some(language) = self.language {
+            if !language.is_empty() {
+                project.set_languages(vec![language]);
+            }
+        }
+        project
+    }
+}
+
+// Parses a page of repos returned by the search API of Gitea. Forks are ignored.
+pub fn parse_search_results(page_content: &str) -> Result<Vec<panbuild::projects::SoftwareProject>, String> {
+    let search_results: GiteaSearchResults = match serde_json::from_str(page_content) {
+        Ok(r) => r,
+        Err(e) => return Err(format!("Could not parse Gitea repos {}.", e)),
+    };
+    if !search_results.ok {
+        return Err("The Gitea API returned an error.".to_string());
+    }
+
+    let mut projects: Vec<panbuild::projects::SoftwareProject> = vec![];
+    for gitea_repo in search_results.data {
+        if gitea_repo.fork {
+            log::debug!("Skipping forked repo {}.", &gitea_repo.full_name);
+            continue;
+        }
+        log::debug!("Adding Gitea repo {}.", gitea_repo.full_name);
+        projects.push(gitea_repo.to_software_project());
+    }
+    Ok(projects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_parse_search_results() {
+        let projects = parse_search_results(include_str!("../../tests/fixtures/hubs/gitea-repos-search.json")).unwrap();
+        assert_eq!(projects.len(), 2);
+        assert_eq!(projects[0].id, "org.codeberg.forgejo.forgejo");
+        assert_eq!(projects[0].vcs_urls, vec!["https://codeberg.org/forgejo/forgejo.git"]);
+        assert_eq!(projects[0].web_urls, vec!["https://codeberg.org/forgejo/forgejo", "https://forgejo.org"]);
+        assert_eq!(projects[0].keywords, vec!["forge", "git"]);
+        assert_eq!(projects[1].name, "scdoc");
+        assert!(projects[1].is_archived);
+        assert_eq!(projects[1].vcs_urls[1], "https://git.sr.ht/~sircmpwn/scdoc");
+    }
+
+    #[test]
+    pub fn test_get_full_name() {
+        assert_eq!(get_full_name("codeberg.org", "org.codeberg.forgejo.forgejo").unwrap(), "forgejo/forgejo");
+        assert_eq!(get_full_name("codeberg.org", "org.codeberg.louib.panbuild.rs").unwrap(), "louib/panbuild.rs");
+        assert!(get_full_name("codeberg.org", "com.github.louib.panbuild").is_none());
+    }
+}
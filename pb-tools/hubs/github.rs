@@ -1,9 +1,11 @@
+use std::collections::BTreeMap;
 use std::env;
 
-use futures::executor::block_on;
 use reqwest::header;
 use serde::{Deserialize, Serialize};
 
+use crate::hubs::hub::SoftwareHub;
+
 // See https://docs.github.com/en/rest/reference/repos
 #[derive(Debug, Serialize, Deserialize)]
 struct GitHubRepo {
@@ -19,11 +21,16 @@ struct GitHubRepo {
     clone_url: Option<String>,
     git_url: Option<String>,
     homepage: Option<String>,
+    // The url of the repo that is mirrored, if the repo is a mirror.
+    mirror_url: Option<String>,
     forks_count: Option<i64>,
     stargazers_count: Option<i64>,
     watchers_count: Option<i64>,
     size: Option<i64>,
     default_branch: Option<String>,
+    license: Option<GitHubLicense>,
+    // The main language of the repo. All the languages are available at /repos/{owner}/{repo}/languages.
+    language: Option<String>,
 }
 impl GitHubRepo {
     pub fn to_software_project(self) -> panbuild::projects::SoftwareProject {
@@ -34,93 +41,178 @@ impl GitHubRepo {
         project.default_branch = self.default_branch;
         project.description = self.description;
         project.vcs_urls.push(git_url);
+        if let Some(homepage) = self.homepage {
+            if !homepage.is_empty() {
+                project.web_urls.push(homepage);
+            }
+        }
         if let Some(topics) = self.topics {
             project.keywords = topics;
         }
+        if let Some(license) = self.license {
+            if let Some(spdx_id) = license.spdx_id {
+                project.license = Some(panbuild::manifests::manifest::License::from_spdx_id(&spdx_id));
+            }
+        }
+        project.is_archived = self.archived.unwrap_or(false);
+        project.is_fork = self.fork;
+        project.is_mirror = self.mirror_url.is_some();
+        if let Some(language) = self.language {
+            project.set_languages(vec![language]);
+        }
         project
     }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct GitHub {}
+struct GitHubLicense {
+    key: String,
+    spdx_id: Option<String>,
+}
 
+// See https://docs.github.com/en/rest/reference/repos#releases
 #[derive(Debug, Serialize, Deserialize)]
-pub struct GitHubError {
-    pub message: String,
-    pub documentation_url: String,
+struct GitHubRelease {
+    tag_name: String,
+    draft: bool,
+    prerelease: bool,
+    tarball_url: Option<String>,
+    assets: Vec<GitHubReleaseAsset>,
 }
-
-pub fn get_org_repos(org_name: &str) -> Vec<panbuild::projects::SoftwareProject> {
-    let mut paged_response = get_repos(panbuild::utils::PagedRequest {
-        domain: "".to_string(),
-        token: None,
-        next_page_url: Some(format!("https://api.github.com/orgs/{}/repos?type=all&per_page=100", org_name)),
-    });
-    let mut all_projects = vec![];
-    let mut projects = paged_response.results;
-    while projects.len() > 0 {
-        for project in projects {
-            log::info!("Adding project {}.", &project.name);
-            all_projects.push(project);
+impl GitHubRelease {
+    // Gets the url of the archive of the release. The archives uploaded with the release are
+    // preferred, since the archives generated by GitHub are not guaranteed to be stable.
+    pub fn get_archive_url(&self) -> Option<String> {
+        for asset in &self.assets {
+            if ARCHIVE_EXTENSIONS.iter().any(|e| asset.name.ends_with(e)) {
+                return Some(asset.browser_download_url.to_string());
+            }
         }
+        self.tarball_url.clone()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GitHubReleaseAsset {
+    name: String,
+    browser_download_url: String,
+}
 
-        if paged_response.next_page_url.is_none() {
-            break;
+const ARCHIVE_EXTENSIONS: &[&str] = &[".tar.xz", ".tar.gz", ".tar.bz2", ".tgz", ".zip"];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitHub {
+    // Only lists the repos of that organization, instead of all the repos of GitHub.
+    pub org: Option<String>,
+}
+impl SoftwareHub for GitHub {
+    fn get_name(&self) -> String {
+        match &self.org {
+            Some(org) => format!("github:{}", org),
+            None => "github".to_string(),
         }
+    }
 
-        paged_response = get_repos(panbuild::utils::PagedRequest {
+    fn list_projects(&self, handle_projects: &mut dyn FnMut(Vec<panbuild::projects::SoftwareProject>)) -> Result<(), String> {
+        let mut crawler = get_crawler();
+        let first_page_url = match &self.org {
+            Some(org) => format!("{}/orgs/{}/repos?type=all&per_page=100", get_api_url(), org),
+            None => {
+                log::info!("Getting all projects from github.com");
+                // Crawling all of GitHub takes a while, so that crawl has to be resumable.
+                crawler.checkpoint_path = Some(crate::crawler::get_checkpoint_path("github"));
+                format!("{}/repositories?type=all&per_page=100", get_api_url())
+            }
+        };
+        let request = panbuild::utils::PagedRequest {
             domain: "".to_string(),
             token: None,
-            next_page_url: paged_response.next_page_url,
-        });
-        projects = paged_response.results;
+            next_page_url: Some(first_page_url),
+        };
+        crate::crawler::block_on(crawler.crawl(vec![request], parse_repos, |projects| handle_projects(projects)))
     }
-    all_projects
-}
 
-pub fn get_and_add_repos(db: &mut panbuild::db::Database) {
-    log::info!("Getting all projects from github.com");
-    let mut request = panbuild::utils::PagedRequest {
-        domain: "".to_string(),
-        token: None,
-        next_page_url: None,
-    };
-    let mut paged_response = get_repos(request);
-
-    let mut projects = paged_response.results;
-    while projects.len() > 0 {
-        for project in projects {
-            log::info!("Adding project {}.", &project.name);
-            db.add_project(project);
+    fn get_project(&self, project_id: &str) -> Result<Option<panbuild::projects::SoftwareProject>, String> {
+        let full_name = match get_full_name(project_id) {
+            Some(n) => n,
+            None => return Ok(None),
+        };
+        let crawler = get_crawler();
+        let page = crate::crawler::block_on(crawler.get_page_if_exists(&format!("{}/repos/{}", get_api_url(), full_name), None))?;
+        let page = match page {
+            Some(p) => p,
+            None => return Ok(None),
+        };
+        let github_repo: GitHubRepo = match serde_yaml::from_str(&page.content) {
+            Ok(r) => r,
+            Err(e) => return Err(format!("Could not parse GitHub repo {}: {}.", full_name, e)),
+        };
+        let mut project = github_repo.to_software_project();
+        if let Err(e) = self.fill_project_details(&mut project) {
+            log::warn!("Could not get the languages of GitHub repo {}: {}", full_name, e);
         }
+        Ok(Some(project))
+    }
 
-        if paged_response.next_page_url.is_none() {
-            break;
+    // The listed repos only have their main language.
+    fn fill_project_details(&self, project: &mut panbuild::projects::SoftwareProject) -> Result<(), String> {
+        let full_name = match get_full_name(&project.id) {
+            Some(n) => n,
+            None => return Ok(()),
+        };
+        let crawler = get_crawler();
+        let languages_url = format!("{}/repos/{}/languages", get_api_url(), full_name);
+        if let Some(page) = crate::crawler::block_on(crawler.get_page_if_exists(&languages_url, None))? {
+            project.set_languages(parse_languages(&page.content)?);
         }
+        Ok(())
+    }
 
-        paged_response = get_repos(panbuild::utils::PagedRequest {
-            domain: "".to_string(),
-            token: paged_response.token,
-            next_page_url: paged_response.next_page_url,
-        });
-        projects = paged_response.results;
+    // The versions are the releases of the repo, with their archive. The tags
+    // of the repo are used if the repo does not publish releases.
+    fn get_versions(&self, project: &panbuild::projects::SoftwareProject) -> Result<Vec<panbuild::projects::ProjectVersion>, String> {
+        let full_name = match get_full_name(&project.id) {
+            Some(n) => n,
+            None => return Ok(vec![]),
+        };
+        let crawler = get_crawler();
+        let releases_url = format!("{}/repos/{}/releases?per_page=100", get_api_url(), full_name);
+        let page = match crate::crawler::block_on(crawler.get_page_if_exists(&releases_url, None))? {
+            Some(p) => p,
+            None => return Ok(vec![]),
+        };
+        let versions = parse_releases(&project.id, &page.content)?;
+        if !versions.is_empty() {
+            return Ok(versions);
+        }
+        let repo_url = format!("https://github.com/{}.git", full_name);
+        let tags = panbuild::utils::get_remote_git_repo_tags(&repo_url)?;
+        Ok(panbuild::projects::get_versions_from_tags(&project.id, &repo_url, tags))
     }
 }
 
-pub fn get_repos(request: panbuild::utils::PagedRequest) -> panbuild::utils::PagedResponse {
-    // By default, we get all the repos.
-    let mut current_url = format!("https://api.github.com/repositories?type=all&per_page=2");
-    if let Some(url) = request.next_page_url {
-        current_url = url;
-    }
+// Gets the full name (owner/repo) of a repo from its project id. GitHub user and
+// organization names can't contain dots, so the first part of the id after the
+// domain is the owner.
+fn get_full_name(project_id: &str) -> Option<String> {
+    Some(project_id.strip_prefix("com.github.")?.replacen('.', "/", 1))
+}
 
-    let mut projects: Vec<panbuild::projects::SoftwareProject> = vec![];
-    let default_response = panbuild::utils::PagedResponse {
-        results: vec![],
-        token: None,
-        next_page_url: None,
-    };
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitHubError {
+    pub message: String,
+    pub documentation_url: String,
+}
+
+// The base url of the GitHub API, which can be replaced for testing.
+pub fn get_api_url() -> String {
+    if let Ok(url) = env::var("PB_GITHUB_API_URL") {
+        return url.trim_end_matches('/').to_string();
+    }
+    "https://api.github.com".to_string()
+}
 
+fn get_crawler() -> crate::crawler::Crawler {
     let mut headers = header::HeaderMap::new();
     // User agent is required when using the GitHub API.
     // See https://docs.github.com/en/rest/overview/resources-in-the-rest-api#user-agent-required
@@ -132,60 +224,159 @@ pub fn get_repos(request: panbuild::utils::PagedRequest) -> panbuild::utils::Pag
     } else {
         log::warn!("No GitHub API token located at PB_GITHUB_TOKEN. We will get rate limited faster.");
     }
+    crate::crawler::Crawler::new(headers)
+}
 
-    let client = reqwest::blocking::Client::builder().default_headers(headers).build().unwrap();
-
-    log::info!("Getting GitHub projects page at {}.", current_url);
-    // TODO make this really asynchronous with async/await.
-    let response = match client.get(&current_url).send() {
-        Ok(r) => r,
-        Err(e) => {
-            log::error!("Could not fetch GitHub url {}: {}.", current_url, e);
-            return default_response;
-        }
+pub fn get_org_repos(org_name: &str) -> Vec<panbuild::projects::SoftwareProject> {
+    let github_org = GitHub {
+        org: Some(org_name.to_string()),
     };
-
-    if response.status().as_u16() == 204 {
-        return default_response;
+    let mut all_projects = vec![];
+    let result = github_org.list_projects(&mut |projects| {
+        for project in projects {
+            log::info!("Adding project {}.", &project.name);
+            all_projects.push(project);
+        }
+    });
+    if let Err(e) = result {
+        log::error!("Could not get all the repos of GitHub org {}: {}", org_name, e);
     }
+    all_projects
+}
 
-    if response.status().as_u16() > 399 {
-        let error_object: GitHubError = match serde_yaml::from_str(&response.text().unwrap()) {
-            Ok(e) => e,
-            Err(e) => {
-                log::error!("Could not parse GitHub error {}.", e);
-                return default_response;
-            }
-        };
-        log::error!("Error returned by the GitHub API: {}", error_object.message);
-        return default_response;
-    }
+// Parses the languages of a repo, which are returned with the number of bytes of code
+// written in each language.
+pub fn parse_languages(page_content: &str) -> Result<Vec<String>, String> {
+    let languages: BTreeMap<String, u64> = match serde_json::from_str(page_content) {
+        Ok(l) => l,
+        Err(e) => return Err(format!("Could not parse GitHub languages {}.", e)),
+    };
+    let mut languages: Vec<(String, u64)> = languages.into_iter().collect();
+    languages.sort_by(|(_, a), (_, b)| b.cmp(a));
+    Ok(languages.into_iter().map(|(l, _)| l).collect())
+}
 
-    let link_header = match &response.headers().get("link") {
-        Some(h) => h.to_str().unwrap(),
-        None => "",
+// Parses the releases of a repo into versions of the project. Drafts and pre-releases are ignored.
+pub fn parse_releases(project_id: &str, page_content: &str) -> Result<Vec<panbuild::projects::ProjectVersion>, String> {
+    let releases: Vec<GitHubRelease> = match serde_json::from_str(page_content) {
+        Ok(r) => r,
+        Err(e) => return Err(format!("Could not parse GitHub releases {}.", e)),
     };
-    let next_page_url = panbuild::utils::get_next_page_url(link_header);
+    let mut versions: Vec<panbuild::projects::ProjectVersion> = vec![];
+    for release in releases {
+        if release.draft || release.prerelease {
+            continue;
+        }
+        let archive_url = match release.get_archive_url() {
+            Some(u) => u,
+            None => continue,
+        };
+        if let Some(version) = panbuild::projects::get_release_version(project_id, &release.tag_name, &archive_url) {
+            versions.push(version);
+        }
+    }
+    Ok(versions)
+}
 
-    let response_content = response.text().unwrap();
-    let github_repos: Vec<GitHubRepo> = match serde_yaml::from_str(&response_content) {
+// Parses a page of repos returned by the GitHub API. Forks are kept, but flagged as such.
+pub fn parse_repos(page_content: &str) -> Result<Vec<panbuild::projects::SoftwareProject>, String> {
+    let github_repos: Vec<GitHubRepo> = match serde_yaml::from_str(page_content) {
         Ok(p) => p,
         Err(e) => {
-            log::error!("Could not parse GitHub repos {}.", e);
-            return default_response;
+            // The error messages of the API are not returned as a list.
+            if let Ok(error_object) = serde_yaml::from_str::<GitHubError>(page_content) {
+                return Err(format!("Error returned by the GitHub API: {}", error_object.message));
+            }
+            return Err(format!("Could not parse GitHub repos {}.", e));
         }
     };
+
+    let mut projects: Vec<panbuild::projects::SoftwareProject> = vec![];
     for github_project in github_repos {
-        if github_project.fork {
-            continue;
-        }
         log::debug!("Adding GitHub repo {}.", github_project.name);
         projects.push(github_project.to_software_project());
     }
+    Ok(projects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_parse_repos() {
+        let projects = parse_repos(
+            r###"[
+              {
+                "id": 1296269,
+                "name": "panbuild",
+                "full_name": "louib/panbuild",
+                "description": "The universal build manifest converter.",
+                "fork": false,
+                "archived": true,
+                "homepage": "https://panbuild.org",
+                "language": "Rust",
+                "license": {"key": "gpl-3.0", "spdx_id": "GPL-3.0"}
+              },
+              {
+                "id": 1296270,
+                "name": "panbuild",
+                "full_name": "someone/panbuild",
+                "description": "",
+                "fork": true
+              }
+            ]"###,
+        )
+        .unwrap();
+        assert_eq!(projects.len(), 2);
+        assert_eq!(projects[0].id, "com.github.louib.panbuild");
+        assert_eq!(projects[0].license, Some(panbuild::manifests::manifest::License::Gpl3));
+        assert!(projects[0].is_archived);
+        assert!(!projects[0].is_fork);
+        assert_eq!(projects[0].web_urls, vec!["https://panbuild.org"]);
+        assert_eq!(projects[0].build_systems, vec!["cargo"]);
+        assert_eq!(projects[1].id, "com.github.someone.panbuild");
+        assert!(projects[1].is_fork);
+    }
 
-    panbuild::utils::PagedResponse {
-        results: projects,
-        token: None,
-        next_page_url: next_page_url,
+    #[test]
+    pub fn test_parse_releases() {
+        let versions = parse_releases(
+            "com.github.louib.panbuild",
+            r###"[
+              {
+                "tag_name": "v0.2.0",
+                "draft": false,
+                "prerelease": false,
+                "tarball_url": "https://api.github.com/repos/louib/panbuild/tarball/v0.2.0",
+                "assets": [
+                  {"name": "panbuild-0.2.0.tar.xz", "browser_download_url": "https://github.com/louib/panbuild/releases/download/v0.2.0/panbuild-0.2.0.tar.xz"}
+                ]
+              },
+              {
+                "tag_name": "v0.1.0",
+                "draft": false,
+                "prerelease": false,
+                "tarball_url": "https://api.github.com/repos/louib/panbuild/tarball/v0.1.0",
+                "assets": []
+              },
+              {
+                "tag_name": "v0.3.0-beta",
+                "draft": false,
+                "prerelease": true,
+                "tarball_url": "https://api.github.com/repos/louib/panbuild/tarball/v0.3.0-beta",
+                "assets": []
+              }
+            ]"###,
+        )
+        .unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].name, "0.2.0");
+        assert_eq!(
+            versions[0].url,
+            "https://github.com/louib/panbuild/releases/download/v0.2.0/panbuild-0.2.0.tar.xz"
+        );
+        assert_eq!(versions[1].tag, "v0.1.0");
+        assert_eq!(versions[1].url, "https://api.github.com/repos/louib/panbuild/tarball/v0.1.0");
     }
 }
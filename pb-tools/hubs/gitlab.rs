@@ -1,11 +1,181 @@
+use std::collections::BTreeMap;
 use std::env;
 
 use reqwest::header;
 
 use serde::{Deserialize, Serialize};
 
+use crate::hubs::hub::SoftwareHub;
+
+// The known public GitLab instances, with the name of the environment variable containing
+// their API token. There is a list of all the public GitLab instances hosted here
+// https://wiki.p2pfoundation.net/List_of_Community-Hosted_GitLab_Instances
+pub const KNOWN_INSTANCES: &[(&str, &str)] = &[
+    ("gitlab.com", "PB_GITLAB_TOKEN"),
+    ("gitlab.gnome.org", "PB_GNOME_GITLAB_TOKEN"),
+    ("source.puri.sm", "PB_PURISM_GITLAB_TOKEN"),
+    ("salsa.debian.org", "PB_DEBIAN_GITLAB_TOKEN"),
+    // KDE was recently migrated to GitLab.
+    // See https://gitlab.com/gitlab-org/gitlab-foss/-/issues/53206 for details.
+    ("invent.kde.org", "PB_KDE_GITLAB_TOKEN"),
+    ("code.videolan.org", "PB_VLC_GITLAB_TOKEN"),
+    ("gitlab.haskell.org", "PB_HASKELL_GITLAB_TOKEN"),
+    ("devel.trisquel.info", "PB_TRISQUEL_GITLAB_TOKEN"),
+    ("gitlab.freedesktop.org", "PB_XDG_GITLAB_TOKEN"),
+];
+
 #[derive(Debug, Serialize, Deserialize)]
-pub struct GitLab {}
+pub struct GitLab {
+    pub domain: String,
+    // The name of the environment variable containing the API token for the instance.
+    pub token_env_var_name: String,
+}
+impl GitLab {
+    pub fn new(domain: &str) -> GitLab {
+        GitLab {
+            domain: domain.to_string(),
+            token_env_var_name: get_token_env_var_name(domain),
+        }
+    }
+
+    fn get_token(&self) -> Option<String> {
+        env::var(&self.token_env_var_name).ok()
+    }
+
+    fn get_project_api_url(&self, project_path: &str) -> String {
+        format!("{}/api/v4/projects/{}", get_base_url(&self.domain), project_path.replace("/", "%2F"))
+    }
+}
+impl SoftwareHub for GitLab {
+    fn get_name(&self) -> String {
+        format!("gitlab:{}", self.domain)
+    }
+
+    fn list_projects(&self, handle_projects: &mut dyn FnMut(Vec<panbuild::projects::SoftwareProject>)) -> Result<(), String> {
+        log::info!("Getting all projects from GitLab instance at {}.", self.domain);
+        // See https://docs.gitlab.com/ee/api/#oauth2-tokens
+        // for documentation on OAuth authentication.
+        let token = match self.get_token() {
+            Some(t) => t,
+            None => {
+                return Err(format!(
+                    "No GitLab API token located at {} for instance at {}.",
+                    self.token_env_var_name, self.domain
+                ))
+            }
+        };
+        let request = panbuild::utils::PagedRequest {
+            domain: self.domain.to_string(),
+            token: Some(token),
+            next_page_url: Some(format!("{}/api/v4/projects?per_page=100&simple=false", get_base_url(&self.domain))),
+        };
+
+        let mut crawler = crate::crawler::Crawler::new(header::HeaderMap::new());
+        let checkpoint_name = format!("gitlab-{}", self.domain.replace(|c: char| !c.is_ascii_alphanumeric() && c != '.', "-"));
+        crawler.checkpoint_path = Some(crate::crawler::get_checkpoint_path(&checkpoint_name));
+        crate::crawler::block_on(crawler.crawl(vec![request], parse_projects, |projects| handle_projects(projects)))
+    }
+
+    fn get_project(&self, project_id: &str) -> Result<Option<panbuild::projects::SoftwareProject>, String> {
+        let project_path = match get_project_path(&self.domain, project_id) {
+            Some(p) => p,
+            None => return Ok(None),
+        };
+        let project_url = self.get_project_api_url(&project_path);
+        let crawler = crate::crawler::Crawler::new(header::HeaderMap::new());
+        let token = self.get_token();
+        let page = match crate::crawler::block_on(crawler.get_page_if_exists(&format!("{}?license=true", project_url), token.as_deref()))? {
+            Some(p) => p,
+            None => return Ok(None),
+        };
+        let gitlab_project: GitLabProject = match serde_yaml::from_str(&page.content) {
+            Ok(p) => p,
+            Err(e) => return Err(format!("Could not parse GitLab project {}: {}.", project_path, e)),
+        };
+        let mut project = gitlab_project.to_software_project();
+        if let Err(e) = self.fill_project_details(&mut project) {
+            log::warn!("Could not get the languages of GitLab project {}: {}", project_path, e);
+        }
+        Ok(Some(project))
+    }
+
+    // The listed projects have no languages, and no license since the license
+    // is only returned when getting a single project.
+    fn fill_project_details(&self, project: &mut panbuild::projects::SoftwareProject) -> Result<(), String> {
+        let project_path = match get_project_path(&self.domain, &project.id) {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        let project_url = self.get_project_api_url(&project_path);
+        let crawler = crate::crawler::Crawler::new(header::HeaderMap::new());
+        let token = self.get_token();
+        if project.license.is_none() {
+            let license_url = format!("{}?license=true", project_url);
+            if let Some(page) = crate::crawler::block_on(crawler.get_page_if_exists(&license_url, token.as_deref()))? {
+                match serde_yaml::from_str::<GitLabProject>(&page.content) {
+                    Ok(p) => project.license = p.to_software_project().license,
+                    Err(e) => return Err(format!("Could not parse GitLab project {}: {}.", project_path, e)),
+                };
+            }
+        }
+        let languages_url = format!("{}/languages", project_url);
+        if let Some(page) = crate::crawler::block_on(crawler.get_page_if_exists(&languages_url, token.as_deref()))? {
+            project.set_languages(parse_languages(&page.content)?);
+        }
+        Ok(())
+    }
+
+    // The versions are the releases of the project, with their archive. The tags
+    // of the project are used if the project does not publish releases.
+    fn get_versions(&self, project: &panbuild::projects::SoftwareProject) -> Result<Vec<panbuild::projects::ProjectVersion>, String> {
+        let project_path = match get_project_path(&self.domain, &project.id) {
+            Some(p) => p,
+            None => return Ok(vec![]),
+        };
+        let releases_url = format!("{}/releases?per_page=100", self.get_project_api_url(&project_path));
+        let crawler = crate::crawler::Crawler::new(header::HeaderMap::new());
+        let token = self.get_token();
+        let page = match crate::crawler::block_on(crawler.get_page_if_exists(&releases_url, token.as_deref()))? {
+            Some(p) => p,
+            None => return Ok(vec![]),
+        };
+        let versions = parse_releases(&project.id, &page.content)?;
+        if !versions.is_empty() {
+            return Ok(versions);
+        }
+        let repo_url = match project.vcs_urls.first() {
+            Some(u) => u.to_string(),
+            None => format!("{}/{}.git", get_base_url(&self.domain), project_path),
+        };
+        let tags = panbuild::utils::get_remote_git_repo_tags(&repo_url)?;
+        Ok(panbuild::projects::get_versions_from_tags(&project.id, &repo_url, tags))
+    }
+}
+
+// Gets the name of the environment variable containing the API token of an instance.
+// Instances that are not known use a variable derived from their domain, for example
+// PB_GITLAB_TOKEN_GITLAB_EXAMPLE_COM for gitlab.example.com.
+pub fn get_token_env_var_name(domain: &str) -> String {
+    if let Some((_, token_env_var_name)) = KNOWN_INSTANCES.iter().find(|(d, _)| *d == domain) {
+        return token_env_var_name.to_string();
+    }
+    let domain = domain.split("://").last().unwrap_or(domain);
+    let domain: String = domain
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    format!("PB_GITLAB_TOKEN_{}", domain)
+}
+
+// Gets the path of a project (its namespace and name) from its id. Since the id is
+// the reverse DNS of the repo url, the namespaces of the project are separated by dots,
+// so projects with dots in their name or namespace can't be found from their id.
+fn get_project_path(domain: &str, project_id: &str) -> Option<String> {
+    let domain = domain.split("://").last().unwrap_or(domain);
+    let reversed_domain: Vec<&str> = domain.split('.').rev().collect();
+    let project_path = project_id.strip_prefix(&format!("{}.", reversed_domain.join(".")))?;
+    Some(project_path.replace(".", "/"))
+}
 
 // GitLab API described here
 // https://docs.gitlab.com/ee/api/projects.html
@@ -28,6 +198,12 @@ pub struct GitLabProject {
     // If the project is a fork, and you provide a valid token to authenticate,
     // the forked_from_project field appears in the response.
     pub forked_from_project: Option<GitLabParentProject>,
+    pub archived: Option<bool>,
+    // Only returned to the users that can administer the project.
+    pub mirror: Option<bool>,
+    pub web_url: Option<String>,
+    // Only returned when getting a single project with the license parameter.
+    pub license: Option<GitLabLicense>,
 }
 impl GitLabProject {
     pub fn to_software_project(self) -> panbuild::projects::SoftwareProject {
@@ -38,113 +214,223 @@ impl GitLabProject {
         project.description = self.description.unwrap_or("".to_string());
         project.vcs_urls.push(self.http_url_to_repo);
         project.keywords = self.tag_list;
+        if let Some(web_url) = self.web_url {
+            project.web_urls.push(web_url);
+        }
+        if let Some(license) = self.license {
+            project.license = Some(panbuild::manifests::manifest::License::from_spdx_id(&license.key));
+        }
+        project.is_archived = self.archived.unwrap_or(false);
+        project.is_fork = self.forked_from_project.is_some();
+        project.is_mirror = self.mirror.unwrap_or(false);
         project
     }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct GitLabParentProject {
-    pub id: String,
+pub struct GitLabLicense {
+    // The lowercase SPDX identifier of the license.
+    pub key: String,
     pub name: String,
 }
 
-pub fn get_and_add_repos(domain: &str, token_env_var_name: &str, db: &mut panbuild::db::Database) {
-    log::info!("Getting all projects from GitLab instance at {}.", domain);
-    let mut request = panbuild::utils::PagedRequest {
-        domain: domain.to_string(),
-        token: None,
-        next_page_url: None,
-    };
-    if let Ok(token) = env::var(token_env_var_name) {
-        // See https://docs.gitlab.com/ee/api/#oauth2-tokens
-        // for documentation on OAuth authentication.
-        request.token = Some(token);
-    } else {
-        log::warn!("No GitLab API token located at {} for instance at {}. Aborting.", token_env_var_name, domain);
-        return;
-    }
-    let mut paged_response = get_repos(request);
-
-    let mut projects = paged_response.results;
-    while projects.len() > 0 {
-        for project in projects {
-            log::debug!("Adding project {}.", &project.name);
-            db.add_project(project);
-        }
-
-        if paged_response.next_page_url.is_none() {
-            break;
+// See https://docs.gitlab.com/ee/api/releases/
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitLabRelease {
+    pub tag_name: String,
+    pub upcoming_release: Option<bool>,
+    pub assets: GitLabReleaseAssets,
+}
+impl GitLabRelease {
+    // Gets the url of the archive of the release. The archives linked to the release are
+    // preferred over the archives generated by GitLab from the tag.
+    pub fn get_archive_url(&self) -> Option<String> {
+        for link in &self.assets.links {
+            if ARCHIVE_EXTENSIONS.iter().any(|e| link.url.ends_with(e)) {
+                return Some(link.url.to_string());
+            }
         }
-
-        paged_response = get_repos(panbuild::utils::PagedRequest {
-            domain: domain.to_string(),
-            token: paged_response.token,
-            next_page_url: paged_response.next_page_url,
-        });
-        projects = paged_response.results;
+        self.assets.sources.iter().find(|s| s.format == "tar.gz").map(|s| s.url.to_string())
     }
 }
 
-pub fn get_repos(request: panbuild::utils::PagedRequest) -> panbuild::utils::PagedResponse {
-    let mut current_url = format!("https://{}/api/v4/projects?per_page=100&simple=false", request.domain);
-    if let Some(url) = request.next_page_url {
-        current_url = url;
-    }
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitLabReleaseAssets {
+    pub sources: Vec<GitLabReleaseSource>,
+    #[serde(default)]
+    pub links: Vec<GitLabReleaseLink>,
+}
 
-    let mut projects: Vec<panbuild::projects::SoftwareProject> = vec![];
-    let default_response = panbuild::utils::PagedResponse {
-        results: vec![],
-        token: None,
-        next_page_url: None,
-    };
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitLabReleaseSource {
+    pub format: String,
+    pub url: String,
+}
 
-    let mut headers = header::HeaderMap::new();
-    let auth_header_value = format!("Bearer {}", request.token.as_ref().unwrap());
-    let auth_header = header::HeaderValue::from_str(&auth_header_value.to_string()).unwrap();
-    headers.insert("Authorization", auth_header);
-    let client = reqwest::blocking::Client::builder().default_headers(headers).build().unwrap();
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitLabReleaseLink {
+    pub name: String,
+    pub url: String,
+}
 
-    log::info!("Getting GitLab projects page at {}.", current_url);
-    // TODO make this really asynchronous with async/await.
-    let mut response = match client.get(&current_url).send() {
-        Ok(r) => r,
-        Err(e) => {
-            log::error!("Could not fetch GitLab url {}: {}.", current_url, e);
-            return default_response;
-        }
-    };
+const ARCHIVE_EXTENSIONS: &[&str] = &[".tar.xz", ".tar.gz", ".tar.bz2", ".tgz", ".zip"];
 
-    if response.status().as_u16() == 204 {
-        return default_response;
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitLabParentProject {
+    pub id: String,
+    pub name: String,
+}
+
+// Gets the base url of a GitLab instance. The domain can also
+// include the scheme, for example when testing with a local server.
+pub fn get_base_url(domain: &str) -> String {
+    if domain.contains("://") {
+        return domain.trim_end_matches('/').to_string();
     }
+    format!("https://{}", domain)
+}
 
-    let response_headers = response.headers();
+// Parses the languages of a project, which are returned with their percentage of the code.
+pub fn parse_languages(page_content: &str) -> Result<Vec<String>, String> {
+    let languages: BTreeMap<String, f64> = match serde_json::from_str(page_content) {
+        Ok(l) => l,
+        Err(e) => return Err(format!("Could not parse GitLab languages {}.", e)),
+    };
+    let mut languages: Vec<(String, f64)> = languages.into_iter().collect();
+    languages.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+    Ok(languages.into_iter().map(|(l, _)| l).collect())
+}
 
-    let link_header = match &response_headers.get("link") {
-        Some(h) => h.to_str().unwrap(),
-        None => "",
+// Parses the releases of a project into versions of the project. Upcoming releases are ignored.
+pub fn parse_releases(project_id: &str, page_content: &str) -> Result<Vec<panbuild::projects::ProjectVersion>, String> {
+    let releases: Vec<GitLabRelease> = match serde_json::from_str(page_content) {
+        Ok(r) => r,
+        Err(e) => return Err(format!("Could not parse GitLab releases {}.", e)),
     };
-    let next_page_url = panbuild::utils::get_next_page_url(link_header);
+    let mut versions: Vec<panbuild::projects::ProjectVersion> = vec![];
+    for release in releases {
+        if release.upcoming_release.unwrap_or(false) {
+            continue;
+        }
+        let archive_url = match release.get_archive_url() {
+            Some(u) => u,
+            None => continue,
+        };
+        if let Some(version) = panbuild::projects::get_release_version(project_id, &release.tag_name, &archive_url) {
+            versions.push(version);
+        }
+    }
+    Ok(versions)
+}
 
-    let gitlab_projects: Vec<GitLabProject> = match serde_yaml::from_str(&response.text().unwrap()) {
+// Parses a page of projects returned by the GitLab API. Forks are kept, but flagged as such.
+pub fn parse_projects(page_content: &str) -> Result<Vec<panbuild::projects::SoftwareProject>, String> {
+    let gitlab_projects: Vec<GitLabProject> = match serde_yaml::from_str(page_content) {
         Ok(p) => p,
-        Err(e) => {
-            log::error!("Could not parse gitlab projects {}.", e);
-            return default_response;
-        }
+        Err(e) => return Err(format!("Could not parse gitlab projects {}.", e)),
     };
+
+    let mut projects: Vec<panbuild::projects::SoftwareProject> = vec![];
     for gitlab_project in gitlab_projects {
-        if let Some(parent_project) = gitlab_project.forked_from_project {
-            log::debug!("Skipping forked project {}.", &gitlab_project.name);
-            continue;
-        }
         log::debug!("Adding GitLab project {}.", gitlab_project.name);
         projects.push(gitlab_project.to_software_project());
     }
+    Ok(projects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_get_token_env_var_name() {
+        assert_eq!(get_token_env_var_name("gitlab.gnome.org"), "PB_GNOME_GITLAB_TOKEN");
+        assert_eq!(get_token_env_var_name("gitlab.example.com"), "PB_GITLAB_TOKEN_GITLAB_EXAMPLE_COM");
+    }
 
-    panbuild::utils::PagedResponse {
-        results: projects,
-        token: request.token,
-        next_page_url: next_page_url,
+    #[test]
+    pub fn test_get_project_path() {
+        assert_eq!(get_project_path("gitlab.gnome.org", "org.gnome.gitlab.GNOME.gtk").unwrap(), "GNOME/gtk");
+        assert!(get_project_path("gitlab.gnome.org", "com.github.louib.panbuild").is_none());
+    }
+
+    #[test]
+    pub fn test_parse_projects() {
+        let projects = parse_projects(
+            r###"[
+              {
+                "id": "3",
+                "name": "gtk",
+                "name_with_namespace": "someone / gtk",
+                "created_at": "2021-05-01T10:00:00.000Z",
+                "last_activity_at": "2021-05-02T10:00:00.000Z",
+                "forks_count": 0,
+                "star_count": 0,
+                "ssh_url_to_repo": "git@gitlab.gnome.org:someone/gtk.git",
+                "http_url_to_repo": "https://gitlab.gnome.org/someone/gtk.git",
+                "readme_url": "https://gitlab.gnome.org/someone/gtk/-/blob/master/README.md",
+                "tag_list": [],
+                "forked_from_project": {"id": "1", "name": "gtk"},
+                "archived": true
+              }
+            ]"###,
+        )
+        .unwrap();
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].id, "org.gnome.gitlab.someone.gtk");
+        assert!(projects[0].is_fork);
+        assert!(projects[0].is_archived);
+    }
+
+    #[test]
+    pub fn test_parse_releases() {
+        let versions = parse_releases(
+            "org.gnome.gitlab.GNOME.gtk",
+            r###"[
+              {
+                "tag_name": "4.3.0",
+                "upcoming_release": false,
+                "assets": {
+                  "sources": [
+                    {"format": "zip", "url": "https://gitlab.gnome.org/GNOME/gtk/-/archive/4.3.0/gtk-4.3.0.zip"},
+                    {"format": "tar.gz", "url": "https://gitlab.gnome.org/GNOME/gtk/-/archive/4.3.0/gtk-4.3.0.tar.gz"}
+                  ],
+                  "links": [
+                    {"name": "tarball", "url": "https://download.gnome.org/sources/gtk/4.3/gtk-4.3.0.tar.xz"}
+                  ]
+                }
+              },
+              {
+                "tag_name": "4.2.1",
+                "assets": {
+                  "sources": [
+                    {"format": "tar.gz", "url": "https://gitlab.gnome.org/GNOME/gtk/-/archive/4.2.1/gtk-4.2.1.tar.gz"}
+                  ]
+                }
+              },
+              {
+                "tag_name": "next",
+                "upcoming_release": true,
+                "assets": {"sources": []}
+              }
+            ]"###,
+        )
+        .unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].name, "4.3.0");
+        assert_eq!(versions[0].url, "https://download.gnome.org/sources/gtk/4.3/gtk-4.3.0.tar.xz");
+        assert_eq!(versions[0].url_type, panbuild::modules::SourceType::Tarball);
+        assert_eq!(versions[1].tag, "4.2.1");
+        assert_eq!(versions[1].url, "https://gitlab.gnome.org/GNOME/gtk/-/archive/4.2.1/gtk-4.2.1.tar.gz");
+    }
+
+    #[test]
+    pub fn test_parse_languages() {
+        let languages = parse_languages(r#"{"C": 12.5, "Rust": 80.1, "Meson": 7.4}"#).unwrap();
+        assert_eq!(languages, vec!["Rust", "C", "Meson"]);
+
+        let mut project = panbuild::projects::SoftwareProject::default();
+        project.set_languages(languages);
+        assert_eq!(project.build_systems, vec!["cargo", "meson"]);
     }
 }
@@ -1,10 +1,227 @@
+use std::env;
+use std::fs;
+use std::sync::mpsc;
+
 use serde::{Deserialize, Serialize};
 
+use panbuild::projects::{ProjectVersion, SoftwareProject};
+
+/// The hubs to import the projects from, when no hub configuration is found.
+pub const DEFAULT_HUB_SPECS: &[&str] = &[
+    "gitlab:gitlab.gnome.org",
+    "gitlab:source.puri.sm",
+    "gitlab:salsa.debian.org",
+    "gitlab:invent.kde.org",
+    "gitlab:code.videolan.org",
+    "gitlab:gitlab.haskell.org",
+    "gitlab:devel.trisquel.info",
+    "gitlab:gitlab.freedesktop.org",
+    "gitea:codeberg.org",
+    "cgit:https://git.savannah.gnu.org/cgit/",
+    "gitweb:https://sourceware.org/git/",
+];
+
+/// Common interface of the software hubs.
+pub trait SoftwareHub {
+    /// The name of the hub, in the format used by `Hub::from_spec`.
+    fn get_name(&self) -> String;
+
+    /// Lists all the projects of the hub. The projects are passed to `handle_projects`
+    /// as soon as they are fetched, since some hubs have millions of projects.
+    fn list_projects(&self, handle_projects: &mut dyn FnMut(Vec<SoftwareProject>)) -> Result<(), String>;
+
+    /// Gets a project of the hub from its id, or None if the hub does not have that project.
+    fn get_project(&self, project_id: &str) -> Result<Option<SoftwareProject>, String>;
+
+    /// Fills the details of a listed project that are not returned when listing the projects,
+    /// like all the languages of the project. By default, the listed projects are complete.
+    fn fill_project_details(&self, _project: &mut SoftwareProject) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Gets the known versions of a project. By default, the versions
+    /// are the tags of the git repository of the project.
+    fn get_versions(&self, project: &SoftwareProject) -> Result<Vec<ProjectVersion>, String> {
+        let repo_url = match project.vcs_urls.first() {
+            Some(u) => u,
+            None => return Ok(vec![]),
+        };
+        let tags = panbuild::utils::get_remote_git_repo_tags(repo_url)?;
+        Ok(panbuild::projects::get_versions_from_tags(&project.id, repo_url, tags))
+    }
+}
+
+/// Gets the id of a project from the url of its git repository. Unlike
+/// `panbuild::utils::repo_url_to_reverse_dns`, the url does not have to end with `.git`,
+/// since some forges serve their repositories without the extension.
+///```
+///let project_id = pb_tools::hubs::hub::get_project_id("https://git.sr.ht/~sircmpwn/scdoc");
+///assert_eq!(project_id, "ht.sr.git.sircmpwn.scdoc");
+///```
+pub fn get_project_id(repo_url: &str) -> String {
+    let repo_url = repo_url.trim_end_matches('/').trim_end_matches(".git").replace('~', "");
+    panbuild::utils::repo_url_to_reverse_dns(&format!("{}.git", repo_url))
+}
+
 /// Main representation for a software hub. A software hub is any online service
 /// that offers a web and/or vcs access to software projects. Software projects
 /// can be discovered through API calls, manifest file parsing or yet other
 /// means.
 #[derive(Debug, Serialize, Deserialize)]
-pub enum SoftwareHub {
+pub enum Hub {
     GitHub(crate::hubs::github::GitHub),
+    GitLab(crate::hubs::gitlab::GitLab),
+    Homebrew(crate::hubs::brew::Homebrew),
+    Debian(crate::hubs::deb::DebianPackagesHub),
+    Gitea(crate::hubs::gitea::Gitea),
+    SourceHut(crate::hubs::sourcehut::SourceHut),
+    RepoIndex(crate::hubs::cgit::RepoIndex),
+}
+impl Hub {
+    /// Parses a hub from its specification, which is the type of the hub optionally followed
+    /// by a colon and an argument. The supported specifications are `github`, `github:<org>`,
+    /// `gitlab:<domain>`, `gitea:<domain>` (or `forgejo:<domain>`), `sourcehut:~<owner>`,
+    /// `sourcehut:<domain>/~<owner>`, `cgit:<index url>`, `gitweb:<index url>`, `brew`,
+    /// `debian:<suite>` and `debian:<path>`, where `<path>` is a local `Sources` or `Sources.xz` index.
+    ///```
+    ///let hub = pb_tools::hubs::hub::Hub::from_spec("gitlab:gitlab.gnome.org").unwrap();
+    ///assert_eq!(hub.get_hub().get_name(), "gitlab:gitlab.gnome.org");
+    ///let hub = pb_tools::hubs::hub::Hub::from_spec("cgit:https://git.savannah.gnu.org/cgit/").unwrap();
+    ///assert_eq!(hub.get_hub().get_name(), "cgit:https://git.savannah.gnu.org/cgit/");
+    ///assert!(pb_tools::hubs::hub::Hub::from_spec("sourceforge").is_err());
+    ///```
+    pub fn from_spec(spec: &str) -> Result<Hub, String> {
+        let (hub_type, argument) = match spec.find(':') {
+            Some(i) => (&spec[..i], Some(&spec[i + 1..])),
+            None => (spec, None),
+        };
+        match (hub_type, argument) {
+            ("github", None) => Ok(Hub::GitHub(crate::hubs::github::GitHub { org: None })),
+            ("github", Some(org)) if !org.is_empty() => Ok(Hub::GitHub(crate::hubs::github::GitHub { org: Some(org.to_string()) })),
+            ("gitlab", Some(domain)) if !domain.is_empty() => Ok(Hub::GitLab(crate::hubs::gitlab::GitLab::new(domain))),
+            ("gitea", Some(domain)) | ("forgejo", Some(domain)) if !domain.is_empty() => Ok(Hub::Gitea(crate::hubs::gitea::Gitea::new(domain))),
+            ("sourcehut", Some(owner)) if owner.contains('~') => Ok(Hub::SourceHut(crate::hubs::sourcehut::SourceHut::from_owner_spec(owner))),
+            ("cgit", Some(index_url)) if index_url.starts_with("http") => Ok(Hub::RepoIndex(crate::hubs::cgit::RepoIndex::new(
+                crate::hubs::cgit::RepoIndexFormat::Cgit,
+                index_url,
+            ))),
+            ("gitweb", Some(index_url)) if index_url.starts_with("http") => Ok(Hub::RepoIndex(crate::hubs::cgit::RepoIndex::new(
+                crate::hubs::cgit::RepoIndexFormat::Gitweb,
+                index_url,
+            ))),
+            ("brew", None) | ("homebrew", None) => Ok(Hub::Homebrew(crate::hubs::brew::Homebrew {})),
+            ("debian", None) => Ok(Hub::Debian(crate::hubs::deb::DebianPackagesHub::new(crate::hubs::deb::DEFAULT_SUITE))),
+            ("debian", Some(path)) if path.starts_with('/') || path.starts_with('.') => {
+                Ok(Hub::Debian(crate::hubs::deb::DebianPackagesHub::from_sources_path(path)))
+            }
+            ("debian", Some(suite)) if !suite.is_empty() => Ok(Hub::Debian(crate::hubs::deb::DebianPackagesHub::new(suite))),
+            _ => Err(format!("Invalid hub {}.", spec)),
+        }
+    }
+
+    pub fn get_hub(&self) -> &dyn SoftwareHub {
+        match self {
+            Hub::GitHub(hub) => hub,
+            Hub::GitLab(hub) => hub,
+            Hub::Homebrew(hub) => hub,
+            Hub::Debian(hub) => hub,
+            Hub::Gitea(hub) => hub,
+            Hub::SourceHut(hub) => hub,
+            Hub::RepoIndex(hub) => hub,
+        }
+    }
+}
+
+/// Gets the path of the hubs configuration file, which contains the list of the specifications
+/// of the hubs to import the projects from.
+pub fn get_hubs_config_path() -> String {
+    if let Ok(path) = env::var("PB_HUBS_CONFIG_PATH") {
+        return path;
+    }
+    format!("{}/hubs.yaml", panbuild::cache::get_data_dir_path())
+}
+
+/// Gets the hubs listed in the hubs configuration file, or the default hubs if there is no configuration file.
+pub fn get_configured_hubs() -> Result<Vec<Hub>, String> {
+    let config_path = get_hubs_config_path();
+    let hub_specs: Vec<String> = match fs::read_to_string(&config_path) {
+        Ok(config_content) => match serde_yaml::from_str(&config_content) {
+            Ok(s) => s,
+            Err(e) => return Err(format!("Invalid hubs configuration at {}: {}", config_path, e)),
+        },
+        Err(_) => DEFAULT_HUB_SPECS.iter().map(|s| s.to_string()).collect(),
+    };
+    hub_specs.iter().map(|s| Hub::from_spec(s)).collect()
+}
+
+// Gets the details and the versions of a listed project. This requires additional
+// requests for every project, so the errors are only logged.
+fn get_project_versions(software_hub: &dyn SoftwareHub, project: &mut SoftwareProject) -> Vec<ProjectVersion> {
+    if let Err(e) = software_hub.fill_project_details(project) {
+        log::warn!("Could not get the details of project {}: {}", project.id, e);
+    }
+    match software_hub.get_versions(project) {
+        Ok(v) => v,
+        Err(e) => {
+            log::warn!("Could not get the versions of project {}: {}", project.id, e);
+            vec![]
+        }
+    }
+}
+
+/// Imports the projects of the hubs in the database. The hubs are crawled concurrently.
+/// The projects already in the database are merged with the imported projects. When
+/// `with_versions` is true, the details and the versions of every project are also imported.
+/// Returns the names of the hubs that could not be imported completely, with the error.
+pub fn import_projects(hubs: Vec<Hub>, db: &mut panbuild::db::Database, with_versions: bool) -> Vec<(String, String)> {
+    let (sender, receiver) = mpsc::channel::<Vec<(SoftwareProject, Vec<ProjectVersion>)>>();
+    let mut crawls = vec![];
+    for hub in hubs {
+        let sender = sender.clone();
+        crawls.push(std::thread::spawn(move || {
+            let software_hub = hub.get_hub();
+            log::info!("Importing projects from {}.", software_hub.get_name());
+            let result = software_hub.list_projects(&mut |projects| {
+                let projects = projects
+                    .into_iter()
+                    .map(|mut p| match with_versions {
+                        true => {
+                            let versions = get_project_versions(software_hub, &mut p);
+                            (p, versions)
+                        }
+                        false => (p, vec![]),
+                    })
+                    .collect();
+                sender.send(projects).unwrap_or(())
+            });
+            (software_hub.get_name(), result)
+        }));
+    }
+    // The channel is closed once all the crawls are done.
+    drop(sender);
+
+    for projects in receiver {
+        for (project, versions) in projects {
+            if !versions.is_empty() {
+                db.add_project_versions(&project.id, versions);
+            }
+            if db.has_project(&project.id) {
+                log::debug!("Updating project {}.", &project.name);
+                db.update_project(&project);
+            } else {
+                log::debug!("Adding project {}.", &project.name);
+                db.add_project(project);
+            }
+        }
+    }
+
+    let mut errors: Vec<(String, String)> = vec![];
+    for crawl in crawls {
+        match crawl.join() {
+            Ok((_, Ok(()))) => continue,
+            Ok((hub_name, Err(e))) => errors.push((hub_name, e)),
+            Err(_) => errors.push(("unknown".to_string(), "The crawl panicked.".to_string())),
+        };
+    }
+    errors
 }
@@ -0,0 +1,234 @@
+use std::env;
+
+use reqwest::header;
+
+use serde::{Deserialize, Serialize};
+
+use crate::hubs::hub::SoftwareHub;
+
+pub const DEFAULT_DOMAIN: &str = "git.sr.ht";
+pub const TOKEN_ENV_VAR_NAME: &str = "PB_SOURCEHUT_TOKEN";
+
+// The GraphQL API of SourceHut does not list all the repositories of an instance,
+// so the repositories are listed one owner at a time.
+// See https://man.sr.ht/git.sr.ht/graphql.md
+const REPOSITORIES_QUERY: &str = "query repositories($username: String!, $cursor: Cursor) {
+  user(username: $username) {
+    repositories(cursor: $cursor) {
+      cursor
+      results { name description visibility owner { canonicalName } }
+    }
+  }
+}";
+
+const REPOSITORY_QUERY: &str = "query repository($username: String!, $name: String!) {
+  user(username: $username) {
+    repository(name: $name) { name description visibility owner { canonicalName } }
+  }
+}";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SourceHut {
+    pub domain: String,
+    // The user or organization owning the repositories, without the leading ~.
+    pub owner: String,
+}
+impl SourceHut {
+    pub fn new(domain: &str, owner: &str) -> SourceHut {
+        SourceHut {
+            domain: domain.to_string(),
+            owner: owner.trim_start_matches('~').to_string(),
+        }
+    }
+
+    // Parses the owner of the repositories, optionally prefixed by the domain of the instance,
+    // for example ~sircmpwn or git.sr.ht/~sircmpwn.
+    pub fn from_owner_spec(owner_spec: &str) -> SourceHut {
+        match owner_spec.rfind('/') {
+            Some(i) => SourceHut::new(&owner_spec[..i], &owner_spec[i + 1..]),
+            None => SourceHut::new(DEFAULT_DOMAIN, owner_spec),
+        }
+    }
+
+    fn get_query_url(&self) -> String {
+        format!("{}/query", crate::hubs::gitlab::get_base_url(&self.domain))
+    }
+
+    fn query(&self, query: &str, variables: serde_json::Value) -> Result<String, String> {
+        // The GraphQL API can't be used anonymously.
+        let token = match env::var(TOKEN_ENV_VAR_NAME) {
+            Ok(t) => t,
+            Err(_) => return Err(format!("No SourceHut API token located at {}.", TOKEN_ENV_VAR_NAME)),
+        };
+        let body = serde_json::json!({
+            "query": query,
+            "variables": variables,
+        });
+        let crawler = crate::crawler::Crawler::new(header::HeaderMap::new());
+        let page = crate::crawler::block_on(crawler.post_page(&self.get_query_url(), Some(&token), &body.to_string()))?;
+        Ok(page.content)
+    }
+}
+impl SoftwareHub for SourceHut {
+    fn get_name(&self) -> String {
+        match self.domain == DEFAULT_DOMAIN {
+            true => format!("sourcehut:~{}", self.owner),
+            false => format!("sourcehut:{}/~{}", self.domain, self.owner),
+        }
+    }
+
+    fn list_projects(&self, handle_projects: &mut dyn FnMut(Vec<panbuild::projects::SoftwareProject>)) -> Result<(), String> {
+        log::info!("Getting all the repositories of ~{} from {}.", self.owner, self.domain);
+        let mut cursor: Option<String> = None;
+        loop {
+            let variables = serde_json::json!({"username": self.owner, "cursor": cursor});
+            let response_content = self.query(REPOSITORIES_QUERY, variables)?;
+            let (projects, next_cursor) = parse_repositories(&self.domain, &response_content)?;
+            handle_projects(projects);
+            cursor = match next_cursor {
+                Some(c) => Some(c),
+                None => return Ok(()),
+            };
+        }
+    }
+
+    fn get_project(&self, project_id: &str) -> Result<Option<panbuild::projects::SoftwareProject>, String> {
+        let owner_id = crate::hubs::hub::get_project_id(&format!("https://{}/~{}", self.domain, self.owner));
+        let repository_name = match project_id.strip_prefix(&format!("{}.", owner_id)) {
+            Some(n) => n,
+            None => return Ok(None),
+        };
+        let variables = serde_json::json!({"username": self.owner, "name": repository_name});
+        let response_content = self.query(REPOSITORY_QUERY, variables)?;
+        let response: GraphQLResponse<SourceHutUserRepository> = parse_response(&response_content)?;
+        let repository = match response.data.and_then(|d| d.user).and_then(|u| u.repository) {
+            Some(r) => r,
+            None => return Ok(None),
+        };
+        Ok(repository.to_software_project(&self.domain))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GraphQLResponse<T> {
+    pub data: Option<T>,
+    pub errors: Option<Vec<GraphQLError>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GraphQLError {
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SourceHutUserRepositories {
+    pub user: Option<SourceHutUser<SourceHutRepositories>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SourceHutUserRepository {
+    pub user: Option<SourceHutUser<SourceHutRepository>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SourceHutUser<T> {
+    #[serde(alias = "repositories")]
+    pub repository: Option<T>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SourceHutRepositories {
+    // The cursor of the next page, if any.
+    pub cursor: Option<String>,
+    pub results: Vec<SourceHutRepository>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SourceHutRepository {
+    pub name: String,
+    pub description: Option<String>,
+    // One of PUBLIC, UNLISTED or PRIVATE.
+    pub visibility: String,
+    pub owner: SourceHutOwner,
+}
+impl SourceHutRepository {
+    // Only the public repositories are converted to projects.
+    pub fn to_software_project(self, domain: &str) -> Option<panbuild::projects::SoftwareProject> {
+        if self.visibility != "PUBLIC" {
+            return None;
+        }
+        let repo_url = format!("{}/{}/{}", crate::hubs::gitlab::get_base_url(domain), self.owner.canonical_name, self.name);
+        let mut project = panbuild::projects::SoftwareProject::default();
+        project.id = crate::hubs::hub::get_project_id(&repo_url);
+        project.name = self.name;
+        project.description = self.description.unwrap_or_default();
+        project.web_urls.push(repo_url.to_string());
+        project.vcs_urls.push(repo_url);
+        Some(project)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SourceHutOwner {
+    // The name of the owner, prefixed by ~.
+    #[serde(rename = "canonicalName")]
+    pub canonical_name: String,
+}
+
+fn parse_response<T: serde::de::DeserializeOwned>(response_content: &str) -> Result<GraphQLResponse<T>, String> {
+    let response: GraphQLResponse<T> = match serde_json::from_str(response_content) {
+        Ok(r) => r,
+        Err(e) => return Err(format!("Could not parse SourceHut response {}.", e)),
+    };
+    if let Some(errors) = &response.errors {
+        if let Some(error) = errors.first() {
+            return Err(format!("Error returned by the SourceHut API: {}", error.message));
+        }
+    }
+    Ok(response)
+}
+
+// Parses a page of the repositories of an owner, and returns the cursor of the next page.
+pub fn parse_repositories(domain: &str, response_content: &str) -> Result<(Vec<panbuild::projects::SoftwareProject>, Option<String>), String> {
+    let response: GraphQLResponse<SourceHutUserRepositories> = parse_response(response_content)?;
+    let repositories = match response.data.and_then(|d| d.user).and_then(|u| u.repository) {
+        Some(r) => r,
+        None => return Err("The SourceHut user was not found.".to_string()),
+    };
+    let projects = repositories.results.into_iter().filter_map(|r| r.to_software_project(domain)).collect();
+    Ok((projects, repositories.cursor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_parse_repositories() {
+        let (projects, cursor) = parse_repositories(DEFAULT_DOMAIN, include_str!("../../tests/fixtures/hubs/sourcehut-repositories.json")).unwrap();
+        assert_eq!(cursor.unwrap(), "Mg==");
+        assert_eq!(projects.len(), 2);
+        assert_eq!(projects[0].id, "ht.sr.git.sircmpwn.scdoc");
+        assert_eq!(projects[0].vcs_urls, vec!["https://git.sr.ht/~sircmpwn/scdoc"]);
+        assert_eq!(projects[1].name, "hare");
+        assert_eq!(projects[1].description, "");
+    }
+
+    #[test]
+    pub fn test_parse_repositories_error() {
+        let response_content = r#"{"data": null, "errors": [{"message": "Authentication error"}]}"#;
+        assert!(parse_repositories(DEFAULT_DOMAIN, response_content).is_err());
+    }
+
+    #[test]
+    pub fn test_from_owner_spec() {
+        let sourcehut = SourceHut::from_owner_spec("~sircmpwn");
+        assert_eq!(sourcehut.domain, "git.sr.ht");
+        assert_eq!(sourcehut.owner, "sircmpwn");
+        assert_eq!(sourcehut.get_name(), "sourcehut:~sircmpwn");
+
+        let sourcehut = SourceHut::from_owner_spec("git.example.com/~louib");
+        assert_eq!(sourcehut.domain, "git.example.com");
+        assert_eq!(sourcehut.get_name(), "sourcehut:git.example.com/~louib");
+    }
+}
@@ -1 +1,5 @@
+pub mod crawler;
+pub mod dedup;
+pub mod flathub;
+pub mod history;
 pub mod hubs;